@@ -0,0 +1,64 @@
+//! Checking the calibrated ADC against a known voltage on GPIO2.
+//!
+//! Wire two equal resistors (e.g. 10kΩ) in series between 3.3V and GND,
+//! with GPIO2 on their middle: that's 1650 mV, or measure the 3.3V rail
+//! with a multimeter and set EXPECTED_MV to half of it. The readings are
+//! logged with their error, calibrated and not, and should land within
+//! a few tens of mV on a chip with eFuse calibration data.
+
+use std::{thread, time::Duration};
+
+use buds::{
+    adc::{AnalogInput, Attenuation, Calibration},
+    board::take_peripherals,
+};
+use esp_idf_svc::hal::gpio::Gpio2;
+
+const EXPECTED_MV: i32 = 1650;
+
+// Anything further off means the wiring or the calibration is wrong.
+const TOLERANCE_MV: i32 = 50;
+
+// Readings averaged per measurement.
+const OVERSAMPLING: usize = 64;
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+
+    // 1650 mV is above the range of the smaller attenuations.
+    let mut input: AnalogInput<{ Attenuation::Db11.raw() }, Gpio2> =
+        AnalogInput::new(peripherals.adc1, peripherals.pins.gpio2).unwrap();
+    match input.calibration() {
+        Calibration::Efuse => log::info!("Calibrated from the eFuses"),
+        Calibration::DefaultVref => log::warn!("No eFuse data, assuming a 1100 mV reference"),
+        Calibration::Linear => log::warn!("No eFuse data, readings are uncalibrated"),
+    }
+
+    let full_scale = input.attenuation().full_scale_mv() as i32;
+    loop {
+        let raw = input.read_averaged(OVERSAMPLING).unwrap();
+        let mv = i32::from(input.raw_to_mv(raw));
+        let linear_mv = i32::from(raw) * full_scale / i32::from(buds::adc::MAX_RAW);
+
+        let error = mv - EXPECTED_MV;
+        let message = format!(
+            "Raw {}: {} mV (error {:+} mV, {:+.1}%), uncalibrated {} mV (error {:+} mV)",
+            raw,
+            mv,
+            error,
+            error as f32 * 100.0 / EXPECTED_MV as f32,
+            linear_mv,
+            linear_mv - EXPECTED_MV
+        );
+        if error.abs() <= TOLERANCE_MV {
+            log::info!("{}", message);
+        } else {
+            log::error!("{}", message);
+        }
+
+        thread::sleep(Duration::from_secs(1));
+    }
+}
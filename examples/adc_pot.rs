@@ -0,0 +1,50 @@
+//! Reading a potentiometer wired between 3.3V and GND, with its wiper on
+//! GPIO2.
+//!
+//! The pot is sampled as fast as the loop goes, to catch the extremes it
+//! was turned to, but the readings are only logged twice a second.
+
+use std::time::Duration;
+
+use buds::{
+    adc::{attenuation, raw_to_percent, AnalogInput},
+    board::take_peripherals,
+    log_every,
+};
+use esp_idf_svc::hal::gpio::Gpio2;
+
+// Readings averaged per sample, more is smoother but slower.
+const OVERSAMPLING: usize = 16;
+
+const LOG_INTERVAL: Duration = Duration::from_millis(500);
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+
+    // 11dB of attenuation so the whole 0-3.3V swing of the wiper is readable.
+    let mut pot: AnalogInput<{ attenuation::DB_11 }, Gpio2> =
+        AnalogInput::new(peripherals.adc1, peripherals.pins.gpio2).unwrap();
+
+    let (mut min, mut max) = (u16::MAX, u16::MIN);
+    loop {
+        let raw = pot.read_averaged(OVERSAMPLING).unwrap();
+        min = min.min(raw);
+        max = max.max(raw);
+
+        // Logging every pass would flood the console.
+        log_every!(
+            LOG_INTERVAL,
+            "Potentiometer: {} ({}%), range seen {}..={}",
+            raw,
+            raw_to_percent(raw),
+            min,
+            max
+        );
+
+        // Lets the idle task run and feed the watchdog.
+        std::thread::sleep(Duration::from_millis(1));
+    }
+}
@@ -0,0 +1,80 @@
+// This example checks that a 1 kHz alarm fires exactly once per period,
+// first with the default level triggered CPU interrupt and then with an
+// edge triggered one. Either way the timer raises a level interrupt, see
+// timer::IntrType, and the count should be 1000 per second; a level line
+// left asserted would show as far more, lost edges as fewer.
+
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use buds::{
+    prelude::*,
+    timer::{IntrType, IsrFlags},
+};
+
+const ALARM_HZ: u32 = 1000;
+const SECONDS: u32 = 5;
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    // The timer peripheral itself can't be edge triggered.
+    match TimerConfigBuilder::new().intr_type(IntrType::Edge).build() {
+        Err(TimerError::UnsupportedIntrType(_)) => log::info!("Edge config rejected, as expected"),
+        _ => log::error!("Edge config should have been rejected"),
+    }
+
+    let config = TimerConfigBuilder::new()
+        .divider(divider_for_hz(1_000_000).unwrap())
+        .intr_type(IntrType::Level)
+        .build()
+        .unwrap();
+    let mut timer = HwTimer::new(TimerId::Group0Timer0, config).unwrap();
+    timer.set_alarm_hz(ALARM_HZ as f32).unwrap();
+
+    for (name, flags) in [
+        ("level", IsrFlags::new()),
+        ("edge", IsrFlags::new().edge(true)),
+    ] {
+        let fires = Arc::new(AtomicU32::new(0));
+        let isr_fires = fires.clone();
+        timer
+            .on_alarm_with_flags(
+                move || {
+                    isr_fires.fetch_add(1, Ordering::Relaxed);
+                },
+                flags,
+            )
+            .unwrap();
+        timer.set_counter(0).unwrap();
+        timer.enable_interrupt().unwrap();
+
+        let started = Instant::now();
+        timer.start().unwrap();
+        thread::sleep(Duration::from_secs(SECONDS.into()));
+        timer.pause().unwrap();
+        let elapsed = started.elapsed();
+
+        // The sleep and the alarms aren't aligned, allow one off.
+        let fired = fires.load(Ordering::Relaxed);
+        let expected = (elapsed.as_secs_f32() * ALARM_HZ as f32) as u32;
+        if fired.abs_diff(expected) <= 1 {
+            log::info!("{} interrupt: {} alarms in {:?}", name, fired, elapsed);
+        } else {
+            log::error!(
+                "{} interrupt: {} alarms in {:?}, expected {}",
+                name,
+                fired,
+                elapsed,
+                expected
+            );
+        }
+    }
+}
@@ -0,0 +1,57 @@
+// This example showcases switching a running timer from periodic to
+// one-shot with set_auto_reload(): the alarms stop after the switch.
+//
+// It also checks that alarm_value() reads back the alarm as set. That
+// needs the timer peripheral, so it's checked here, on the chip, and not
+// by the host tests of the timer module.
+
+use std::{
+    sync::atomic::{AtomicU32, Ordering},
+    thread,
+    time::Duration,
+};
+
+use buds::prelude::*;
+
+static FIRES: AtomicU32 = AtomicU32::new(0);
+
+const PERIOD: Duration = Duration::from_millis(200);
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let config = TimerConfigBuilder::new()
+        .divider(divider_for_hz(1_000_000).unwrap())
+        .auto_reload(true)
+        .build()
+        .unwrap();
+    let mut timer = HwTimer::new(TimerId::Group0Timer0, config).unwrap();
+    timer.set_alarm_after(PERIOD).unwrap();
+    // 1 MHz counts one tick per microsecond.
+    assert_eq!(timer.alarm_value().unwrap(), PERIOD.as_micros() as u64);
+    timer
+        .on_alarm(|| {
+            FIRES.fetch_add(1, Ordering::SeqCst);
+        })
+        .unwrap();
+    timer.enable_interrupt().unwrap();
+    timer.start().unwrap();
+
+    // Periodic: fires every PERIOD.
+    thread::sleep(PERIOD * 5 + PERIOD / 2);
+    log::info!("Periodic, fired {} times", FIRES.load(Ordering::SeqCst));
+
+    // One-shot from here on, only the alarm already underway still fires.
+    timer.set_auto_reload(false).unwrap();
+    let before = FIRES.load(Ordering::SeqCst);
+    thread::sleep(PERIOD * 5);
+    match FIRES.load(Ordering::SeqCst) - before {
+        0 | 1 => log::info!("Stopped firing after switching to one-shot"),
+        n => log::error!("Still fired {} times after switching to one-shot", n),
+    }
+
+    loop {
+        thread::sleep(Duration::from_secs(1));
+    }
+}
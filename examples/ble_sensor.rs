@@ -0,0 +1,323 @@
+//! Serving the potentiometer reading on GPIO2 over Bluetooth LE.
+//!
+//! The board advertises as "buds" with a custom GATT service holding one
+//! characteristic, the raw ADC reading as a little endian u16. Clients can
+//! read it, or enable notifications to get every new reading. Once a client
+//! disconnects the board advertises again.
+//!
+//! Bluetooth is off in the default sdkconfig, uncomment the Bluetooth lines
+//! in sdkconfig.defaults and build with
+//! `cargo run --example ble_sensor --features experimental`.
+//!
+//! The GAP and GATT server wrappers of esp-idf-svc 0.48 don't handle the
+//! connection and write events yet, so this talks to Bluedroid directly
+//! and only uses BtDriver to bring the controller up.
+
+use std::{ffi::CString, sync::Mutex, thread, time::Duration};
+
+use buds::{
+    adc::{attenuation, AnalogInput},
+    prelude::*,
+};
+use esp_idf_svc::{
+    bt::{Ble, BtDriver},
+    hal::gpio::Gpio2,
+    sys::*,
+};
+
+// Change these to tell several boards apart, or to match an existing app.
+const DEVICE_NAME: &str = "buds";
+const SERVICE_UUID: u128 = 0x6b0e_3c35_1c4f_4a8a_9a1e_0b5e_2d6f_0001;
+const READING_UUID: u128 = 0x6b0e_3c35_1c4f_4a8a_9a1e_0b5e_2d6f_0002;
+
+const APP_ID: u16 = 0;
+// Service declaration, characteristic declaration and value, and the
+// client configuration descriptor.
+const SERVICE_HANDLES: u16 = 4;
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+// What the callbacks learned, they run on the Bluedroid task.
+struct Server {
+    gatts_if: esp_gatt_if_t,
+    reading_handle: u16,
+    config_handle: u16,
+    // The connected client, and whether it enabled notifications.
+    conn_id: Option<u16>,
+    notify: bool,
+}
+
+static SERVER: Mutex<Server> = Mutex::new(Server {
+    gatts_if: ESP_GATT_IF_NONE as _,
+    reading_handle: 0,
+    config_handle: 0,
+    conn_id: None,
+    notify: false,
+});
+
+fn uuid128(uuid: u128) -> esp_bt_uuid_t {
+    let mut raw = esp_bt_uuid_t {
+        len: ESP_UUID_LEN_128 as _,
+        ..Default::default()
+    };
+    // UUIDs go over the air least significant byte first.
+    raw.uuid.uuid128 = uuid.to_le_bytes();
+    raw
+}
+
+fn uuid16(uuid: u16) -> esp_bt_uuid_t {
+    let mut raw = esp_bt_uuid_t {
+        len: ESP_UUID_LEN_16 as _,
+        ..Default::default()
+    };
+    raw.uuid.uuid16 = uuid;
+    raw
+}
+
+fn start_advertising() {
+    let mut params = esp_ble_adv_params_t {
+        adv_int_min: 0x20,
+        adv_int_max: 0x40,
+        adv_type: esp_ble_adv_type_t_ADV_TYPE_IND,
+        own_addr_type: esp_ble_addr_type_t_BLE_ADDR_TYPE_PUBLIC,
+        channel_map: esp_ble_adv_channel_t_ADV_CHNL_ALL,
+        adv_filter_policy: esp_ble_adv_filter_t_ADV_FILTER_ALLOW_SCAN_ANY_CON_ANY,
+        ..Default::default()
+    };
+    // SAFETY: esp_ble_gap_start_advertising() is an ESP32 ABI call.
+    if let Err(e) = esp!(unsafe { esp_ble_gap_start_advertising(&mut params) }) {
+        log::error!("Couldn't start advertising: {}", e);
+    }
+}
+
+unsafe extern "C" fn gap_event(event: esp_gap_ble_cb_event_t, param: *mut esp_ble_gap_cb_param_t) {
+    // SAFETY: Bluedroid passes a valid param for the event.
+    let param = unsafe { &*param };
+
+    #[allow(non_upper_case_globals)]
+    match event {
+        // Advertising can only start once the data is in place.
+        esp_gap_ble_cb_event_t_ESP_GAP_BLE_ADV_DATA_SET_COMPLETE_EVT => start_advertising(),
+        esp_gap_ble_cb_event_t_ESP_GAP_BLE_ADV_START_COMPLETE_EVT => {
+            // SAFETY: adv_start_cmpl is the member set for this event.
+            let status = unsafe { param.adv_start_cmpl.status };
+            if status == esp_bt_status_t_ESP_BT_STATUS_SUCCESS {
+                log::info!("Advertising as {}", DEVICE_NAME);
+            } else {
+                log::error!("Advertising failed: {}", status);
+            }
+        }
+        _ => {}
+    }
+}
+
+// Sets the name and advertising data, and creates the service.
+fn on_registered(gatts_if: esp_gatt_if_t) -> Result<(), EspError> {
+    SERVER.lock().unwrap().gatts_if = gatts_if;
+
+    let name = CString::new(DEVICE_NAME).unwrap();
+    // SAFETY: esp_ble_gap_set_device_name() is an ESP32 ABI call.
+    esp!(unsafe { esp_ble_gap_set_device_name(name.as_ptr()) })?;
+
+    let mut service_uuid = SERVICE_UUID.to_le_bytes();
+    let mut adv_data = esp_ble_adv_data_t {
+        include_name: true,
+        service_uuid_len: service_uuid.len() as _,
+        p_service_uuid: service_uuid.as_mut_ptr(),
+        flag: (ESP_BLE_ADV_FLAG_GEN_DISC | ESP_BLE_ADV_FLAG_BREDR_NOT_SPT) as _,
+        ..Default::default()
+    };
+    // SAFETY: esp_ble_gap_config_adv_data() is an ESP32 ABI call, it copies
+    // the data before returning.
+    esp!(unsafe { esp_ble_gap_config_adv_data(&mut adv_data) })?;
+
+    let mut service_id = esp_gatt_srvc_id_t {
+        is_primary: true,
+        id: esp_gatt_id_t {
+            uuid: uuid128(SERVICE_UUID),
+            inst_id: 0,
+        },
+    };
+    // SAFETY: esp_ble_gatts_create_service() is an ESP32 ABI call.
+    esp!(unsafe { esp_ble_gatts_create_service(gatts_if, &mut service_id, SERVICE_HANDLES) })
+}
+
+// Starts the service and adds the reading characteristic to it.
+fn on_created(service_handle: u16) -> Result<(), EspError> {
+    // SAFETY: esp_ble_gatts_start_service() is an ESP32 ABI call.
+    esp!(unsafe { esp_ble_gatts_start_service(service_handle) })?;
+
+    // Answered by the stack from the value set in main().
+    let mut initial = [0u8; 2];
+    let mut value = esp_attr_value_t {
+        attr_max_len: initial.len() as _,
+        attr_len: initial.len() as _,
+        attr_value: initial.as_mut_ptr(),
+    };
+    let mut control = esp_attr_control_t {
+        auto_rsp: ESP_GATT_AUTO_RSP as _,
+    };
+    // SAFETY: esp_ble_gatts_add_char() is an ESP32 ABI call, it copies the
+    // initial value.
+    esp!(unsafe {
+        esp_ble_gatts_add_char(
+            service_handle,
+            &mut uuid128(READING_UUID),
+            ESP_GATT_PERM_READ as _,
+            (ESP_GATT_CHAR_PROP_BIT_READ | ESP_GATT_CHAR_PROP_BIT_NOTIFY) as _,
+            &mut value,
+            &mut control,
+        )
+    })
+}
+
+// Adds the descriptor clients write to turn notifications on and off.
+fn on_reading_added(service_handle: u16, reading_handle: u16) -> Result<(), EspError> {
+    SERVER.lock().unwrap().reading_handle = reading_handle;
+
+    let mut initial = [0u8; 2];
+    let mut value = esp_attr_value_t {
+        attr_max_len: initial.len() as _,
+        attr_len: initial.len() as _,
+        attr_value: initial.as_mut_ptr(),
+    };
+    let mut control = esp_attr_control_t {
+        auto_rsp: ESP_GATT_AUTO_RSP as _,
+    };
+    // SAFETY: esp_ble_gatts_add_char_descr() is an ESP32 ABI call.
+    esp!(unsafe {
+        esp_ble_gatts_add_char_descr(
+            service_handle,
+            &mut uuid16(ESP_GATT_UUID_CHAR_CLIENT_CONFIG as _),
+            (ESP_GATT_PERM_READ | ESP_GATT_PERM_WRITE) as _,
+            &mut value,
+            &mut control,
+        )
+    })
+}
+
+unsafe extern "C" fn gatts_event(
+    event: esp_gatts_cb_event_t,
+    gatts_if: esp_gatt_if_t,
+    param: *mut esp_ble_gatts_cb_param_t,
+) {
+    // SAFETY: Bluedroid passes a valid param for the event, each arm below
+    // only reads the member set for its event.
+    let param = unsafe { &*param };
+
+    #[allow(non_upper_case_globals)]
+    let result = match event {
+        esp_gatts_cb_event_t_ESP_GATTS_REG_EVT => on_registered(gatts_if),
+        esp_gatts_cb_event_t_ESP_GATTS_CREATE_EVT => {
+            on_created(unsafe { param.create.service_handle })
+        }
+        esp_gatts_cb_event_t_ESP_GATTS_ADD_CHAR_EVT => {
+            let added = unsafe { param.add_char };
+            on_reading_added(added.service_handle, added.attr_handle)
+        }
+        esp_gatts_cb_event_t_ESP_GATTS_ADD_CHAR_DESCR_EVT => {
+            SERVER.lock().unwrap().config_handle = unsafe { param.add_char_descr.attr_handle };
+            log::info!("Service ready");
+            Ok(())
+        }
+        esp_gatts_cb_event_t_ESP_GATTS_CONNECT_EVT => {
+            let connect = unsafe { param.connect };
+            log::info!("Client {:02x?} connected", connect.remote_bda);
+            let mut server = SERVER.lock().unwrap();
+            server.conn_id = Some(connect.conn_id);
+            server.notify = false;
+            Ok(())
+        }
+        esp_gatts_cb_event_t_ESP_GATTS_DISCONNECT_EVT => {
+            let disconnect = unsafe { param.disconnect };
+            log::info!("Client disconnected, reason {:#x}", disconnect.reason);
+            let mut server = SERVER.lock().unwrap();
+            server.conn_id = None;
+            server.notify = false;
+            drop(server);
+            // Advertising stops on connect, so we can be found again.
+            start_advertising();
+            Ok(())
+        }
+        esp_gatts_cb_event_t_ESP_GATTS_WRITE_EVT => {
+            let write = unsafe { param.write };
+            let mut server = SERVER.lock().unwrap();
+            if write.handle == server.config_handle && write.len == 2 {
+                // SAFETY: `value` holds `len` bytes for the duration of the
+                // callback.
+                let value = unsafe { core::slice::from_raw_parts(write.value, 2) };
+                server.notify = value[0] & 0x01 != 0;
+                log::info!("Notifications {}", if server.notify { "on" } else { "off" });
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    };
+
+    if let Err(e) = result {
+        log::error!("GATT server setup failed: {}", e);
+    }
+}
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+    let nvs = EspDefaultNvsPartition::take().unwrap();
+
+    let mut pot: AnalogInput<{ attenuation::DB_11 }, Gpio2> =
+        AnalogInput::new(peripherals.adc1, peripherals.pins.gpio2).unwrap();
+
+    // Brings up the controller and Bluedroid, kept alive for as long as the
+    // server runs.
+    let _driver = BtDriver::<Ble>::new(peripherals.modem, Some(nvs)).unwrap();
+
+    // SAFETY: the esp_ble_*() functions are ESP32 ABI calls. The rest of
+    // the setup happens in the callbacks, one event after the other.
+    unsafe {
+        esp!(esp_ble_gap_register_callback(Some(gap_event))).unwrap();
+        esp!(esp_ble_gatts_register_callback(Some(gatts_event))).unwrap();
+        esp!(esp_ble_gatts_app_register(APP_ID)).unwrap();
+    }
+
+    loop {
+        thread::sleep(SAMPLE_INTERVAL);
+
+        let reading = pot.read_averaged(16).unwrap();
+        let mut value = reading.to_le_bytes();
+
+        let server = SERVER.lock().unwrap();
+        if server.reading_handle == 0 {
+            // Not set up yet.
+            continue;
+        }
+
+        // SAFETY: esp_ble_gatts_set_attr_value() is an ESP32 ABI call, it
+        // copies the value.
+        let set = unsafe {
+            esp_ble_gatts_set_attr_value(server.reading_handle, value.len() as _, value.as_ptr())
+        };
+        if let Err(e) = esp!(set) {
+            log::warn!("Couldn't update the reading: {}", e);
+        }
+
+        if let (Some(conn_id), true) = (server.conn_id, server.notify) {
+            // SAFETY: esp_ble_gatts_send_indicate() is an ESP32 ABI call, it
+            // copies the value.
+            let sent = unsafe {
+                esp_ble_gatts_send_indicate(
+                    server.gatts_if,
+                    conn_id,
+                    server.reading_handle,
+                    value.len() as _,
+                    value.as_mut_ptr(),
+                    false,
+                )
+            };
+            if let Err(e) = esp!(sent) {
+                log::warn!("Couldn't notify the reading: {}", e);
+            }
+        }
+    }
+}
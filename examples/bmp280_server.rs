@@ -0,0 +1,96 @@
+//! Logging the temperature & pressure from a BMP280 on I2C (SDA on GPIO6,
+//! SCL on GPIO7, SDO to GND), and serving the latest reading as JSON on
+//! `/api/weather`.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use buds::{
+    bmp280::{Bmp280, PRIMARY_ADDRESS},
+    i2c::I2cDevice,
+    prelude::*,
+    wifi::{connect_blocking, load_credentials},
+};
+use esp_idf_svc::{
+    hal::{
+        i2c::{I2cConfig, I2cDriver},
+        prelude::*,
+    },
+    http::{
+        server::{Configuration as HttpConfiguration, EspHttpServer},
+        Method,
+    },
+    io::{EspIOError, Write},
+};
+
+const LOG_INTERVAL: Duration = Duration::from_secs(5);
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+    let system_event_loop = EspSystemEventLoop::take().unwrap();
+    let nvs_storage = EspDefaultNvsPartition::take().unwrap();
+
+    let i2c_config = I2cConfig::new().baudrate(400.kHz().into());
+    let driver = I2cDriver::new(
+        peripherals.i2c0,
+        peripherals.pins.gpio6,
+        peripherals.pins.gpio7,
+        &i2c_config,
+    )
+    .unwrap();
+    let sensor = Bmp280::new(I2cDevice::new(driver, PRIMARY_ADDRESS).unwrap()).unwrap();
+    // Read by the server's handler and the log loop.
+    let sensor = Arc::new(Mutex::new(sensor));
+
+    let (wifi_ssid, wifi_pwd) = load_credentials(&nvs_storage)
+        .expect("Store credentials in NVS or export WIFI_SSID & WIFI_PWD Enviroment Variables");
+    let config = Configuration::Client(ClientConfiguration {
+        ssid: wifi_ssid.as_str().try_into().unwrap(),
+        password: wifi_pwd.as_str().try_into().unwrap(),
+        ..Default::default()
+    });
+    let wifi =
+        connect_blocking(peripherals.modem, system_event_loop, nvs_storage, &config).unwrap();
+
+    let mut server = EspHttpServer::new(&HttpConfiguration::default()).unwrap();
+    let handler_sensor = sensor.clone();
+    server
+        .fn_handler::<EspIOError, _>("/api/weather", Method::Get, move |req| {
+            let reading = handler_sensor.lock().unwrap().read();
+            let Ok((temperature, pressure)) = reading else {
+                return req
+                    .into_status_response(503)?
+                    .write_all(b"Sensor not ready");
+            };
+            let json = format!(
+                r#"{{"temperature":{:.2},"pressure":{:.0}}}"#,
+                temperature, pressure
+            );
+
+            req.into_response(200, None, &[("Content-Type", "application/json")])?
+                .write_all(json.as_bytes())
+        })
+        .unwrap();
+
+    let ip = wifi.wifi().sta_netif().get_ip_info().unwrap().ip;
+    log::info!("Serving on http://{}/api/weather", ip);
+
+    loop {
+        // Also leaves the sensor time for its first measurement.
+        std::thread::sleep(LOG_INTERVAL);
+
+        match sensor.lock().unwrap().read() {
+            Ok((temperature, pressure)) => log::info!(
+                "Temperature: {:.2}°C, Pressure: {:.2} hPa",
+                temperature,
+                pressure / 100.0
+            ),
+            Err(e) => log::warn!("Reading failed: {}", e),
+        }
+    }
+}
@@ -0,0 +1,61 @@
+//! Cycling an LED on GPIO1 through brightness levels with the button on
+//! GPIO9 (BOOT on most ESP32-C3 boards), holding it switches the LED off.
+
+use std::{thread, time::Duration};
+
+use buds::{
+    board::take_peripherals,
+    gpio::{ButtonEvent, DebouncedButton},
+    pwm::PwmLed,
+};
+use esp_idf_svc::hal::{
+    ledc::{config::TimerConfig, LedcDriver, LedcTimerDriver},
+    prelude::*,
+};
+
+// Brightness steps a click moves through, in percent.
+const LEVELS: [u8; 5] = [0, 10, 30, 60, 100];
+
+// The button is polled this often, 5 stable samples debounce it over 10ms.
+const POLL_INTERVAL: Duration = Duration::from_millis(2);
+const STABLE_SAMPLES: u8 = 5;
+
+const LONG_PRESS: Duration = Duration::from_secs(1);
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+
+    let timer = LedcTimerDriver::new(
+        peripherals.ledc.timer0,
+        &TimerConfig::new().frequency(5.kHz().into()),
+    )
+    .unwrap();
+    let channel =
+        LedcDriver::new(peripherals.ledc.channel0, &timer, peripherals.pins.gpio1).unwrap();
+    let mut led = PwmLed::new(channel);
+    led.set_brightness(0).unwrap();
+
+    // BOOT pulls the pin to GND and the board has a pull-up on it.
+    let mut button = DebouncedButton::new(peripherals.pins.gpio9, STABLE_SAMPLES, true).unwrap();
+
+    let mut level = 0;
+    loop {
+        if let ButtonEvent::Released { held } = button.poll() {
+            level = if held >= LONG_PRESS {
+                0
+            } else {
+                (level + 1) % LEVELS.len()
+            };
+            led.set_brightness(LEVELS[level]).unwrap();
+            log::info!("Brightness {}%", LEVELS[level]);
+        } else if button.is_long_press(LONG_PRESS) && led.brightness() != 0 {
+            // Switch off as soon as the press is long, not on release.
+            led.set_brightness(0).unwrap();
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    }
+}
@@ -0,0 +1,45 @@
+//! Logging the sensors inside the chip every 2 seconds: the die temperature
+//! and the hall effect sensor, bring a magnet close to the chip to move it.
+//!
+//! Which of the two a chip has depends on the model, the other one logs
+//! that it isn't supported, once.
+
+use std::{thread, time::Duration};
+
+use buds::chip_sensors::{read_hall_sensor, read_internal_temperature};
+use esp_idf_svc::sys::ESP_ERR_NOT_SUPPORTED;
+
+const LOG_INTERVAL: Duration = Duration::from_secs(2);
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let mut has_temperature = true;
+    let mut has_hall = true;
+    loop {
+        if has_temperature {
+            match read_internal_temperature() {
+                Ok(celsius) => log::info!("Die temperature: {:.1}°C", celsius),
+                Err(e) if e.code() == ESP_ERR_NOT_SUPPORTED => {
+                    log::warn!("No temperature sensor on this chip");
+                    has_temperature = false;
+                }
+                Err(e) => log::error!("Reading the temperature failed: {}", e),
+            }
+        }
+
+        if has_hall {
+            match read_hall_sensor() {
+                Ok(value) => log::info!("Hall sensor: {}", value),
+                Err(e) if e.code() == ESP_ERR_NOT_SUPPORTED => {
+                    log::warn!("No hall sensor on this chip, or ESP IDF 5 can't read it");
+                    has_hall = false;
+                }
+                Err(e) => log::error!("Reading the hall sensor failed: {}", e),
+            }
+        }
+
+        thread::sleep(LOG_INTERVAL);
+    }
+}
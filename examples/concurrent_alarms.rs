@@ -0,0 +1,85 @@
+// This example showcases two timers running their own alarm callbacks
+// at the same time: one blinks an LED on GPIO1 at 1 Hz, the other counts
+// at 10 Hz. Each callback owns its state, and the main loop checks every
+// second that both kept their pace, i.e. that neither ISR ran the other's
+// closure or touched its data.
+
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use buds::prelude::*;
+use esp_idf_svc::sys::timer_config_t;
+
+fn config() -> timer_config_t {
+    TimerConfigBuilder::new()
+        .divider(divider_for_hz(1_000_000).unwrap())
+        .build()
+        .unwrap()
+}
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+    let mut led = PinDriver::output(peripherals.pins.gpio1).unwrap();
+
+    // One timer from each group, these exist on every ESP32 variant.
+    let mut blinker = HwTimer::new(TimerId::Group0Timer0, config()).unwrap();
+    let mut counter = HwTimer::new(TimerId::Group1Timer0, config()).unwrap();
+
+    // Toggled twice a second, so on and off once per second. The LED moves
+    // into the closure, only this ISR drives it.
+    let toggles = Arc::new(AtomicU32::new(0));
+    let blinker_toggles = toggles.clone();
+    blinker.set_alarm_hz(2.0).unwrap();
+    blinker
+        .on_alarm(move || {
+            let _ = led.toggle();
+            blinker_toggles.fetch_add(1, Ordering::Relaxed);
+        })
+        .unwrap();
+
+    let count = Arc::new(AtomicU32::new(0));
+    let counter_count = count.clone();
+    counter.set_alarm_hz(10.0).unwrap();
+    counter
+        .on_alarm(move || {
+            counter_count.fetch_add(1, Ordering::Relaxed);
+        })
+        .unwrap();
+
+    for timer in [&mut blinker, &mut counter] {
+        timer.enable_interrupt().unwrap();
+        timer.start().unwrap();
+    }
+
+    let (mut last_toggles, mut last_count) = (0, 0);
+    loop {
+        thread::sleep(Duration::from_secs(1));
+
+        let (toggles, count) = (
+            toggles.load(Ordering::Relaxed),
+            count.load(Ordering::Relaxed),
+        );
+        let (toggled, counted) = (toggles - last_toggles, count - last_count);
+        (last_toggles, last_count) = (toggles, count);
+
+        // The sleep isn't aligned with the alarms, allow one off.
+        if toggled.abs_diff(2) <= 1 && counted.abs_diff(10) <= 1 {
+            log::info!("Blinker: {} toggles, counter: {}", toggles, count);
+        } else {
+            log::error!(
+                "Timers out of pace: {} toggles and {} counts in the last second",
+                toggled,
+                counted
+            );
+        }
+    }
+}
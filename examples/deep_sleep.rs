@@ -0,0 +1,32 @@
+//! Battery friendly sensor readings: wake up, read the potentiometer on
+//! GPIO2, log it and deep sleep for another 10 seconds.
+
+use std::time::Duration;
+
+use buds::{
+    adc::{attenuation, raw_to_percent, AnalogInput},
+    board::take_peripherals,
+    power::{deep_sleep_for, wake_cause, WakeCause},
+};
+use esp_idf_svc::hal::gpio::Gpio2;
+
+const SLEEP_TIME: Duration = Duration::from_secs(10);
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    match wake_cause() {
+        WakeCause::PowerOn => log::info!("First boot"),
+        cause => log::info!("Woke up from deep sleep: {:?}", cause),
+    }
+
+    let peripherals = take_peripherals().unwrap();
+    let mut pot: AnalogInput<{ attenuation::DB_11 }, Gpio2> =
+        AnalogInput::new(peripherals.adc1, peripherals.pins.gpio2).unwrap();
+
+    let raw = pot.read_averaged(16).unwrap();
+    log::info!("Reading: {} ({}%)", raw, raw_to_percent(raw));
+
+    deep_sleep_for(SLEEP_TIME);
+}
@@ -0,0 +1,33 @@
+//! Logging the temperature & humidity from a DHT22 with its data pin on
+//! GPIO4 (add a 10k pull-up to 3.3V if the module doesn't have one).
+
+use std::time::Duration;
+
+use buds::{dht::Dht22, prelude::*};
+
+// The DHT22 doesn't measure more often than this.
+const READ_INTERVAL: Duration = Duration::from_secs(2);
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+    let mut sensor = Dht22::new(peripherals.pins.gpio4, TimerId::Group0Timer0).unwrap();
+
+    loop {
+        // The sensor needs a moment after power up before the first reading.
+        std::thread::sleep(READ_INTERVAL);
+
+        match sensor.read() {
+            Ok((temperature, humidity)) => {
+                log::info!(
+                    "Temperature: {:.1}°C, Humidity: {:.1}%",
+                    temperature,
+                    humidity
+                )
+            }
+            Err(e) => log::warn!("Reading failed: {}", e),
+        }
+    }
+}
@@ -0,0 +1,104 @@
+//! Keeping settings in a 24C32 EEPROM on I2C (SDA on GPIO6, SCL on GPIO7,
+//! A0-A2 to GND): the settings are read back at boot, their boot counter
+//! bumped, and written again.
+//!
+//! The struct is 12 bytes stored at address 30, so it straddles the
+//! boundary between the first two 32 bytes pages; the driver splits the
+//! write there. A magic number tells a blank (all 0xff) chip apart.
+
+use buds::{
+    board::take_peripherals,
+    eeprom::{AddressWidth, Eeprom24, BASE_ADDRESS},
+    i2c::I2cDevice,
+};
+use esp_idf_svc::hal::{
+    i2c::{I2cConfig, I2cDriver},
+    prelude::*,
+};
+
+// Where the settings live.
+const SETTINGS_ADDR: usize = 30;
+
+const MAGIC: u32 = 0x6275_6473;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Settings {
+    boot_count: u32,
+    brightness: u8,
+    volume: i16,
+}
+
+impl Settings {
+    const SIZE: usize = 12;
+
+    fn to_bytes(self) -> [u8; Self::SIZE] {
+        let mut bytes = [0u8; Self::SIZE];
+        bytes[0..4].copy_from_slice(&MAGIC.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.boot_count.to_le_bytes());
+        bytes[8] = self.brightness;
+        bytes[9..11].copy_from_slice(&self.volume.to_le_bytes());
+        // bytes[11] is padding, left at 0.
+        bytes
+    }
+
+    // None when the magic number doesn't match, e.g. on a new chip.
+    fn from_bytes(bytes: &[u8; Self::SIZE]) -> Option<Self> {
+        let word = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+        if word(0) != MAGIC {
+            return None;
+        }
+
+        Some(Self {
+            boot_count: word(4),
+            brightness: bytes[8],
+            volume: i16::from_le_bytes([bytes[9], bytes[10]]),
+        })
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            boot_count: 0,
+            brightness: 128,
+            volume: -20,
+        }
+    }
+}
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+    let i2c_config = I2cConfig::new().baudrate(400.kHz().into());
+    let driver = I2cDriver::new(
+        peripherals.i2c0,
+        peripherals.pins.gpio6,
+        peripherals.pins.gpio7,
+        &i2c_config,
+    )
+    .unwrap();
+    let device = I2cDevice::new(driver, BASE_ADDRESS).unwrap();
+    // 4 KiB in 32 bytes pages.
+    let mut eeprom = Eeprom24::new(device, AddressWidth::TwoBytes, 4096, 32).unwrap();
+
+    let mut bytes = [0u8; Settings::SIZE];
+    eeprom.read(SETTINGS_ADDR, &mut bytes).unwrap();
+    let mut settings = Settings::from_bytes(&bytes).unwrap_or_else(|| {
+        log::warn!("No settings stored, using the defaults");
+        Settings::default()
+    });
+    log::info!("Read {:?}", settings);
+
+    settings.boot_count += 1;
+    eeprom.write(SETTINGS_ADDR, &settings.to_bytes()).unwrap();
+
+    // Check what made it to the chip.
+    eeprom.read(SETTINGS_ADDR, &mut bytes).unwrap();
+    if Settings::from_bytes(&bytes) == Some(settings) {
+        log::info!("Wrote {:?}", settings);
+    } else {
+        log::error!("Read back {:02x?} after writing {:?}", bytes, settings);
+    }
+}
@@ -0,0 +1,61 @@
+// This example showcases three rotary encoders sharing a single timer: each
+// one drives its own counter from the callback it registered in the bank.
+
+use std::{
+    sync::atomic::{AtomicI32, Ordering},
+    thread,
+    time::Duration,
+};
+
+use buds::{
+    encoder::{Direction, EncoderBank},
+    prelude::*,
+};
+
+// What each encoder controls, e.g. the red, green & blue of a color.
+static COUNTERS: [AtomicI32; 3] = [AtomicI32::new(0), AtomicI32::new(0), AtomicI32::new(0)];
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+    let pins = peripherals.pins;
+
+    let mut bank = EncoderBank::new();
+    bank.add(RotaryEncoder::new(pins.gpio0, pins.gpio1).unwrap());
+    bank.add(RotaryEncoder::new(pins.gpio2, pins.gpio3).unwrap());
+    bank.add(RotaryEncoder::new(pins.gpio4, pins.gpio5).unwrap());
+
+    for (index, counter) in COUNTERS.iter().enumerate() {
+        bank.on_event(index, move |event| {
+            let delta = match event.direction {
+                Direction::Clockwise => 1,
+                Direction::CounterClockwise => -1,
+            };
+            counter.fetch_add(delta, Ordering::SeqCst);
+        })
+        .unwrap();
+    }
+
+    // Polling at 1 kHz doesn't miss a state even when turned quickly.
+    let config = TimerConfigBuilder::new()
+        .divider(divider_for_hz(1_000_000).unwrap())
+        .build()
+        .unwrap();
+    let mut timer = HwTimer::new(TimerId::Group0Timer0, config).unwrap();
+    timer.set_alarm_hz(1000.0).unwrap();
+    timer.on_alarm(move || bank.poll()).unwrap();
+    timer.enable_interrupt().unwrap();
+    timer.start().unwrap();
+
+    let mut previous = [0; 3];
+    loop {
+        let current = [0, 1, 2].map(|i| COUNTERS[i].load(Ordering::SeqCst));
+        if current != previous {
+            log::info!("Counters: {:?}", current);
+            previous = current;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+}
@@ -0,0 +1,54 @@
+// This example showcases the encoder's debugging aids, for checking how an
+// encoder on GPIO0 (A) and GPIO1 (B) is wired. Every gray code change is
+// logged with the step it decoded, and the raw levels once a second.
+//
+// A clean clockwise turn logs AB 00 -> 01 -> 11 -> 10 -> 00, each one CW.
+// Lots of "skipped a state" means it's polled too slowly for the turn, A
+// and B changing together points at a missing pull-up or a loose wire.
+
+use std::{
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use buds::prelude::*;
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+    // Shared with the ISR, the main thread logs the trace.
+    let encoder =
+        Arc::new(RotaryEncoder::new(peripherals.pins.gpio0, peripherals.pins.gpio1).unwrap());
+    encoder.debug_trace(true);
+
+    let config = TimerConfigBuilder::new()
+        .divider(divider_for_hz(1_000_000).unwrap())
+        .build()
+        .unwrap();
+    let mut timer = HwTimer::new(TimerId::Group0Timer0, config).unwrap();
+    let polled = encoder.clone();
+    timer.set_alarm_hz(1000.0).unwrap();
+    timer
+        .on_alarm(move || {
+            polled.poll();
+        })
+        .unwrap();
+    timer.enable_interrupt().unwrap();
+    timer.start().unwrap();
+
+    let mut last_state = Instant::now();
+    loop {
+        encoder.log_trace();
+
+        if last_state.elapsed() >= Duration::from_secs(1) {
+            let (a, b) = encoder.raw_state();
+            log::info!("A: {:?}, B: {:?}, position {}", a, b, encoder.position());
+            last_state = Instant::now();
+        }
+
+        thread::sleep(Duration::from_millis(50));
+    }
+}
@@ -0,0 +1,45 @@
+// This example showcases iterating over the steps of a rotary encoder on
+// GPIO0 and GPIO1. A timer alarm polls it at 1 kHz so no state is missed,
+// and the main thread handles the steps in a plain for loop, blocking
+// until the knob moves.
+
+use std::sync::Arc;
+
+use buds::{
+    encoder::{Direction, EventMode},
+    prelude::*,
+};
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+    // Shared with the ISR, which polls it, while the main thread reads the
+    // events.
+    let encoder =
+        Arc::new(RotaryEncoder::new(peripherals.pins.gpio0, peripherals.pins.gpio1).unwrap());
+
+    let config = TimerConfigBuilder::new()
+        .divider(divider_for_hz(1_000_000).unwrap())
+        .build()
+        .unwrap();
+    let mut timer = HwTimer::new(TimerId::Group0Timer0, config).unwrap();
+    let polled = encoder.clone();
+    timer.set_alarm_hz(1000.0).unwrap();
+    timer
+        .on_alarm(move || {
+            polled.poll();
+        })
+        .unwrap();
+    timer.enable_interrupt().unwrap();
+    timer.start().unwrap();
+
+    for event in encoder.events(EventMode::Blocking) {
+        let arrow = match event.direction {
+            Direction::Clockwise => "->",
+            Direction::CounterClockwise => "<-",
+        };
+        log::info!("{} {}", arrow, event.position);
+    }
+}
@@ -0,0 +1,81 @@
+// This example showcases a volume knob bounded to 0..=100, flashing an LED
+// on GPIO3 when the knob is turned against either end stop. The encoder is
+// on GPIO0 and GPIO1.
+
+use std::{
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use buds::{encoder::Limit, prelude::*};
+
+const FLASH_TIME: Duration = Duration::from_millis(80);
+
+// End stops hit and not flashed yet, 0 for none, 1 for the min, 2 for the
+// max. The limit callback runs in the ISR, which can't drive the LED for
+// a while itself.
+static HIT: AtomicU8 = AtomicU8::new(0);
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+    let mut led = PinDriver::output(peripherals.pins.gpio3).unwrap();
+
+    let config = EncoderConfig::new().bounds(0, 100);
+    let mut encoder =
+        RotaryEncoder::with_config(peripherals.pins.gpio0, peripherals.pins.gpio1, config).unwrap();
+    encoder.set_position(50);
+    // Only called on the first step against an end stop, the knob can keep
+    // turning that way without flashing again.
+    encoder.on_limit(|limit| {
+        let hit = match limit {
+            Limit::Min => 1,
+            Limit::Max => 2,
+        };
+        HIT.store(hit, Ordering::SeqCst);
+    });
+
+    // Polling at 1 kHz doesn't miss a state even when turned quickly.
+    let timer_config = TimerConfigBuilder::new()
+        .divider(divider_for_hz(1_000_000).unwrap())
+        .build()
+        .unwrap();
+    let mut timer = HwTimer::new(TimerId::Group0Timer0, timer_config).unwrap();
+    // Shared with the ISR, the main loop reads the position.
+    let encoder = Arc::new(encoder);
+    let polled = encoder.clone();
+    timer.set_alarm_hz(1000.0).unwrap();
+    timer
+        .on_alarm(move || {
+            polled.poll();
+        })
+        .unwrap();
+    timer.enable_interrupt().unwrap();
+    timer.start().unwrap();
+
+    let mut shown = i32::MIN;
+    loop {
+        let volume = encoder.position();
+        if volume != shown {
+            log::info!("Volume: {}", volume);
+            shown = volume;
+        }
+
+        // A short flash for the min, two for the max.
+        let flashes = HIT.swap(0, Ordering::SeqCst);
+        for _ in 0..flashes {
+            led.set_high().unwrap();
+            thread::sleep(FLASH_TIME);
+            led.set_low().unwrap();
+            thread::sleep(FLASH_TIME);
+        }
+
+        thread::sleep(Duration::from_millis(20));
+    }
+}
@@ -0,0 +1,61 @@
+// This example showcases a menu selected with a rotary encoder on GPIO0 and
+// GPIO1: the selection moves by one entry per click of the knob, whatever
+// the number of steps the encoder takes between two detents, and the
+// button on GPIO9 (BOOT on most ESP32-C3 boards) selects the entry.
+
+use std::{sync::mpsc::RecvTimeoutError, time::Duration};
+
+use buds::{
+    gpio::{SmartButton, SmartButtonConfig},
+    menu::Menu,
+    prelude::*,
+};
+
+const MENU: [&str; 5] = ["Brightness", "Contrast", "Volume", "Timer", "Reset"];
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+    let mut encoder = RotaryEncoder::new(peripherals.pins.gpio0, peripherals.pins.gpio1).unwrap();
+    let button = SmartButton::new(peripherals.pins.gpio9, SmartButtonConfig::new()).unwrap();
+
+    // Turning the knob halfway and letting it snap back doesn't move the
+    // selection, only landing on a new detent does.
+    let mut menu = Menu::new(MENU.to_vec()).unwrap();
+    menu.attach(&mut encoder);
+    menu.on_select(|entry| log::info!("Selected {}", entry));
+
+    // Polling at 1 kHz doesn't miss a state even when turned quickly.
+    let config = TimerConfigBuilder::new()
+        .divider(divider_for_hz(1_000_000).unwrap())
+        .build()
+        .unwrap();
+    let mut timer = HwTimer::new(TimerId::Group0Timer0, config).unwrap();
+    timer.set_alarm_hz(1000.0).unwrap();
+    timer
+        .on_alarm(move || {
+            encoder.poll();
+        })
+        .unwrap();
+    timer.enable_interrupt().unwrap();
+    timer.start().unwrap();
+
+    log::info!("> {}", menu.current());
+    loop {
+        // Waiting on the button also paces the loop.
+        match button.events().recv_timeout(Duration::from_millis(50)) {
+            Ok(event) => menu.handle_click(event),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                log::error!("Button stopped");
+                return;
+            }
+        }
+
+        if menu.update() {
+            log::info!("> {}", menu.current());
+        }
+    }
+}
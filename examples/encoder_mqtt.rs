@@ -0,0 +1,100 @@
+//! Publishing the position of a rotary encoder (A on GPIO0, B on GPIO1) to
+//! an MQTT broker on every detent, on the `buds/encoder/position` topic.
+//!
+//! Set the broker with the MQTT_URL environment variable at build time,
+//! e.g. `MQTT_URL=mqtt://192.168.1.10:1883`. The wifi and the broker both
+//! reconnect when they drop; detents turned meanwhile are published once
+//! the broker is back, QoS 1 keeping them in the client's outbox.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicI32, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use buds::{
+    mqtt::{MqttConfig, MqttPublisher, QoS},
+    prelude::*,
+    wifi::{connect_blocking, ensure_connected, load_credentials},
+};
+
+const TOPIC: &str = "buds/encoder/position";
+
+const WIFI_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+    let system_event_loop = EspSystemEventLoop::take().unwrap();
+    let nvs_storage = EspDefaultNvsPartition::take().unwrap();
+
+    let (wifi_ssid, wifi_pwd) = load_credentials(&nvs_storage)
+        .expect("Store credentials in NVS or export WIFI_SSID & WIFI_PWD Enviroment Variables");
+    let config = Configuration::Client(ClientConfiguration {
+        ssid: wifi_ssid.as_str().try_into().unwrap(),
+        password: wifi_pwd.as_str().try_into().unwrap(),
+        ..Default::default()
+    });
+    let mut wifi =
+        connect_blocking(peripherals.modem, system_event_loop, nvs_storage, &config).unwrap();
+
+    let mqtt_config = MqttConfig::new()
+        .client_id("buds-encoder")
+        .qos(QoS::AtLeastOnce)
+        .retain(true);
+    let mut mqtt = MqttPublisher::new(env!("MQTT_URL", "Export MQTT_URL"), mqtt_config).unwrap();
+    if mqtt.wait_connected(Duration::from_secs(10)).is_err() {
+        log::warn!("MQTT broker not there yet, publishing once it is");
+    }
+
+    // The detent callback runs in the timer's ISR, which can't publish, so
+    // it leaves the position for the main loop.
+    let mut encoder = RotaryEncoder::new(peripherals.pins.gpio0, peripherals.pins.gpio1).unwrap();
+    let position = Arc::new(AtomicI32::new(0));
+    let moved = Arc::new(AtomicBool::new(false));
+    let (detent_position, detent_moved) = (position.clone(), moved.clone());
+    encoder.on_detent(move |detent| {
+        detent_position.store(detent, Ordering::SeqCst);
+        detent_moved.store(true, Ordering::SeqCst);
+    });
+
+    let timer_config = TimerConfigBuilder::new()
+        .divider(divider_for_hz(1_000_000).unwrap())
+        .build()
+        .unwrap();
+    let mut timer = HwTimer::new(TimerId::Group0Timer0, timer_config).unwrap();
+    timer.set_alarm_hz(1000.0).unwrap();
+    timer
+        .on_alarm(move || {
+            encoder.poll();
+        })
+        .unwrap();
+    timer.enable_interrupt().unwrap();
+    timer.start().unwrap();
+
+    let mut last_wifi_check = Instant::now();
+    loop {
+        if moved.swap(false, Ordering::SeqCst) {
+            let position = position.load(Ordering::SeqCst);
+            match mqtt.publish(TOPIC, position.to_string().as_bytes()) {
+                Ok(_) => log::info!("Published position {}", position),
+                Err(e) => log::warn!("Failed to publish position {}: {}", position, e),
+            }
+        }
+
+        // The broker reconnects by itself, the wifi needs a hand.
+        if last_wifi_check.elapsed() >= WIFI_CHECK_INTERVAL {
+            if let Err(e) = ensure_connected(wifi.wifi_mut(), 5) {
+                log::error!("Wifi lost: {}", e);
+            }
+            last_wifi_check = Instant::now();
+        }
+
+        thread::sleep(Duration::from_millis(20));
+    }
+}
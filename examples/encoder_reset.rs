@@ -0,0 +1,65 @@
+// This example showcases a volume knob, an encoder on GPIO0 and GPIO1 with
+// its push button on GPIO9: turning moves the volume within 0..=100, a
+// click logs it, and holding the button for 1.5 s puts it back to 50. The
+// release ending that hold doesn't count as a click.
+
+use std::{sync::Arc, time::Duration};
+
+use buds::{
+    gpio::SmartButtonConfig,
+    knob::{Knob, ResetPolicy},
+    prelude::*,
+};
+
+const INITIAL_VOLUME: i32 = 50;
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+    let config = EncoderConfig::new().bounds(0, 100);
+    let encoder =
+        RotaryEncoder::with_config(peripherals.pins.gpio0, peripherals.pins.gpio1, config).unwrap();
+    encoder.set_position(INITIAL_VOLUME);
+    // Shared with the ISR polling it.
+    let encoder = Arc::new(encoder);
+
+    let timer_config = TimerConfigBuilder::new()
+        .divider(divider_for_hz(1_000_000).unwrap())
+        .build()
+        .unwrap();
+    let mut timer = HwTimer::new(TimerId::Group0Timer0, timer_config).unwrap();
+    let polled = encoder.clone();
+    timer.set_alarm_hz(1000.0).unwrap();
+    timer
+        .on_alarm(move || {
+            polled.poll();
+        })
+        .unwrap();
+    timer.enable_interrupt().unwrap();
+    timer.start().unwrap();
+
+    // Created at 50, which is what a reset goes back to.
+    let policy = ResetPolicy::LongPress(Duration::from_millis(1500));
+    let mut knob = Knob::new(
+        encoder,
+        peripherals.pins.gpio9,
+        SmartButtonConfig::new(),
+        policy,
+    )
+    .unwrap();
+    knob.on_click(|volume| log::info!("Volume set to {}", volume));
+    knob.on_reset(|volume| log::info!("Volume reset to {}", volume));
+
+    let mut volume = knob.position();
+    loop {
+        // Waiting on the button also paces the loop.
+        knob.update(Duration::from_millis(50));
+
+        if knob.position() != volume {
+            volume = knob.position();
+            log::info!("Volume {}", volume);
+        }
+    }
+}
@@ -0,0 +1,142 @@
+// This example is a self test of the encoder decoding, for running without
+// an encoder or any wiring, on the chip or under the Wokwi or QEMU
+// simulators (see the README). Each case drives a QuadratureDecoder over
+// MockLevelSources through scripted turns and checks the position it ends
+// at.
+//
+// The last line logged is "Encoder selftest passed" or "Encoder selftest
+// failed", for a simulator run to wait for. A failure also panics, so a run
+// that nobody watches the log of doesn't pass quietly.
+//
+// It only runs on the chip or a simulator: it links against ESP IDF like
+// every example. tests/encoder.rs has the same cases as a host test.
+
+use buds::{
+    encoder::{EncoderConfig, QuadratureDecoder},
+    levels::MockLevelSource,
+};
+
+// One clockwise turn through the whole gray code, back on the detent.
+const CW: [i8; 4] = [1, 2, 3, 0];
+const CCW: [i8; 4] = [3, 2, 1, 0];
+
+struct Case {
+    name: &'static str,
+    config: EncoderConfig,
+    // Gray codes read on each poll, after the detent 0 the decoder starts
+    // from.
+    codes: Vec<i8>,
+    expected: i32,
+}
+
+// `turn` repeated `times` times.
+fn turns(turn: [i8; 4], times: usize) -> Vec<i8> {
+    turn.iter().copied().cycle().take(4 * times).collect()
+}
+
+// Each code of `codes` bouncing once back to the one before, then held for
+// 3 polls.
+fn bouncy(codes: &[i8]) -> Vec<i8> {
+    let mut previous = 0;
+    let mut bouncing = Vec::new();
+    for &code in codes {
+        bouncing.extend_from_slice(&[code, previous, code, code, code]);
+        previous = code;
+    }
+    bouncing
+}
+
+// Polls a decoder through `case`, returning the position it ends at.
+fn run(case: &Case) -> i32 {
+    // The inverse of greycode_from_bits().
+    let a_high = |code: &i8| matches!(code, 2 | 3);
+    let b_high = |code: &i8| matches!(code, 1 | 2);
+
+    // from_pins() reads the starting levels first.
+    let codes = || std::iter::once(&0).chain(case.codes.iter());
+    let a = MockLevelSource::new(codes().map(a_high).collect::<Vec<_>>());
+    let b = MockLevelSource::new(codes().map(b_high).collect::<Vec<_>>());
+
+    let decoder = QuadratureDecoder::from_pins(a, b, case.config);
+    for _ in &case.codes {
+        decoder.poll();
+    }
+    decoder.position()
+}
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let cases = [
+        Case {
+            name: "clockwise",
+            config: EncoderConfig::new(),
+            codes: turns(CW, 3),
+            expected: 12,
+        },
+        Case {
+            name: "counter clockwise",
+            config: EncoderConfig::new(),
+            codes: turns(CCW, 2),
+            expected: -8,
+        },
+        Case {
+            name: "bouncy contacts",
+            config: EncoderConfig::new().debounce_samples(3),
+            codes: bouncy(&turns(CW, 2)),
+            expected: 8,
+        },
+        Case {
+            name: "one step per detent",
+            config: EncoderConfig::new().steps_per_detent(4),
+            // Three clicks, then partway to the next one and back.
+            codes: [turns(CW, 3), vec![1, 2, 1, 0]].concat(),
+            expected: 3,
+        },
+        Case {
+            name: "bounded",
+            config: EncoderConfig::new().bounds(0, 10),
+            codes: turns(CW, 4),
+            expected: 10,
+        },
+        Case {
+            name: "confirmed reversal",
+            config: EncoderConfig::new().confirm_steps(2),
+            codes: [turns(CW, 1), turns(CCW, 1)].concat(),
+            expected: 0,
+        },
+        Case {
+            name: "inverted",
+            config: EncoderConfig::new().invert(true),
+            codes: turns(CW, 1),
+            expected: -4,
+        },
+    ];
+
+    let mut failed = 0;
+    for case in &cases {
+        let position = run(case);
+        if position == case.expected {
+            log::info!("PASS {}: position {}", case.name, position);
+        } else {
+            log::error!(
+                "FAIL {}: position {}, expected {}",
+                case.name,
+                position,
+                case.expected
+            );
+            failed += 1;
+        }
+    }
+
+    if failed > 0 {
+        log::error!(
+            "Encoder selftest failed, {} of {} cases",
+            failed,
+            cases.len()
+        );
+        panic!("{} encoder selftest cases failed", failed);
+    }
+    log::info!("Encoder selftest passed, {} cases", cases.len());
+}
@@ -0,0 +1,55 @@
+//! An encoder and a button that bring their own pull resistors, so the
+//! internal ones stay off with Pull::Floating.
+//!
+//! Wiring:
+//! - An encoder module with 10k pull-ups to 3.3V on its A and B outputs
+//!   (most KY-040 style boards have them), A on GPIO0 and B on GPIO1.
+//! - A button between 3.3V and GPIO2, with a 10k pull-down from GPIO2 to
+//!   GND, so it reads high while pressed.
+//!
+//! Leaving the internal resistors on as well would only put them in
+//! parallel with the external ones, but a pull-up fighting an external
+//! pull-down leaves the pin halfway and reading garbage.
+
+use std::{thread, time::Duration};
+
+use buds::{
+    gpio::{ButtonEvent, DebouncedButton},
+    prelude::*,
+};
+
+// Fast enough for slow turns, a timer alarm polls quick ones better.
+const POLL_INTERVAL: Duration = Duration::from_millis(2);
+const STABLE_SAMPLES: u8 = 5;
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+
+    let config = EncoderConfig::new().pull(Pull::Floating);
+    let encoder =
+        RotaryEncoder::with_config(peripherals.pins.gpio0, peripherals.pins.gpio1, config).unwrap();
+
+    // Active high, the external pull-down holds it low when released.
+    let mut button = DebouncedButton::with_pull(
+        peripherals.pins.gpio2,
+        STABLE_SAMPLES,
+        false,
+        Pull::Floating,
+    )
+    .unwrap();
+
+    loop {
+        if encoder.poll().is_some() {
+            log::info!("Position: {}", encoder.position());
+        }
+        if let ButtonEvent::Pressed = button.poll() {
+            log::info!("Reset to 0");
+            encoder.set_position(0);
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    }
+}
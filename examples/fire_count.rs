@@ -0,0 +1,46 @@
+// This example showcases HwTimer::fire_count() for checking an alarm's
+// timing: a 500 Hz alarm runs a callback that counts nothing itself, and
+// every 2 seconds the fires counted by the timer are compared with the
+// ones expected for the time that really passed.
+
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use buds::prelude::*;
+
+const ALARM_HZ: u32 = 500;
+
+const INTERVAL: Duration = Duration::from_secs(2);
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let config = TimerConfigBuilder::new()
+        .divider(divider_for_hz(1_000_000).unwrap())
+        .build()
+        .unwrap();
+    let mut timer = HwTimer::new(TimerId::Group0Timer0, config).unwrap();
+    timer.set_alarm_hz(ALARM_HZ as f32).unwrap();
+    // The application's own work, the timer does the counting.
+    timer.on_alarm(|| {}).unwrap();
+    timer.enable_interrupt().unwrap();
+
+    let started = Instant::now();
+    timer.start().unwrap();
+
+    loop {
+        thread::sleep(INTERVAL);
+
+        let fired = timer.fire_count();
+        let expected = (started.elapsed().as_secs_f64() * f64::from(ALARM_HZ)) as u64;
+        // The read isn't aligned with the alarms, allow one off.
+        if fired.abs_diff(expected) <= 1 {
+            log::info!("{} fires, as expected", fired);
+        } else {
+            log::error!("{} fires, expected {}", fired, expected);
+        }
+    }
+}
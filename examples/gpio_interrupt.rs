@@ -0,0 +1,64 @@
+//! Reacting to a PIR motion sensor on GPIO4 with a GPIO edge interrupt, no
+//! timer involved: the sensor's output goes high when it sees motion, the
+//! rising edge runs an ISR, and the main loop logs it.
+//!
+//! Two things to get right. subscribe() leaves the interrupt disabled, and
+//! the driver disables it again after every edge, so enable_interrupt() has
+//! to follow both; forgetting it gets exactly one edge, or none. And an
+//! edge can come with bounces (a switch, or a sensor's output settling), so
+//! edges too close to the last one handled are ignored.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use buds::prelude::*;
+
+// Edges within this long of the last one handled are bounces.
+const DEBOUNCE: Duration = Duration::from_millis(50);
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+    let mut sensor = PinDriver::input(peripherals.pins.gpio4).unwrap();
+    // Most PIR modules drive their output both ways, the pull-down only
+    // keeps the pin low if it's disconnected.
+    sensor.set_pull(Pull::Down).unwrap();
+    sensor.set_interrupt_type(InterruptType::PosEdge).unwrap();
+
+    // The ISR only raises a flag, it mustn't block, allocate or log.
+    let motion = Arc::new(AtomicBool::new(false));
+    let isr_motion = motion.clone();
+    // SAFETY: the callback only stores to an atomic, which is ISR safe.
+    unsafe {
+        sensor
+            .subscribe(move || isr_motion.store(true, Ordering::SeqCst))
+            .unwrap();
+    }
+    // subscribe() leaves the interrupt disabled.
+    sensor.enable_interrupt().unwrap();
+
+    let mut last_edge: Option<Instant> = None;
+    loop {
+        if motion.swap(false, Ordering::SeqCst) {
+            // Disabled by the driver when the edge fired, re-armed here as
+            // that can't be done from the ISR.
+            sensor.enable_interrupt().unwrap();
+
+            let bounce = last_edge.is_some_and(|edge| edge.elapsed() < DEBOUNCE);
+            if !bounce {
+                log::info!("Motion detected");
+                last_edge = Some(Instant::now());
+            }
+        }
+
+        thread::sleep(Duration::from_millis(10));
+    }
+}
@@ -0,0 +1,41 @@
+//! Fetching a small JSON document over HTTPS once the wifi is connected.
+
+use std::time::Duration;
+
+use buds::{
+    http::http_get_with,
+    prelude::*,
+    wifi::{connect_blocking, load_credentials},
+};
+
+// Answers with our public IP, e.g. {"ip":"203.0.113.7"}.
+const URL: &str = "https://api.ipify.org?format=json";
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+    let system_event_loop = EspSystemEventLoop::take().unwrap();
+    let nvs_storage = EspDefaultNvsPartition::take().unwrap();
+
+    let (wifi_ssid, wifi_pwd) = load_credentials(&nvs_storage)
+        .expect("Store credentials in NVS or export WIFI_SSID & WIFI_PWD Enviroment Variables");
+    let config = Configuration::Client(ClientConfiguration {
+        ssid: wifi_ssid.as_str().try_into().unwrap(),
+        password: wifi_pwd.as_str().try_into().unwrap(),
+        ..Default::default()
+    });
+    // Kept alive, dropping it would disconnect.
+    let _wifi =
+        connect_blocking(peripherals.modem, system_event_loop, nvs_storage, &config).unwrap();
+
+    let headers = [("Accept", "application/json")];
+    loop {
+        match http_get_with(URL, Duration::from_secs(5), &headers) {
+            Ok(body) => log::info!("{}", String::from_utf8_lossy(&body)),
+            Err(e) => log::error!("GET {} failed: {}", URL, e),
+        }
+        std::thread::sleep(Duration::from_secs(30));
+    }
+}
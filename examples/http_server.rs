@@ -0,0 +1,94 @@
+//! A small web server exposing a potentiometer on GPIO2: an HTML page on
+//! `/` and the current reading as JSON on `/api/reading`.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use buds::{
+    adc::{attenuation, raw_to_percent, AnalogInput},
+    mdns::{add_http_service, start_mdns, HTTP_PORT},
+    prelude::*,
+    wifi::{connect_blocking, load_credentials},
+};
+use esp_idf_svc::{
+    hal::gpio::Gpio2,
+    http::{
+        server::{Configuration as HttpConfiguration, EspHttpServer},
+        Method,
+    },
+    io::{EspIOError, Write},
+};
+
+const HOSTNAME: &str = "buds";
+
+// Readings averaged per request.
+const OVERSAMPLING: usize = 16;
+
+// Polls /api/reading every second and shows the result.
+const INDEX_HTML: &str = r#"<!DOCTYPE html>
+<html>
+<head><meta name="viewport" content="width=device-width, initial-scale=1"><title>buds</title></head>
+<body>
+<h1>Potentiometer</h1>
+<p id="reading">...</p>
+<script>
+setInterval(async () => {
+  const r = await (await fetch('/api/reading')).json();
+  document.getElementById('reading').textContent = r.raw + ' (' + r.percent + '%)';
+}, 1000);
+</script>
+</body>
+</html>"#;
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+    let system_event_loop = EspSystemEventLoop::take().unwrap();
+    let nvs_storage = EspDefaultNvsPartition::take().unwrap();
+
+    let (wifi_ssid, wifi_pwd) = load_credentials(&nvs_storage)
+        .expect("Store credentials in NVS or export WIFI_SSID & WIFI_PWD Enviroment Variables");
+    let config = Configuration::Client(ClientConfiguration {
+        ssid: wifi_ssid.as_str().try_into().unwrap(),
+        password: wifi_pwd.as_str().try_into().unwrap(),
+        ..Default::default()
+    });
+    let wifi =
+        connect_blocking(peripherals.modem, system_event_loop, nvs_storage, &config).unwrap();
+
+    // A request can come in on any of the server's open connections, the
+    // ADC is shared between them behind a mutex.
+    let pot: AnalogInput<{ attenuation::DB_11 }, Gpio2> =
+        AnalogInput::new(peripherals.adc1, peripherals.pins.gpio2).unwrap();
+    let pot = Arc::new(Mutex::new(pot));
+
+    let mut server = EspHttpServer::new(&HttpConfiguration::default()).unwrap();
+    server
+        .fn_handler("/", Method::Get, |req| {
+            req.into_ok_response()?.write_all(INDEX_HTML.as_bytes())
+        })
+        .unwrap()
+        .fn_handler::<EspIOError, _>("/api/reading", Method::Get, move |req| {
+            let raw = pot.lock().unwrap().read_averaged(OVERSAMPLING)?;
+            let json = format!(r#"{{"raw":{},"percent":{}}}"#, raw, raw_to_percent(raw));
+
+            req.into_response(200, None, &[("Content-Type", "application/json")])?
+                .write_all(json.as_bytes())
+        })
+        .unwrap();
+
+    // Kept alive, the device stops being discoverable when it's dropped.
+    let mut mdns = start_mdns(HOSTNAME, "buds http server").unwrap();
+    add_http_service(&mut mdns, HTTP_PORT).unwrap();
+
+    let ip = wifi.wifi().sta_netif().get_ip_info().unwrap().ip;
+    log::info!("Serving on http://{}.local/ and http://{}/", HOSTNAME, ip);
+
+    loop {
+        std::thread::sleep(Duration::from_secs(10));
+    }
+}
@@ -0,0 +1,53 @@
+//! Finding the address of the devices attached to an I2C bus.
+//! Prints the same grid as the usual Arduino scanners, with `--` for
+//! addresses nobody answered on.
+
+use std::time::Duration;
+
+use buds::{
+    board::take_peripherals,
+    i2c::{scan_i2c, FIRST_ADDRESS, LAST_ADDRESS},
+};
+use esp_idf_svc::hal::{
+    i2c::{I2cConfig, I2cDriver},
+    prelude::*,
+};
+
+// Bus speed, most sensors support 100 kHz and 400 kHz.
+const FREQUENCY_KHZ: u32 = 100;
+
+// Prints the addresses in rows of 16.
+fn print_grid(found: &[u8]) {
+    println!("     0  1  2  3  4  5  6  7  8  9  a  b  c  d  e  f");
+    for row in (0..0x80u8).step_by(16) {
+        let cells: String = (row..row + 16)
+            .map(|addr| match addr {
+                addr if !(FIRST_ADDRESS..=LAST_ADDRESS).contains(&addr) => "   ".to_string(),
+                addr if found.contains(&addr) => format!(" {:02x}", addr),
+                _ => " --".to_string(),
+            })
+            .collect();
+        println!("{:02x}:{}", row, cells);
+    }
+}
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+
+    // SDA on GPIO6 & SCL on GPIO7, change these to match your wiring.
+    let sda = peripherals.pins.gpio6;
+    let scl = peripherals.pins.gpio7;
+    let config = I2cConfig::new().baudrate(FREQUENCY_KHZ.kHz().into());
+    let mut driver = I2cDriver::new(peripherals.i2c0, sda, scl, &config).unwrap();
+
+    loop {
+        let found = scan_i2c(&mut driver);
+        log::info!("Found {} device(s) on the bus", found.len());
+        print_grid(&found);
+
+        std::thread::sleep(Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,75 @@
+// This example showcases learning a button of an NEC remote and replaying
+// it: the first code a TSOP38238 style receiver on GPIO4 picks up is kept,
+// and an IR LED on GPIO5 (through a transistor) sends it again every 5
+// seconds, held for half a second with repeat codes.
+//
+// The receiver keeps listening, so with the LED in its view the replays
+// show up in the log too, which checks both sides.
+
+use std::{
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use buds::{
+    board::take_peripherals,
+    ir::{IrReceiver, IrTransmitter, REPEAT_PERIOD},
+};
+
+const REPLAY_INTERVAL: Duration = Duration::from_secs(5);
+
+// Repeat codes sent after each replayed frame.
+const REPEATS: u32 = 4;
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+
+    // Frames come in on the receiver's thread.
+    let (frames_tx, frames_rx) = mpsc::channel();
+    let _receiver = IrReceiver::new(
+        peripherals.rmt.channel2,
+        peripherals.pins.gpio4,
+        move |frame| {
+            let _ = frames_tx.send(frame);
+        },
+    )
+    .unwrap();
+    // Transmit and receive channels differ on the ESP32-C3, 0-1 and 2-3.
+    let mut transmitter =
+        IrTransmitter::new(peripherals.rmt.channel0, peripherals.pins.gpio5).unwrap();
+
+    log::info!("Press a button of the remote...");
+    let learned = loop {
+        let frame = frames_rx.recv().unwrap();
+        if !frame.repeat {
+            break frame.code;
+        }
+    };
+    log::info!(
+        "Learned address {:#04x} command {:#04x}",
+        learned.address,
+        learned.command
+    );
+
+    loop {
+        thread::sleep(REPLAY_INTERVAL);
+
+        log::info!("Replaying");
+        // The frames start REPEAT_PERIOD apart, however long they take.
+        let start = Instant::now();
+        transmitter.send_code(learned).unwrap();
+        for i in 1..=REPEATS {
+            let at = start + REPEAT_PERIOD * i;
+            thread::sleep(at.saturating_duration_since(Instant::now()));
+            transmitter.send_repeat().unwrap();
+        }
+
+        for frame in frames_rx.try_iter() {
+            log::info!("Received {:?}", frame);
+        }
+    }
+}
@@ -0,0 +1,54 @@
+// This example showcases handing a timer ISR a struct of several pins: a
+// chaser lighting LEDs on GPIO4, GPIO5 and GPIO6 one after the other, a
+// step every 200ms. The struct lives in an IsrContext the timer owns, so
+// the ISR gets `&mut` to it without anything else holding on to it.
+
+use std::{thread, time::Duration};
+
+use buds::{prelude::*, timer::IsrContext};
+use esp_idf_svc::hal::gpio::{AnyOutputPin, Output};
+
+// Everything the ISR works on.
+struct Chaser {
+    leds: [PinDriver<'static, AnyOutputPin, Output>; 3],
+    lit: usize,
+}
+
+impl Chaser {
+    fn step(&mut self) {
+        let _ = self.leds[self.lit].set_low();
+        self.lit = (self.lit + 1) % self.leds.len();
+        let _ = self.leds[self.lit].set_high();
+    }
+}
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+    let pins = peripherals.pins;
+    let leds = [
+        PinDriver::output(AnyOutputPin::from(pins.gpio4)).unwrap(),
+        PinDriver::output(AnyOutputPin::from(pins.gpio5)).unwrap(),
+        PinDriver::output(AnyOutputPin::from(pins.gpio6)).unwrap(),
+    ];
+    let context = IsrContext::new(Chaser { leds, lit: 0 });
+
+    let config = TimerConfigBuilder::new()
+        .divider(divider_for_hz(1_000_000).unwrap())
+        .build()
+        .unwrap();
+    let mut timer = HwTimer::new(TimerId::Group0Timer0, config).unwrap();
+    timer.set_alarm_after(Duration::from_millis(200)).unwrap();
+    timer
+        .on_alarm_with_context(context, |chaser| chaser.step())
+        .unwrap();
+    timer.enable_interrupt().unwrap();
+    timer.start().unwrap();
+
+    log::info!("Chasing...");
+    loop {
+        thread::sleep(Duration::from_secs(1));
+    }
+}
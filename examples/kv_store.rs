@@ -0,0 +1,46 @@
+// This example checks KvStore against the default NVS partition: each
+// type is set then read back, a key that was never set reads as None and
+// a removed one goes back to None.
+//
+// NVS lives in the flash, so this runs on the chip and not as a host test
+// of the storage module. It uses its own namespace and removes its keys,
+// leaving the rest of the partition alone.
+
+use buds::storage::KvStore;
+use esp_idf_svc::nvs::EspDefaultNvsPartition;
+
+const NAMESPACE: &str = "kv_selftest";
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let nvs = EspDefaultNvsPartition::take().unwrap();
+    let mut store = KvStore::new(&nvs, NAMESPACE).unwrap();
+
+    store.set_u32("count", 0xdead_beef).unwrap();
+    assert_eq!(store.get_u32("count").unwrap(), Some(0xdead_beef));
+
+    store.set_i32("offset", -42).unwrap();
+    assert_eq!(store.get_i32("offset").unwrap(), Some(-42));
+
+    store.set_str("name", "buds").unwrap();
+    assert_eq!(store.get_str("name").unwrap().as_deref(), Some("buds"));
+
+    let blob = [0u8, 1, 2, 0xff, 0];
+    store.set_blob("blob", &blob).unwrap();
+    assert_eq!(store.get_blob("blob").unwrap().as_deref(), Some(&blob[..]));
+
+    // Never set, under every type.
+    assert_eq!(store.get_u32("missing").unwrap(), None);
+    assert_eq!(store.get_str("missing").unwrap(), None);
+    assert_eq!(store.get_blob("missing").unwrap(), None);
+
+    for key in ["count", "offset", "name", "blob"] {
+        assert!(store.remove(key).unwrap());
+    }
+    assert!(!store.remove("count").unwrap());
+    assert_eq!(store.get_u32("count").unwrap(), None);
+
+    log::info!("KvStore selftest passed");
+}
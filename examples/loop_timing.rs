@@ -0,0 +1,51 @@
+// This example showcases timing the iterations of a main loop with a
+// Stopwatch: each lap() is one iteration, and the stopwatch is stopped
+// around the sleep so the total only counts the work done.
+
+use std::{thread, time::Duration};
+
+use buds::{stopwatch::Stopwatch, timer::TimerId};
+
+// Laps per report.
+const ITERATIONS: usize = 10;
+
+// Work that takes longer every iteration.
+fn sum_of_squares(n: u64) -> u64 {
+    (1..=n).map(|i| i * i).sum()
+}
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let mut stopwatch = Stopwatch::new(TimerId::Group0Timer0).unwrap();
+
+    loop {
+        let mut work = 10_000;
+        let mut slowest = Duration::ZERO;
+        stopwatch.reset().unwrap();
+        stopwatch.start().unwrap();
+        for _ in 0..ITERATIONS {
+            let sum = sum_of_squares(work);
+            let lap = stopwatch.lap().unwrap();
+            slowest = slowest.max(lap);
+
+            // Not part of the iteration.
+            stopwatch.stop().unwrap();
+            log::info!("Sum to {} = {} took {:?}", work, sum, lap);
+            work += 10_000;
+            thread::sleep(Duration::from_millis(100));
+            stopwatch.start().unwrap();
+        }
+        stopwatch.stop().unwrap();
+
+        // The last split is the total, as the stopwatch only ran for laps.
+        log::info!(
+            "{} iterations took {:?} (last split {:?}), the slowest {:?}",
+            stopwatch.laps().len(),
+            stopwatch.elapsed().unwrap(),
+            stopwatch.laps().last().copied().unwrap_or_default(),
+            slowest
+        );
+    }
+}
@@ -0,0 +1,77 @@
+//! A battery powered station that stays online for a while between deep
+//! sleeps: it reports the potentiometer on GPIO2 every 10 seconds for a
+//! minute with the modem sleeping as much as it can, then powers down for
+//! five minutes.
+//!
+//! Ballpark currents of an ESP32-C3 module, from Espressif's figures rather
+//! than measured on this board (regulators and LEDs add their own):
+//! - PowerSave::None, radio always on: around 80 mA.
+//! - PowerSave::MinModem, waking every DTIM beacon: around 20 mA.
+//! - PowerSave::MaxModem, listen interval 3: under 10 mA.
+//! - Deep sleep: around 5 µA.
+
+use std::{thread, time::Duration};
+
+use buds::{
+    adc::{attenuation, raw_to_percent, AnalogInput},
+    power::{deep_sleep_for, wake_cause},
+    prelude::*,
+    wifi::{connect_blocking, load_credentials, power_save, set_power_save, stop, PowerSave},
+};
+use esp_idf_svc::hal::gpio::Gpio2;
+
+const REPORTS: u32 = 6;
+const REPORT_INTERVAL: Duration = Duration::from_secs(10);
+const SLEEP_TIME: Duration = Duration::from_secs(300);
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    log::info!("Woke up: {:?}", wake_cause());
+
+    let peripherals = take_peripherals().unwrap();
+    let system_event_loop = EspSystemEventLoop::take().unwrap();
+    let nvs_storage = EspDefaultNvsPartition::take().unwrap();
+
+    let mut pot: AnalogInput<{ attenuation::DB_11 }, Gpio2> =
+        AnalogInput::new(peripherals.adc1, peripherals.pins.gpio2).unwrap();
+
+    let (wifi_ssid, wifi_pwd) = load_credentials(&nvs_storage)
+        .expect("Store credentials in NVS or export WIFI_SSID & WIFI_PWD Enviroment Variables");
+    let config = Configuration::Client(ClientConfiguration {
+        ssid: wifi_ssid.as_str().try_into().unwrap(),
+        password: wifi_pwd.as_str().try_into().unwrap(),
+        ..Default::default()
+    });
+    match connect_blocking(
+        peripherals.modem,
+        system_event_loop.clone(),
+        nvs_storage,
+        &config,
+    ) {
+        Ok(mut wifi) => {
+            // Nothing here needs a quick answer, the readings can wait for
+            // the modem to wake up.
+            if let Err(e) = set_power_save(wifi.wifi_mut(), PowerSave::MaxModem) {
+                log::warn!("Power save unchanged: {}", e);
+            }
+            log::info!("Power save: {:?}", power_save(wifi.wifi()));
+
+            for _ in 0..REPORTS {
+                let raw = pot.read_averaged(16).unwrap();
+                // This is where the reading would be sent somewhere.
+                log::info!("Reading: {} ({}%)", raw, raw_to_percent(raw));
+                thread::sleep(REPORT_INTERVAL);
+            }
+
+            if let Err(e) = stop(wifi.wifi_mut(), &system_event_loop) {
+                log::warn!("Wifi didn't stop cleanly: {}", e);
+            }
+        }
+        // Better luck on the next wake up.
+        Err(e) => log::error!("Wifi connection failed: {}", e),
+    }
+
+    deep_sleep_for(SLEEP_TIME);
+}
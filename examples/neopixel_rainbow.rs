@@ -0,0 +1,37 @@
+//! Animating a rainbow across a strip of 8 WS2812 pixels on GPIO8, the
+//! pin of the single on board pixel of the ESP32-C3-DevKitM-1.
+
+use std::time::Duration;
+
+use buds::{
+    board::take_peripherals,
+    neopixel::{wheel, NeoPixel},
+};
+
+const PIXELS: usize = 8;
+
+// Time between two frames of the animation.
+const FRAME_DELAY: Duration = Duration::from_millis(20);
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+    let mut strip =
+        NeoPixel::new(peripherals.rmt.channel0, peripherals.pins.gpio8, PIXELS).unwrap();
+
+    let mut offset = 0u8;
+    loop {
+        // Spread the whole wheel over the strip, and turn it a bit each frame.
+        for index in 0..strip.len() {
+            let position = (index * 256 / strip.len()) as u8;
+            let (r, g, b) = wheel(position.wrapping_add(offset));
+            strip.set_pixel(index, r, g, b).unwrap();
+        }
+        strip.flush().unwrap();
+
+        offset = offset.wrapping_add(1);
+        std::thread::sleep(FRAME_DELAY);
+    }
+}
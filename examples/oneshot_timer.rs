@@ -0,0 +1,39 @@
+// This example showcases a one-shot timer: the callback runs once after
+// the delay and the timer stays quiet afterwards.
+
+use std::{
+    sync::atomic::{AtomicU32, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use buds::prelude::*;
+
+static FIRES: AtomicU32 = AtomicU32::new(0);
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let start = Instant::now();
+    let _timer = HwTimer::oneshot(TimerId::Group0Timer0, Duration::from_millis(500), || {
+        FIRES.fetch_add(1, Ordering::SeqCst);
+    })
+    .unwrap();
+
+    // Wait for the first fire, then give it plenty more time to misbehave.
+    while FIRES.load(Ordering::SeqCst) == 0 {
+        thread::sleep(Duration::from_millis(1));
+    }
+    log::info!("Fired after {:?}", start.elapsed());
+
+    thread::sleep(Duration::from_secs(3));
+    match FIRES.load(Ordering::SeqCst) {
+        1 => log::info!("Fired exactly once"),
+        n => log::error!("Fired {} times", n),
+    }
+
+    loop {
+        thread::sleep(Duration::from_secs(1));
+    }
+}
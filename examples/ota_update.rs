@@ -0,0 +1,96 @@
+//! Updating the firmware over the air when the server has a newer version.
+//!
+//! The server publishes two files: `version.txt`, holding the version of
+//! the latest build (e.g. `0.2.0`), and `firmware.bin`, that build's app
+//! image as produced by `espflash save-image`. On boot the board compares
+//! the published version with its own and only downloads the image when
+//! it's newer.
+//!
+//! OTA needs a partition table with two app slots and a 4MB flash, see the
+//! OTA lines of sdkconfig.defaults. espflash brings its own table, so flash
+//! the first build with `--partition-table` pointing at ESP IDF's
+//! `components/partition_table/partitions_two_ota.csv`.
+
+use std::time::Duration;
+
+use buds::{
+    http::http_get,
+    ota::ota_update,
+    prelude::*,
+    wifi::{connect_blocking, load_credentials},
+};
+use esp_idf_svc::{ota::EspOta, sys::esp_restart};
+
+const VERSION_URL: &str = "https://example.com/buds/version.txt";
+const FIRMWARE_URL: &str = "https://example.com/buds/firmware.bin";
+
+// How often the server is asked for a new version.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+// Parses "major.minor.patch", ignoring surrounding whitespace.
+fn parse_version(version: &str) -> Option<(u32, u32, u32)> {
+    let mut parts = version.trim().split('.').map(|part| part.parse().ok());
+    let version = (parts.next()??, parts.next()??, parts.next()??);
+    parts.next().is_none().then_some(version)
+}
+
+// Whether the server has a newer version than the one running.
+fn update_available() -> bool {
+    let running = parse_version(env!("CARGO_PKG_VERSION"));
+
+    let published = match http_get(VERSION_URL) {
+        Ok(body) => parse_version(&String::from_utf8_lossy(&body)),
+        Err(e) => {
+            log::warn!("Couldn't fetch {}: {}", VERSION_URL, e);
+            return false;
+        }
+    };
+    log::info!("Running {:?}, published {:?}", running, published);
+
+    // Tuples compare field by field, so this is a version comparison.
+    matches!((running, published), (Some(running), Some(published)) if published > running)
+}
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+    let system_event_loop = EspSystemEventLoop::take().unwrap();
+    let nvs_storage = EspDefaultNvsPartition::take().unwrap();
+
+    let (wifi_ssid, wifi_pwd) = load_credentials(&nvs_storage)
+        .expect("Store credentials in NVS or export WIFI_SSID & WIFI_PWD Enviroment Variables");
+    let config = Configuration::Client(ClientConfiguration {
+        ssid: wifi_ssid.as_str().try_into().unwrap(),
+        password: wifi_pwd.as_str().try_into().unwrap(),
+        ..Default::default()
+    });
+    // Kept alive, dropping it would disconnect.
+    let _wifi =
+        connect_blocking(peripherals.modem, system_event_loop, nvs_storage, &config).unwrap();
+
+    // With rollback enabled (CONFIG_BOOTLOADER_APP_ROLLBACK_ENABLE) a new
+    // image has to confirm it works, or the next boot goes back to the old
+    // one. Getting online is good enough here. The EspOta is dropped right
+    // after, ota_update() needs to take it.
+    EspOta::new()
+        .and_then(|mut ota| ota.mark_running_slot_valid())
+        .unwrap();
+
+    loop {
+        if update_available() {
+            match ota_update(FIRMWARE_URL) {
+                Ok(()) => {
+                    log::info!("Restarting into the new firmware");
+                    // SAFETY: esp_restart() is an ESP32 ABI call.
+                    unsafe { esp_restart() };
+                }
+                // The running firmware is untouched, try again next time.
+                Err(e) => log::error!("Firmware update failed: {}", e),
+            }
+        }
+
+        std::thread::sleep(CHECK_INTERVAL);
+    }
+}
@@ -0,0 +1,88 @@
+//! Counting a rotary encoder on GPIO4 (A) & GPIO5 (B) with the PCNT, next
+//! to the software decoder reading the same pins from a 1 kHz timer. Spin
+//! the knob fast and the polled count falls behind, the hardware one
+//! doesn't.
+//!
+//! The ESP32-C3 has no PCNT, this one is for the ESP32, ESP32-S2 & -S3.
+
+#[cfg(any(esp32, esp32s2, esp32s3))]
+fn main() {
+    use std::{
+        sync::{
+            atomic::{AtomicI32, Ordering},
+            Arc,
+        },
+        thread,
+        time::Duration,
+    };
+
+    use buds::{
+        board::take_peripherals,
+        encoder::RotaryEncoder,
+        pcnt::{PcntEncoder, PcntLimit},
+        timer::{divider_for_hz, HwTimer, TimerConfigBuilder, TimerId},
+    };
+    use esp_idf_svc::hal::peripheral::Peripheral;
+
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    // Times the hardware counter went past +PCNT_LIMIT, minus past -PCNT_LIMIT.
+    static WRAPS: AtomicI32 = AtomicI32::new(0);
+
+    let peripherals = take_peripherals().unwrap();
+    let mut a = peripherals.pins.gpio4;
+    let mut b = peripherals.pins.gpio5;
+
+    // SAFETY: both decoders only ever read the pins, the software one as
+    // plain inputs and the PCNT through the GPIO matrix. The PCNT is set
+    // up last, so its input routing comes after the GPIO setup.
+    let (pcnt_a, pcnt_b) = unsafe { (a.clone_unchecked(), b.clone_unchecked()) };
+    // Shared with the ISR polling it.
+    let software = Arc::new(RotaryEncoder::new(a, b).unwrap());
+    let mut hardware = PcntEncoder::new(peripherals.pcnt0, pcnt_a, pcnt_b).unwrap();
+    hardware
+        .on_limit(|limit| {
+            let wrap = match limit {
+                PcntLimit::Overflow => 1,
+                PcntLimit::Underflow => -1,
+            };
+            WRAPS.fetch_add(wrap, Ordering::SeqCst);
+        })
+        .unwrap();
+
+    let config = TimerConfigBuilder::new()
+        .divider(divider_for_hz(1_000_000).unwrap())
+        .build()
+        .unwrap();
+    let mut timer = HwTimer::new(TimerId::Group0Timer0, config).unwrap();
+    let polled = software.clone();
+    timer.set_alarm_hz(1000.0).unwrap();
+    timer
+        .on_alarm(move || {
+            polled.poll();
+        })
+        .unwrap();
+    timer.enable_interrupt().unwrap();
+    timer.start().unwrap();
+
+    loop {
+        let pcnt = hardware.count().unwrap();
+        let polled = software.position();
+        log::info!(
+            "PCNT: {}, polled: {} ({} apart)",
+            pcnt,
+            polled,
+            pcnt - polled
+        );
+        thread::sleep(Duration::from_millis(500));
+    }
+}
+
+#[cfg(not(any(esp32, esp32s2, esp32s3)))]
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    log::error!("This chip has no PCNT peripheral");
+}
@@ -0,0 +1,45 @@
+//! Setting up wifi without recompiling.
+//! On first boot join the `buds-setup` AP and open http://192.168.71.1 to
+//! enter the network to use, the device then reboots and connects to it.
+
+use std::time::Duration;
+
+use buds::{
+    prelude::*,
+    provision::provision,
+    wifi::{connect_with_retry, load_credentials},
+};
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+    let system_event_loop = EspSystemEventLoop::take().unwrap();
+    let nvs_storage = EspDefaultNvsPartition::take().unwrap();
+
+    let mut wifi = EspWifi::new(
+        peripherals.modem,
+        system_event_loop,
+        Some(nvs_storage.clone()),
+    )
+    .unwrap();
+
+    // Only comes back if credentials were stored on a previous boot.
+    provision(&mut wifi, &nvs_storage, "buds-setup").unwrap();
+
+    let (wifi_ssid, wifi_pwd) = load_credentials(&nvs_storage).unwrap();
+    wifi.set_configuration(&Configuration::Client(ClientConfiguration {
+        ssid: wifi_ssid.as_str().try_into().unwrap(),
+        password: wifi_pwd.as_str().try_into().unwrap(),
+        ..Default::default()
+    }))
+    .unwrap();
+    wifi.start().unwrap();
+    connect_with_retry(&mut wifi, 5).unwrap();
+    log::info!("Connected to {}", wifi_ssid);
+
+    loop {
+        std::thread::sleep(Duration::new(10, 0));
+    }
+}
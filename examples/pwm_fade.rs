@@ -0,0 +1,35 @@
+//! Fading an LED on GPIO1 in and out with PWM.
+
+use std::time::Duration;
+
+use buds::{board::take_peripherals, pwm::PwmLed};
+use esp_idf_svc::hal::{
+    ledc::{config::TimerConfig, LedcDriver, LedcTimerDriver},
+    prelude::*,
+};
+
+// How long a fade in or out takes.
+const FADE_TIME: Duration = Duration::from_secs(1);
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+
+    // 5 kHz is well above what the eye can see flickering.
+    let timer = LedcTimerDriver::new(
+        peripherals.ledc.timer0,
+        &TimerConfig::new().frequency(5.kHz().into()),
+    )
+    .unwrap();
+    let channel =
+        LedcDriver::new(peripherals.ledc.channel0, &timer, peripherals.pins.gpio1).unwrap();
+    let mut led = PwmLed::new(channel);
+
+    loop {
+        led.fade_to(100, FADE_TIME).unwrap();
+        led.fade_to(0, FADE_TIME).unwrap();
+        log::info!("Faded in and out, brightness back at {}%", led.brightness());
+    }
+}
@@ -0,0 +1,39 @@
+// This example showcases an alarm rescheduling itself: an LED on GPIO4
+// toggles at random intervals between 50 and 500 ms, each fire picking
+// when the next one happens. HwTimer::rescheduling() turns auto reload off
+// so the interval counts from the fire, and re-arms the alarm from the ISR.
+
+use std::{thread, time::Duration};
+
+use buds::prelude::*;
+use esp_idf_svc::sys::esp_random;
+
+const MIN_INTERVAL_MS: u32 = 50;
+const MAX_INTERVAL_MS: u32 = 500;
+
+// A random interval within MIN_INTERVAL_MS..=MAX_INTERVAL_MS.
+fn random_interval() -> Duration {
+    // SAFETY: esp_random() is an ESP32 ABI call, it only reads the RNG.
+    let random = unsafe { esp_random() };
+    let ms = MIN_INTERVAL_MS + random % (MAX_INTERVAL_MS - MIN_INTERVAL_MS + 1);
+    Duration::from_millis(u64::from(ms))
+}
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+    let mut led = PinDriver::output(peripherals.pins.gpio4).unwrap();
+
+    let timer = HwTimer::rescheduling(TimerId::Group0Timer0, random_interval(), move || {
+        let _ = led.toggle();
+        Some(random_interval())
+    })
+    .unwrap();
+
+    loop {
+        thread::sleep(Duration::from_secs(5));
+        log::info!("{} blinks so far", timer.fire_count());
+    }
+}
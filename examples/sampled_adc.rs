@@ -0,0 +1,58 @@
+//! Sampling a potentiometer on GPIO2 at 1 kHz with a SampledAdc, wired
+//! like the adc_pot example.
+//!
+//! The main loop only wakes every 100 ms and drains what was sampled
+//! meanwhile, about 100 readings, whose spread shows the noise of the ADC.
+
+use std::time::Duration;
+
+use buds::{
+    adc::{attenuation, raw_to_percent, AnalogInput, SampledAdc},
+    prelude::*,
+};
+use esp_idf_svc::hal::gpio::Gpio2;
+
+const SAMPLE_PERIOD: Duration = Duration::from_millis(1);
+
+// Room for 256 ms of samples, the loop drains them well before.
+const CAPACITY: usize = 256;
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+
+    let pot: AnalogInput<{ attenuation::DB_11 }, Gpio2> =
+        AnalogInput::new(peripherals.adc1, peripherals.pins.gpio2).unwrap();
+    let sampled = SampledAdc::<CAPACITY>::new(pot, TimerId::Group0Timer0, SAMPLE_PERIOD).unwrap();
+
+    loop {
+        std::thread::sleep(Duration::from_millis(100));
+
+        let (mut count, mut sum) = (0u32, 0u32);
+        let (mut min, mut max) = (u16::MAX, u16::MIN);
+        for raw in sampled.drain() {
+            count += 1;
+            sum += u32::from(raw);
+            min = min.min(raw);
+            max = max.max(raw);
+        }
+        if count == 0 {
+            log::warn!("No samples");
+            continue;
+        }
+
+        let mean = (sum / count) as u16;
+        log::info!(
+            "{} samples every {:?}: mean {} ({}%), spread {}..={}, {} dropped so far",
+            count,
+            sampled.sample_period(),
+            mean,
+            raw_to_percent(mean),
+            min,
+            max,
+            sampled.dropped()
+        );
+    }
+}
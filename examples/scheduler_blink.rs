@@ -0,0 +1,66 @@
+// This example showcases a Scheduler blinking two LEDs at different rates
+// from a single timer: GPIO1 toggles every 500 ms and GPIO3 every 150 ms.
+// A third task, counting seconds, is added at runtime and cancelled after
+// ten runs.
+
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use buds::{prelude::*, scheduler::Scheduler};
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+    let mut slow_led = PinDriver::output(peripherals.pins.gpio1).unwrap();
+    let mut fast_led = PinDriver::output(peripherals.pins.gpio3).unwrap();
+
+    // A 10 ms tick is plenty for blinking, and wakes the CPU rarely.
+    let scheduler = Scheduler::new(TimerId::Group0Timer0, Duration::from_millis(10), 4).unwrap();
+
+    scheduler
+        .add(Duration::from_millis(500), move || {
+            let _ = slow_led.toggle();
+        })
+        .unwrap();
+    scheduler
+        .add(Duration::from_millis(150), move || {
+            let _ = fast_led.toggle();
+        })
+        .unwrap();
+
+    thread::sleep(Duration::from_secs(2));
+
+    let seconds = Arc::new(AtomicU32::new(0));
+    let counted = seconds.clone();
+    let counter = scheduler
+        .add(Duration::from_secs(1), move || {
+            counted.fetch_add(1, Ordering::Relaxed);
+        })
+        .unwrap();
+    log::info!("Counter added");
+
+    loop {
+        thread::sleep(Duration::from_millis(250));
+
+        let seconds = seconds.load(Ordering::Relaxed);
+        if seconds >= 10 {
+            break;
+        }
+        log::info!("Counter: {}", seconds);
+    }
+
+    // The LEDs keep blinking.
+    scheduler.cancel(counter).unwrap();
+    log::info!("Counter cancelled");
+    loop {
+        thread::sleep(Duration::from_secs(1));
+    }
+}
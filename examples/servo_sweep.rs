@@ -0,0 +1,32 @@
+//! Sweeping a hobby servo, with its signal wire on GPIO1, back and forth.
+
+use std::time::Duration;
+
+use buds::{
+    board::take_peripherals,
+    pwm::{servo_timer_config, Servo, SERVO_MAX_ANGLE},
+};
+use esp_idf_svc::hal::ledc::{LedcDriver, LedcTimerDriver};
+
+// Pause between two 1° steps, sets the sweep speed.
+const STEP_DELAY: Duration = Duration::from_millis(15);
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+
+    let timer = LedcTimerDriver::new(peripherals.ledc.timer0, &servo_timer_config()).unwrap();
+    let channel =
+        LedcDriver::new(peripherals.ledc.channel0, &timer, peripherals.pins.gpio1).unwrap();
+    let mut servo = Servo::new(channel);
+
+    loop {
+        for angle in (0..=SERVO_MAX_ANGLE).chain((0..SERVO_MAX_ANGLE).rev()) {
+            servo.set_angle(angle).unwrap();
+            std::thread::sleep(STEP_DELAY);
+        }
+        log::info!("Completed a sweep");
+    }
+}
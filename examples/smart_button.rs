@@ -0,0 +1,30 @@
+//! Telling clicks, double clicks and long presses apart on the button on
+//! GPIO9 (BOOT on most ESP32-C3 boards), using edge interrupts.
+
+use std::time::Duration;
+
+use buds::{
+    board::take_peripherals,
+    gpio::{ClickEvent, SmartButton, SmartButtonConfig},
+};
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+
+    let config = SmartButtonConfig::new()
+        .double_click_window(Duration::from_millis(400))
+        .long_press(Duration::from_secs(1));
+    let button = SmartButton::new(peripherals.pins.gpio9, config).unwrap();
+
+    log::info!("Click, double click or hold the BOOT button");
+    for event in button.events() {
+        match event {
+            ClickEvent::Click => log::info!("Click"),
+            ClickEvent::DoubleClick => log::info!("Double click"),
+            ClickEvent::LongPress => log::info!("Long press"),
+        }
+    }
+}
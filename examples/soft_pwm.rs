@@ -0,0 +1,29 @@
+//! Dimming an LED on GPIO3 with a timer driven software PWM, for when the
+//! LEDC channels are all taken by something else.
+
+use std::{thread, time::Duration};
+
+use buds::{prelude::*, pwm::SoftPwm};
+
+// Fast enough not to flicker, at a handful of interrupts per millisecond.
+const FREQUENCY_HZ: u32 = 200;
+
+// Time between two duty steps, a full fade takes 100 of them.
+const STEP: Duration = Duration::from_millis(10);
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+    let mut led =
+        SoftPwm::new(peripherals.pins.gpio3, TimerId::Group0Timer0, FREQUENCY_HZ).unwrap();
+
+    loop {
+        for percent in (0..=100).chain((0..100).rev()) {
+            led.set_duty(percent);
+            thread::sleep(STEP);
+        }
+        log::info!("Faded in and out, duty back at {}%", led.duty());
+    }
+}
@@ -0,0 +1,70 @@
+//! Filling a 240x240 ST7789 display with color bars.
+//! Wiring: SCLK GPIO6, MOSI GPIO7, CS GPIO10, DC GPIO2 & RST GPIO3.
+
+use std::time::Duration;
+
+use buds::{
+    board::take_peripherals,
+    display::{SpiDisplay, ST7789_INIT},
+};
+use esp_idf_svc::hal::{
+    gpio::AnyIOPin,
+    prelude::*,
+    spi::{SpiConfig, SpiDeviceDriver, SpiDriverConfig},
+};
+
+const WIDTH: usize = 240;
+const HEIGHT: usize = 240;
+
+// RGB565: white, yellow, cyan, green, magenta, red, blue & black.
+const BARS: [u16; 8] = [
+    0xffff, 0xffe0, 0x07ff, 0x07e0, 0xf81f, 0xf800, 0x001f, 0x0000,
+];
+
+// ST7789 commands to select the area the pixels are written to.
+const CASET: u8 = 0x2a;
+const RASET: u8 = 0x2b;
+const RAMWR: u8 = 0x2c;
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+    let pins = peripherals.pins;
+
+    let device = SpiDeviceDriver::new_single(
+        peripherals.spi2,
+        pins.gpio6,
+        pins.gpio7,
+        Option::<AnyIOPin>::None,
+        Some(pins.gpio10),
+        &SpiDriverConfig::new(),
+        &SpiConfig::new().baudrate(26.MHz().into()).write_only(true),
+    )
+    .unwrap();
+    let mut display = SpiDisplay::new(device, pins.gpio2, pins.gpio3).unwrap();
+    display.init(ST7789_INIT).unwrap();
+    log::info!("Display initialized");
+
+    // Every row is the same, so one is built and sent HEIGHT times.
+    let row: Vec<u8> = (0..WIDTH)
+        .flat_map(|x| BARS[x * BARS.len() / WIDTH].to_be_bytes())
+        .collect();
+
+    let last_col = (WIDTH as u16 - 1).to_be_bytes();
+    let last_row = (HEIGHT as u16 - 1).to_be_bytes();
+    display.command(CASET).unwrap();
+    display.data(&[0, 0, last_col[0], last_col[1]]).unwrap();
+    display.command(RASET).unwrap();
+    display.data(&[0, 0, last_row[0], last_row[1]]).unwrap();
+    display.command(RAMWR).unwrap();
+    for _ in 0..HEIGHT {
+        display.write_pixels(&row).unwrap();
+    }
+    log::info!("Test pattern drawn");
+
+    loop {
+        std::thread::sleep(Duration::from_secs(10));
+    }
+}
@@ -0,0 +1,35 @@
+// This example showcases timing code with a hardware timer used as a
+// stopwatch, elapsed() turning the counter into a Duration.
+
+use std::{thread, time::Duration};
+
+use buds::prelude::*;
+
+// Something worth timing.
+fn sum_of_squares(n: u64) -> u64 {
+    (1..=n).map(|i| i * i).sum()
+}
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let mut stopwatch = HwTimer::free_running(TimerId::Group0Timer0).unwrap();
+
+    loop {
+        stopwatch.reset_and_elapsed().unwrap();
+        let sum = sum_of_squares(100_000);
+        let computing = stopwatch.reset_and_elapsed().unwrap();
+
+        thread::sleep(Duration::from_millis(100));
+        let sleeping = stopwatch.elapsed().unwrap();
+
+        log::info!(
+            "Sum {} took {:?}, sleeping 100ms took {:?}",
+            sum,
+            computing,
+            sleeping
+        );
+        thread::sleep(Duration::from_secs(1));
+    }
+}
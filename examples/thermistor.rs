@@ -0,0 +1,44 @@
+//! Logging the temperature of a 10kΩ NTC thermistor (beta 3950) wired to
+//! GND, with a 10kΩ series resistor from 3.3V to GPIO2, every two seconds.
+//!
+//! The thermistor's resistance is logged along, to check it against a
+//! multimeter or the datasheet's table.
+
+use std::{thread, time::Duration};
+
+use buds::{
+    adc::{attenuation, AnalogInput},
+    board::take_peripherals,
+    thermistor::{Coefficients, Divider, Thermistor},
+};
+use esp_idf_svc::hal::gpio::Gpio2;
+
+const SERIES_OHMS: f32 = 10_000.0;
+
+// From the thermistor's datasheet.
+const NTC: Coefficients = Coefficients::Beta {
+    r0: 10_000.0,
+    t0: 25.0,
+    beta: 3950.0,
+};
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+
+    let input: AnalogInput<{ attenuation::DB_11 }, Gpio2> =
+        AnalogInput::new(peripherals.adc1, peripherals.pins.gpio2).unwrap();
+    let mut ntc = Thermistor::new(input, SERIES_OHMS, Divider::NtcToGround, NTC);
+
+    loop {
+        // One reading for both, temperature_c() would take another.
+        match ntc.resistance() {
+            Ok(ohms) => log::info!("{:.1}°C ({:.0}Ω)", NTC.temperature_c(ohms), ohms),
+            Err(e) => log::warn!("No temperature: {}", e),
+        }
+
+        thread::sleep(Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,66 @@
+// This example showcases two hardware timers running independently,
+// each with its own alarm rate and callback. The fast one gets the highest
+// interrupt level, so its alarm can preempt the slow one's.
+
+use std::{
+    sync::atomic::{AtomicU32, Ordering},
+    thread,
+    time::Duration,
+};
+
+use buds::{prelude::*, timer::IsrFlags};
+use esp_idf_svc::sys::timer_config_t;
+
+static SLOW_TICKS: AtomicU32 = AtomicU32::new(0);
+static FAST_TICKS: AtomicU32 = AtomicU32::new(0);
+
+fn config() -> timer_config_t {
+    TimerConfigBuilder::new()
+        .divider(divider_for_hz(50_000).unwrap())
+        .build()
+        .unwrap()
+}
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    // One timer from each group, these exist on every ESP32 variant.
+    let mut slow = HwTimer::new(TimerId::Group0Timer0, config()).unwrap();
+    let mut fast = HwTimer::new(TimerId::Group1Timer0, config()).unwrap();
+
+    // A timer can only be owned once.
+    match HwTimer::new(TimerId::Group0Timer0, config()) {
+        Err(TimerError::AlreadyInUse(id)) => log::info!("{:?} is taken, as expected", id),
+        _ => log::error!("Claimed the same timer twice!"),
+    }
+
+    slow.set_alarm_hz(1.0).unwrap();
+    slow.on_alarm(|| {
+        SLOW_TICKS.fetch_add(1, Ordering::Relaxed);
+    })
+    .unwrap();
+
+    fast.set_alarm_hz(5.0).unwrap();
+    fast.on_alarm_with_flags(
+        || {
+            FAST_TICKS.fetch_add(1, Ordering::Relaxed);
+        },
+        IsrFlags::new().level(3),
+    )
+    .unwrap();
+
+    for timer in [&mut slow, &mut fast] {
+        timer.enable_interrupt().unwrap();
+        timer.start().unwrap();
+    }
+
+    loop {
+        log::info!(
+            "Slow: {}, Fast: {}",
+            SLOW_TICKS.load(Ordering::Relaxed),
+            FAST_TICKS.load(Ordering::Relaxed)
+        );
+        thread::sleep(Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,73 @@
+//! Echoing back everything received on UART1 (TX on GPIO4, RX on GPIO5).
+//! Connect a USB serial adapter and type into its terminal, every line
+//! received is also logged.
+
+use buds::{
+    board::take_peripherals,
+    uart::{write_all, UartConfig},
+};
+use esp_idf_svc::hal::{
+    delay::TickType,
+    gpio::AnyIOPin,
+    uart::{config::Parity, UartDriver},
+};
+
+// Line settings, match them with the other end.
+const BAUDRATE: u32 = 115_200;
+const PARITY: Parity = Parity::ParityNone;
+
+// How long a read waits for data before we check on the pending line.
+const READ_TIMEOUT_MS: u64 = 100;
+
+// Longest line we buffer before logging it anyway.
+const MAX_LINE_LEN: usize = 256;
+
+fn log_line(line: &mut Vec<u8>) {
+    if !line.is_empty() {
+        log::info!("Received: {}", String::from_utf8_lossy(line).trim_end());
+        line.clear();
+    }
+}
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+
+    let config = UartConfig::new().baudrate(BAUDRATE).parity(PARITY).build();
+    let uart = UartDriver::new(
+        peripherals.uart1,
+        peripherals.pins.gpio4,
+        peripherals.pins.gpio5,
+        Option::<AnyIOPin>::None,
+        Option::<AnyIOPin>::None,
+        &config,
+    )
+    .unwrap();
+    log::info!("Echoing on UART1 at {} baud", BAUDRATE);
+
+    let timeout = TickType::new_millis(READ_TIMEOUT_MS).ticks();
+    let mut buf = [0u8; 64];
+    let mut line = Vec::with_capacity(MAX_LINE_LEN);
+
+    loop {
+        // Returns as soon as some bytes arrived, so a read may hold only
+        // part of a line, or several.
+        let len = uart.read(&mut buf, timeout).unwrap();
+        if len == 0 {
+            // Timed out, log what arrived without a line ending so far.
+            log_line(&mut line);
+            continue;
+        }
+
+        write_all(&uart, &buf[..len]).unwrap();
+
+        for &byte in &buf[..len] {
+            line.push(byte);
+            if byte == b'\n' || line.len() == MAX_LINE_LEN {
+                log_line(&mut line);
+            }
+        }
+    }
+}
@@ -0,0 +1,29 @@
+//! Printing the distance measured by an HC-SR04, with TRIG on GPIO4 and
+//! ECHO (through a 5V to 3.3V divider) on GPIO5.
+
+use buds::{
+    prelude::*,
+    ultrasonic::{Ultrasonic, UltrasonicError},
+};
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+    let mut sensor = Ultrasonic::new(
+        peripherals.pins.gpio4,
+        peripherals.pins.gpio5,
+        TimerId::Group0Timer0,
+    )
+    .unwrap();
+
+    // distance_cm() already waits between measurements.
+    loop {
+        match sensor.distance_cm() {
+            Ok(distance) => log::info!("Distance: {:.1} cm", distance),
+            Err(UltrasonicError::Timeout) => log::info!("Nothing within range"),
+            Err(e) => log::error!("Measurement failed: {}", e),
+        }
+    }
+}
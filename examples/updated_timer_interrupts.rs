@@ -1,11 +1,9 @@
 // This example showcases how to configure ESP32 timers and the interrupts
-// using the TimerDriver API.
+// using closures registered through HwTimer::on_alarm().
 
-use esp_idf_svc::hal::{gpio::Gpio1, peripherals::Peripherals, timer::TimerDriver};
+use buds::{prelude::*, timer::ClockSource};
 use std::time::Duration;
 
-use esp_idf_svc::hal::gpio::{Output, PinDriver};
-
 use std::sync::atomic::{AtomicI32, Ordering};
 use std::thread;
 
@@ -15,49 +13,47 @@ fn main() {
     esp_idf_svc::sys::link_patches();
     esp_idf_svc::log::EspLogger::initialize_default();
 
-    let peripherals = Peripherals::take().unwrap();
-    let mut timer_driver = TimerDriver::new(
-        peripherals.timer00,
-        &esp_idf_svc::hal::timer::config::Config {
-            divider: 1600,
-            xtal: false,
-            auto_reload: true,
-        },
-    )
-    .unwrap();
+    let peripherals = take_peripherals().unwrap();
+
+    let mut led = PinDriver::output(peripherals.pins.gpio1)
+        .context("Setting up the LED pin")
+        .unwrap();
 
-    // Now we set the alarm.
-    // Since the clock is 80 MHz and the 1600 divider gives us 50 kHz frequency.
-    // This means 50,000 ticks per second.
-    // If we want the alarm to ring every 10 sec then it should be set to 500,000.
-    let ticks_per_second: u64 = 50000;
-    timer_driver.set_alarm(10 * ticks_per_second).unwrap();
+    // Counting from the crystal, which unlike APB keeps its rate when power
+    // management scales the clocks. The divider has to be picked for it.
+    let config = TimerConfigBuilder::new()
+        .clock_source(ClockSource::Xtal)
+        .divider(ClockSource::Xtal.divider_for_hz(50_000).unwrap())
+        .build()
+        .unwrap();
+    let mut timer = HwTimer::new(TimerId::Group0Timer0, config).unwrap();
 
-    let mut led = PinDriver::output(peripherals.pins.gpio1).unwrap();
+    // Now we set the alarm to ring every 10 sec.
+    timer.set_alarm_after(Duration::from_secs(10)).unwrap();
 
     // A simple Interrupt Service Routine that toggles an led
     // based on a timer interrupt every 10 sec.
-    let blinky_isr = || {
-        // led.toggle();
-        READING.fetch_add(1, Ordering::Relaxed);
-        move |mut led: PinDriver<Gpio1, Output>| led.toggle();
-    };
-
-    // The TimeDriver only seems to take closures and with closures, passing led
-    // only works with moves. And moves does not cause the toggle to work.
-    // Note that the ISR does get called!
-    let _ = unsafe { timer_driver.subscribe_nonstatic(blinky_isr).unwrap() };
-    timer_driver.set_counter(0).unwrap();
-    timer_driver.enable_interrupt().unwrap();
-    timer_driver.enable_alarm(true).unwrap();
-    timer_driver.enable(true).unwrap();
+    //
+    // The previous version of this example passed a closure that *returned*
+    // another closure (`move |mut led| led.toggle()`) to the TimerDriver.
+    // That inner closure was only ever constructed and thrown away, never
+    // called, so the ISR ran but the led never toggled. Here the led moves
+    // into the closure, which on_alarm() keeps on the heap for as long as
+    // the timer exists. It can't borrow the led instead: a leaked timer
+    // would keep toggling it after it's gone (on_alarm_nonstatic() allows
+    // that, as an unsafe fn).
+    timer
+        .on_alarm(move || {
+            READING.fetch_add(1, Ordering::Relaxed);
+            let _ = led.toggle();
+        })
+        .unwrap();
+    timer.enable_interrupt().unwrap();
+    timer.start().unwrap();
 
     log::info!("Running test...");
 
     loop {
-        timer_driver
-            .counter()
-            .inspect(|x| log::info!("Counter Value: {x}"));
         log::info!("READING: {}", READING.load(Ordering::Relaxed));
         thread::sleep(Duration::from_millis(1000));
     }
@@ -0,0 +1,38 @@
+//! Rebooting a hung device with the task watchdog.
+//! The loop feeds the watchdog for a few iterations, then pretends to get
+//! stuck (as if waiting on an ISR that never fires) and the chip resets.
+
+use std::time::Duration;
+
+use buds::watchdog::Watchdog;
+use esp_idf_svc::hal::reset::ResetReason;
+
+// Each iteration below takes about a second, sleep included.
+const WATCHDOG_TIMEOUT: Duration = Duration::from_secs(3);
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    // After the reset, this shows it came from the watchdog.
+    log::info!("Reset reason: {:?}", ResetReason::get());
+
+    let watchdog = Watchdog::init(WATCHDOG_TIMEOUT).unwrap();
+    watchdog.add_current_task().unwrap();
+
+    for i in 0..5 {
+        watchdog.feed().unwrap();
+        log::info!("Iteration {}, watchdog fed", i);
+        std::thread::sleep(Duration::from_secs(1));
+    }
+
+    log::warn!(
+        "Blocking without feeding, expect a reset in {:?}",
+        watchdog.timeout()
+    );
+    loop {
+        // Sleeping instead of spinning makes no difference, what matters is
+        // that feed() isn't called any more.
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
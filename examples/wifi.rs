@@ -1,26 +1,56 @@
 //! Setting up wifi on esp32 with std implementation.
 //! We also try to show the status of the connection using an rgb.
 
-use std::time::Duration;
-
-use esp_idf_svc::{
-    hal::peripherals,
-    sys::{
-        wifi_mode_t_WIFI_MODE_AP, wifi_mode_t_WIFI_MODE_APSTA, wifi_mode_t_WIFI_MODE_MAX,
-        wifi_mode_t_WIFI_MODE_NAN, wifi_mode_t_WIFI_MODE_NULL, wifi_mode_t_WIFI_MODE_STA,
+use std::{sync::mpsc, thread, time::Duration};
+
+use buds::{
+    mdns::{add_http_service, start_mdns, HTTP_PORT},
+    prelude::*,
+    status_led::{Status, StatusLed},
+    wifi::{
+        connect_timeout, current_rssi, ensure_connected, format_netinfo, load_credentials,
+        signal_quality, ConnectError, WifiMode, WifiModeController,
     },
 };
 
-fn parse_wifi_mode(current_mode: u32) -> String {
-    match current_mode {
-        wifi_mode_t_WIFI_MODE_APSTA => "APSTA".into(),
-        wifi_mode_t_WIFI_MODE_AP => "AP".into(),
-        wifi_mode_t_WIFI_MODE_STA => "STA".into(),
-        wifi_mode_t_WIFI_MODE_MAX => "MAX".into(),
-        wifi_mode_t_WIFI_MODE_NAN => "NAN".into(),
-        wifi_mode_t_WIFI_MODE_NULL => "NULL".into(),
-        _ => "unexpected".into(),
-    }
+// Name advertised over mDNS, the device answers to buds.local.
+const HOSTNAME: &str = "buds";
+
+// How fast the LED blinks while connecting.
+const BLINK_INTERVAL: Duration = Duration::from_millis(250);
+
+// How long one connection attempt may take, AP unreachable or not.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Drives the LED from its own thread so it keeps blinking while the main
+// thread is blocked connecting.
+fn spawn_status_led(mut led: StatusLed<'static>) -> mpsc::Sender<Status> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut status = Status::Connecting;
+        loop {
+            if let Err(e) = led.show(status) {
+                log::error!("Failed to update the status LED: {:?}", e);
+            }
+
+            // Only the connecting state needs to be redrawn to blink.
+            let next = if status == Status::Connecting {
+                match rx.recv_timeout(BLINK_INTERVAL) {
+                    Err(mpsc::RecvTimeoutError::Timeout) => continue,
+                    next => next.ok(),
+                }
+            } else {
+                rx.recv().ok()
+            };
+
+            match next {
+                Some(next) => status = next,
+                // The main thread went away, nothing left to show.
+                None => return,
+            }
+        }
+    });
+    tx
 }
 
 fn main() {
@@ -31,112 +61,131 @@ fn main() {
     // Bind the log crate to the ESP Logging facilities.
     esp_idf_svc::log::EspLogger::initialize_default();
 
-    // Get the WiFi SSID & Password from Environment Variables.
-    let wifi_ssid = env!("WIFI_SSID", "Export WIFI_SSID Enviroment Variable");
-    let wifi_pwd = env!("WIFI_PWD", "Export WIFI_PWD Enviroment Variable");
-
     // Take peripherals, System event loop & non-volatile storafe.
-    let periperals = peripherals::Peripherals::take().unwrap();
-    let system_event_loop = esp_idf_svc::eventloop::EspSystemEventLoop::take().unwrap();
-    let nvs_storage = esp_idf_svc::nvs::EspDefaultNvsPartition::take().unwrap();
-
-    // Now we initlialize wifi.
-    let mut wifi =
-        esp_idf_svc::wifi::EspWifi::new(periperals.modem, system_event_loop, Some(nvs_storage))
-            .unwrap();
-    log::info!("Initialized WiFi...");
-
-    // Attempting to set wifi to blocking.
-    // It looks like we get the blocking wifi by default?
-    // wifi = esp_idf_svc::wifi::BlockingWifi::wrap(wifi, system_event_loop).unwrap();
-    log::info!("Set up blocking wifi...");
-
-    // Esp Wifi Configuration.
-    wifi.set_configuration(&esp_idf_svc::wifi::Configuration::Client(
-        esp_idf_svc::wifi::ClientConfiguration {
-            ssid: wifi_ssid.try_into().unwrap(),
-            password: wifi_pwd.try_into().unwrap(),
-            ..Default::default()
-        },
-    ))
+    let periperals = take_peripherals().unwrap();
+    let system_event_loop = esp_idf_svc::eventloop::EspSystemEventLoop::take()
+        .context("Taking the system event loop")
+        .unwrap();
+    let nvs_storage = esp_idf_svc::nvs::EspDefaultNvsPartition::take()
+        .context("Taking the NVS partition")
+        .unwrap();
+
+    // Get the WiFi SSID & Password from NVS, or the build time Environment
+    // Variables if none were stored yet.
+    let (wifi_ssid, wifi_pwd) = load_credentials(&nvs_storage)
+        .expect("Store credentials in NVS or export WIFI_SSID & WIFI_PWD Enviroment Variables");
+
+    // Common cathode RGB LED on GPIO3 (red), GPIO4 (green) & GPIO5 (blue).
+    // Boards with an on board WS2812 can use StatusLed::from_neopixel()
+    // with a one pixel NeoPixel instead.
+    let pins = periperals.pins;
+    let led = StatusLed::new(pins.gpio3, pins.gpio4, pins.gpio5, false).unwrap();
+    let status = spawn_status_led(led);
+
+    let wifi = EspWifi::new(
+        periperals.modem,
+        system_event_loop.clone(),
+        Some(nvs_storage),
+    )
+    .context("Creating the wifi driver")
     .unwrap();
-    log::info!("Set up client configuration...");
-
-    // For curiosity, lets check the currentl Wifi mode of operation.
-    // esp_wifi_get_mode takes a out param.
-    // Notes: turns out STA is the default here?
-    let mut current_mode: u32 = 0;
-    // SAFETY: wifi_mode_t_WIFI_MODE_STA is always u32.
-    let status = unsafe { esp_idf_svc::hal::sys::esp_wifi_get_mode(&mut current_mode) };
-    esp_idf_svc::hal::sys::EspError::convert(status).unwrap();
-
-    log::warn!("Current Wifi Mode: {}", parse_wifi_mode(current_mode));
-
-    // Change WiFi Mode.
-    // Available modes are:
-    // AP : Access Point
-    // STA : Client Mode
-    // APSTA: AP + STA
-    // NAN: Wi-Fi AwareTM (NAN)
-    // MAX: ??
-    // NULL: ??
-    // In our case, we only need STA mode
-    // NOTE: This is a C ABI call and needs to be wrapped in unsafe.
-    // SAFETY: wifi_mode_t_WIFI_MODE_STA is always u32.
-    unsafe {
-        let status = esp_idf_svc::hal::sys::esp_wifi_set_mode(
-            esp_idf_svc::hal::sys::wifi_mode_t_WIFI_MODE_STA,
-        );
-    }
-    esp_idf_svc::hal::sys::EspError::convert(status).unwrap();
-
-    // Check the new WiFi mode.
-    // SAFETY: wifi_mode_t_WIFI_MODE_STA is always u32.
-    unsafe {
-        let status = esp_idf_svc::hal::sys::esp_wifi_get_mode(&mut current_mode);
-    }
-    esp_idf_svc::hal::sys::EspError::convert(status).unwrap();
-    log::warn!("New Wifi Mode: {}", parse_wifi_mode(current_mode));
-
-    wifi.start().unwrap();
-    log::info!("Started the wifi...");
-
-    // If scanning is needed for WiFi, use this functions.
-    /*
-    for ap in wifi.scan().unwrap() {
-        if ap.ssid == "<SSID>" {
-            println!("\n{:?}\n", ap);
-            break;
+    let mut wifi = BlockingWifi::wrap(wifi, system_event_loop)
+        .context("Wrapping the wifi driver")
+        .unwrap();
+
+    // Starts the wifi, connects and waits for DHCP to give us an address.
+    let config = Configuration::Client(ClientConfiguration {
+        ssid: wifi_ssid.as_str().try_into().unwrap(),
+        password: wifi_pwd.as_str().try_into().unwrap(),
+        ..Default::default()
+    });
+    status.send(Status::Connecting).ok();
+    loop {
+        match connect_timeout(&mut wifi, &config, CONNECT_TIMEOUT) {
+            Ok(()) => break,
+            // The AP may only be out of reach for now, keep trying.
+            Err(ConnectError::Timeout) => {
+                log::warn!("Wifi not up after {:?}, retrying", CONNECT_TIMEOUT);
+            }
+            Err(e) => {
+                // Leaves the LED red, there is nothing left to run.
+                status.send(Status::Error).ok();
+                log::error!("Wifi connection failed: {:?}", e);
+                return;
+            }
         }
-    } */
-
-    match wifi.connect() {
-        Ok(_) => log::info!("Attempting to connect to wifi..."),
-        Err(e) => log::error!("Wifi connection failed: {:?}", e),
     }
+    status.send(Status::Connected).ok();
+    log::info!(
+        "Wifi Connection established, IP: {}",
+        wifi.wifi()
+            .sta_netif()
+            .get_ip_info()
+            .context("Reading the STA IP info")
+            .unwrap()
+            .ip
+    );
 
-    // We need to wait for the connection status to be successful.
-    // Otherwise we won't be able to complete the connection.
-    while !wifi.is_connected().unwrap() {
-        println!("Waiting for connection: ...");
-        std::thread::sleep(Duration::new(10, 0));
+    // A client configuration puts the wifi in STA mode.
+    let mut modes = WifiModeController::new(wifi.wifi_mut());
+    log::info!("Current Wifi Mode: {}", modes.get());
+
+    // Switching needs the wifi driver, so get() after set() is checked
+    // here, on the chip, and not by the host tests of the wifi module.
+    // APSTA brings the AP up next to the connected station, STA takes it
+    // down again.
+    for mode in [WifiMode::ApSta, WifiMode::Sta] {
+        modes.set(mode).context("Switching the wifi mode").unwrap();
+        assert_eq!(modes.get(), mode);
     }
-    log::info!("Wifi Connection established");
-
-    // sta_netif returns the client mode ip addresses.
-    let net_info = wifi.sta_netif();
+    assert!(modes.set(WifiMode::Max).is_err());
+    assert_eq!(modes.get(), WifiMode::Sta);
+
+    // Advertise ourselves so the device can be found without knowing the
+    // DHCP address. Not being discoverable is no reason to stop running.
+    let _mdns = match start_mdns(HOSTNAME, "buds wifi example") {
+        Ok(mut mdns) => {
+            if let Err(e) = add_http_service(&mut mdns, HTTP_PORT) {
+                log::warn!("Failed to advertise the HTTP service: {:?}", e);
+            }
+            log::info!("Reachable as {}.local", HOSTNAME);
+            Some(mdns)
+        }
+        Err(e) => {
+            log::error!("mDNS init failed: {:?}", e);
+            None
+        }
+    };
 
     log::warn!(
         "WiFi AP Status Is On ?: {}",
-        wifi.ap_netif().is_up().unwrap()
+        wifi.wifi()
+            .ap_netif()
+            .is_up()
+            .context("Checking the AP interface")
+            .unwrap()
     );
 
     loop {
-        log::info!(
-            "\nMAC: {:?}, IP Info: {:?}\n",
-            net_info.get_mac().unwrap(),
-            net_info.get_ip_info().unwrap()
-        );
+        // Reconnects if the connection dropped in the meantime.
+        if !wifi.is_connected().unwrap_or(false) {
+            status.send(Status::Connecting).ok();
+        }
+        match ensure_connected(wifi.wifi_mut(), 5) {
+            Ok(()) => status.send(Status::Connected).ok(),
+            Err(e) => {
+                log::error!("Wifi reconnection failed: {:?}", e);
+                status.send(Status::Error).ok()
+            }
+        };
+
+        match current_rssi(wifi.wifi()) {
+            Ok(rssi) => log::info!("Signal: {} dBm ({}%)", rssi, signal_quality(rssi)),
+            Err(e) => log::warn!("No signal: {:?}", e),
+        }
+
+        // sta_netif returns the client mode ip addresses.
+        log::info!("\n{}", format_netinfo(wifi.wifi().sta_netif()));
         std::thread::sleep(Duration::new(10, 0));
     }
 }
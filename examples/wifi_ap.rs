@@ -0,0 +1,46 @@
+//! Running the esp32 as a WiFi access point.
+//! Clients joining and leaving the network are logged.
+
+use std::time::Duration;
+
+use buds::{
+    prelude::*,
+    wifi::{connected_clients, start_ap},
+};
+use esp_idf_svc::wifi::WifiEvent;
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+    let system_event_loop = EspSystemEventLoop::take().unwrap();
+    let nvs_storage = EspDefaultNvsPartition::take().unwrap();
+
+    let mut wifi = EspWifi::new(
+        peripherals.modem,
+        system_event_loop.clone(),
+        Some(nvs_storage),
+    )
+    .unwrap();
+
+    // Log clients as they come and go, the subscription lives as long as
+    // the returned handle.
+    let _subscription = system_event_loop
+        .subscribe::<WifiEvent, _>(|event| match event {
+            WifiEvent::ApStaConnected => log::info!("A client joined"),
+            WifiEvent::ApStaDisconnected => log::info!("A client left"),
+            _ => {}
+        })
+        .unwrap();
+
+    // Leave the password empty for an open network.
+    start_ap(&mut wifi, "buds", "budsbudsbuds", 6).unwrap();
+    log::info!("AP IP Info: {:?}", wifi.ap_netif().get_ip_info().unwrap());
+
+    loop {
+        let clients = connected_clients().unwrap();
+        log::info!("{} client(s) connected: {:02X?}", clients.len(), clients);
+        std::thread::sleep(Duration::new(10, 0));
+    }
+}
@@ -0,0 +1,72 @@
+//! Connecting to wifi from async code, on the block_on() executor of
+//! esp-idf-hal. The connection races a 30 second deadline: if the deadline
+//! wins, the connection future is dropped, which stops the radio, and the
+//! example tries again.
+
+use core::{future::Future, pin::pin, task::Poll, time::Duration};
+
+use buds::{
+    prelude::*,
+    wifi::{connect_async, load_credentials},
+};
+use esp_idf_svc::{hal::task::block_on, timer::EspTaskTimerService};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Polls both futures, resolving with the first one done (`first`'s result
+// if both are), and drops the other.
+async fn first_of<A: Future, B: Future>(first: A, second: B) -> Result<A::Output, B::Output> {
+    let (mut first, mut second) = (pin!(first), pin!(second));
+    core::future::poll_fn(|cx| {
+        if let Poll::Ready(output) = first.as_mut().poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+        second.as_mut().poll(cx).map(Err)
+    })
+    .await
+}
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let mut peripherals = take_peripherals().unwrap();
+    let system_event_loop = EspSystemEventLoop::take().unwrap();
+    let timer_service = EspTaskTimerService::new().unwrap();
+    let nvs_storage = EspDefaultNvsPartition::take().unwrap();
+
+    let (wifi_ssid, wifi_pwd) = load_credentials(&nvs_storage)
+        .expect("Store credentials in NVS or export WIFI_SSID & WIFI_PWD Enviroment Variables");
+    let config = Configuration::Client(ClientConfiguration {
+        ssid: wifi_ssid.as_str().try_into().unwrap(),
+        password: wifi_pwd.as_str().try_into().unwrap(),
+        ..Default::default()
+    });
+
+    block_on(async {
+        let mut deadline = timer_service.timer_async().unwrap();
+        let wifi = loop {
+            let connecting = connect_async(
+                &mut peripherals.modem,
+                system_event_loop.clone(),
+                timer_service.clone(),
+                nvs_storage.clone(),
+                &config,
+            );
+            match first_of(connecting, deadline.after(CONNECT_TIMEOUT)).await {
+                Ok(Ok(wifi)) => break wifi,
+                Ok(Err(e)) => log::error!("Failed to connect: {}", e),
+                Err(_) => log::warn!("No address after {:?}, retrying", CONNECT_TIMEOUT),
+            }
+        };
+
+        let ip_info = wifi.wifi().sta_netif().get_ip_info().unwrap();
+        log::info!("Wifi up, IP {}", ip_info.ip);
+
+        // The executor keeps running, the connection with it.
+        loop {
+            deadline.after(Duration::from_secs(10)).await.unwrap();
+            log::info!("Still connected: {}", wifi.is_connected().unwrap());
+        }
+    })
+}
@@ -0,0 +1,59 @@
+//! A battery friendly station: wake up, connect, report the potentiometer
+//! on GPIO2, then drop off the network cleanly and deep sleep for a minute.
+
+use std::time::Duration;
+
+use buds::{
+    adc::{attenuation, raw_to_percent, AnalogInput},
+    power::{deep_sleep_for, wake_cause},
+    prelude::*,
+    wifi::{connect_blocking, load_credentials, stop},
+};
+use esp_idf_svc::hal::gpio::Gpio2;
+
+const SLEEP_TIME: Duration = Duration::from_secs(60);
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    log::info!("Woke up: {:?}", wake_cause());
+
+    let peripherals = take_peripherals().unwrap();
+    let system_event_loop = EspSystemEventLoop::take().unwrap();
+    let nvs_storage = EspDefaultNvsPartition::take().unwrap();
+
+    let mut pot: AnalogInput<{ attenuation::DB_11 }, Gpio2> =
+        AnalogInput::new(peripherals.adc1, peripherals.pins.gpio2).unwrap();
+
+    let (wifi_ssid, wifi_pwd) = load_credentials(&nvs_storage)
+        .expect("Store credentials in NVS or export WIFI_SSID & WIFI_PWD Enviroment Variables");
+    let config = Configuration::Client(ClientConfiguration {
+        ssid: wifi_ssid.as_str().try_into().unwrap(),
+        password: wifi_pwd.as_str().try_into().unwrap(),
+        ..Default::default()
+    });
+    match connect_blocking(
+        peripherals.modem,
+        system_event_loop.clone(),
+        nvs_storage,
+        &config,
+    ) {
+        Ok(mut wifi) => {
+            let ip = wifi.wifi().sta_netif().get_ip_info().unwrap().ip;
+            let raw = pot.read_averaged(16).unwrap();
+            // This is where the reading would be sent somewhere.
+            log::info!("Reading from {}: {} ({}%)", ip, raw, raw_to_percent(raw));
+
+            // Leaving without saying goodbye makes the AP keep the station
+            // around until it times out.
+            if let Err(e) = stop(wifi.wifi_mut(), &system_event_loop) {
+                log::warn!("Wifi didn't stop cleanly: {}", e);
+            }
+        }
+        // Better luck on the next wake up.
+        Err(e) => log::error!("Wifi connection failed: {}", e),
+    }
+
+    deep_sleep_for(SLEEP_TIME);
+}
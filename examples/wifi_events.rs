@@ -0,0 +1,67 @@
+//! Logging every change of the wifi connection: started, connected,
+//! disconnected, got an address and lost it, and reconnecting each time
+//! the station drops off the AP.
+//!
+//! The callbacks run on the system event loop, so they only log and hand
+//! the disconnects over to the main thread, which does the reconnecting.
+
+use std::sync::mpsc;
+
+use buds::{
+    prelude::*,
+    wifi::{connect_blocking, connect_with_retry, load_credentials, on_ip_event, on_wifi_event},
+};
+use esp_idf_svc::{netif::IpEvent, wifi::WifiEvent};
+
+// Connection attempts on each reconnect before giving up.
+const RECONNECT_ATTEMPTS: u32 = 5;
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+    let sysloop = EspSystemEventLoop::take().unwrap();
+    let nvs = EspDefaultNvsPartition::take().unwrap();
+    let (ssid, password) = load_credentials(&nvs)
+        .expect("Store credentials in NVS or export WIFI_SSID & WIFI_PWD Enviroment Variables");
+
+    // Subscribed before starting the wifi, so the first events aren't
+    // missed. Each callback stays subscribed until its handle is dropped,
+    // at the end of main.
+    let (disconnects, disconnected) = mpsc::channel();
+    let _wifi_events = on_wifi_event(&sysloop, move |event| match event {
+        WifiEvent::StaStarted => log::info!("Wifi started"),
+        WifiEvent::StaStopped => log::info!("Wifi stopped"),
+        WifiEvent::StaConnected => log::info!("Wifi connected"),
+        WifiEvent::StaDisconnected => {
+            log::warn!("Wifi disconnected");
+            let _ = disconnects.send(());
+        }
+        _ => {}
+    })
+    .unwrap();
+    let _ip_events = on_ip_event(&sysloop, |event| match event {
+        IpEvent::DhcpIpAssigned(assignment) => log::info!("Got IP {}", assignment.ip()),
+        IpEvent::DhcpIpDeassigned(_) => log::warn!("Lost IP"),
+        _ => {}
+    })
+    .unwrap();
+
+    let config = Configuration::Client(ClientConfiguration {
+        ssid: ssid.as_str().try_into().unwrap(),
+        password: password.as_str().try_into().unwrap(),
+        ..Default::default()
+    });
+    let mut wifi = connect_blocking(peripherals.modem, sysloop, nvs, &config).unwrap();
+
+    // A failed attempt disconnects too, which queues the next one.
+    for () in disconnected {
+        if wifi.is_connected().unwrap_or(false) {
+            continue;
+        }
+        if let Err(e) = connect_with_retry(wifi.wifi_mut(), RECONNECT_ATTEMPTS) {
+            log::error!("Reconnecting failed: {:?}", e);
+        }
+    }
+}
@@ -0,0 +1,69 @@
+//! Connecting to wifi from a cooperative main loop, without blocking it or
+//! spawning a thread: the LED on GPIO1 keeps blinking while the connection
+//! comes up, fast while connecting and slow once connected.
+
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use buds::{
+    prelude::*,
+    wifi::{load_credentials, ConnectState, WifiConnectFsm},
+};
+
+// One pass of the main loop, every task gets its turn this often.
+const TICK: Duration = Duration::from_millis(10);
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+    let system_event_loop = EspSystemEventLoop::take().unwrap();
+    let nvs_storage = EspDefaultNvsPartition::take().unwrap();
+
+    let (wifi_ssid, wifi_pwd) = load_credentials(&nvs_storage)
+        .expect("Store credentials in NVS or export WIFI_SSID & WIFI_PWD Enviroment Variables");
+    let config = Configuration::Client(ClientConfiguration {
+        ssid: wifi_ssid.as_str().try_into().unwrap(),
+        password: wifi_pwd.as_str().try_into().unwrap(),
+        ..Default::default()
+    });
+    let wifi = EspWifi::new(peripherals.modem, system_event_loop, Some(nvs_storage)).unwrap();
+    let mut connection = WifiConnectFsm::new(wifi, &config, CONNECT_TIMEOUT).unwrap();
+
+    let mut led = PinDriver::output(peripherals.pins.gpio1).unwrap();
+    let mut blink_period = Duration::from_millis(100);
+    let mut last_blink = Instant::now();
+    let mut reported = false;
+
+    loop {
+        // The connection advances a step whenever one is done.
+        match connection.poll() {
+            ConnectState::Connecting => {}
+            ConnectState::GotIp(ip) if !reported => {
+                log::info!("Wifi connection established, IP: {}", ip);
+                blink_period = Duration::from_secs(1);
+                reported = true;
+            }
+            ConnectState::Failed(e) if !reported => {
+                log::error!("Wifi connection failed: {}", e);
+                blink_period = Duration::MAX;
+                led.set_low().unwrap();
+                reported = true;
+            }
+            _ => {}
+        }
+
+        // Anything else the loop has to keep doing, here the LED.
+        if last_blink.elapsed() >= blink_period {
+            led.toggle().unwrap();
+            last_blink = Instant::now();
+        }
+
+        thread::sleep(TICK);
+    }
+}
@@ -0,0 +1,34 @@
+//! Scanning for the WiFi networks around, strongest first, and logging
+//! them as a table every 10 seconds. No network is joined.
+
+use std::{thread, time::Duration};
+
+use buds::{
+    prelude::*,
+    wifi::{log_scan_results, scan_sorted},
+};
+
+const SCAN_INTERVAL: Duration = Duration::from_secs(10);
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    let peripherals = take_peripherals().unwrap();
+    let system_event_loop = EspSystemEventLoop::take().unwrap();
+    let nvs_storage = EspDefaultNvsPartition::take().unwrap();
+
+    let mut wifi = EspWifi::new(peripherals.modem, system_event_loop, Some(nvs_storage)).unwrap();
+    // Scanning needs the station started, not connected.
+    wifi.set_configuration(&Configuration::Client(ClientConfiguration::default()))
+        .unwrap();
+    wifi.start().unwrap();
+
+    loop {
+        match scan_sorted(&mut wifi, None) {
+            Ok(aps) => log_scan_results(&aps),
+            Err(e) => log::error!("Scan failed: {:?}", e),
+        }
+        thread::sleep(SCAN_INTERVAL);
+    }
+}
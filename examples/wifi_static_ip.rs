@@ -0,0 +1,55 @@
+//! Connecting to wifi with a fixed IP address instead of DHCP,
+//! e.g. for a device that acts as a server.
+
+use std::time::Duration;
+
+use buds::{
+    prelude::*,
+    wifi::{configure_static_ip, connect_with_retry},
+};
+use esp_idf_svc::ipv4::Ipv4Addr;
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    esp_idf_svc::log::EspLogger::initialize_default();
+
+    // Get the WiFi SSID & Password from Environment Variables.
+    let wifi_ssid = env!("WIFI_SSID", "Export WIFI_SSID Enviroment Variable");
+    let wifi_pwd = env!("WIFI_PWD", "Export WIFI_PWD Enviroment Variable");
+
+    let peripherals = take_peripherals().unwrap();
+    let system_event_loop = EspSystemEventLoop::take().unwrap();
+    let nvs_storage = EspDefaultNvsPartition::take().unwrap();
+
+    let mut wifi = EspWifi::new(peripherals.modem, system_event_loop, Some(nvs_storage)).unwrap();
+
+    // Adjust these to match your network.
+    configure_static_ip(
+        &mut wifi,
+        Ipv4Addr::new(192, 168, 1, 200),
+        Ipv4Addr::new(192, 168, 1, 1),
+        Ipv4Addr::new(255, 255, 255, 0),
+        Some(Ipv4Addr::new(192, 168, 1, 1)),
+    )
+    .unwrap();
+
+    wifi.set_configuration(&Configuration::Client(ClientConfiguration {
+        ssid: wifi_ssid.try_into().unwrap(),
+        password: wifi_pwd.try_into().unwrap(),
+        ..Default::default()
+    }))
+    .unwrap();
+    wifi.start().unwrap();
+    connect_with_retry(&mut wifi, 5).unwrap();
+
+    let ip_info = wifi.sta_netif().get_ip_info().unwrap();
+    log::info!(
+        "Device reachable at {}, try `ping {}`",
+        ip_info.ip,
+        ip_info.ip
+    );
+
+    loop {
+        std::thread::sleep(Duration::new(10, 0));
+    }
+}
@@ -0,0 +1,444 @@
+// One-shot ADC readings with oversampling to smooth out the noise.
+//
+// Millivolt readings go through esp_adc_cal, characterized from the
+// calibration data Espressif burns into each chip's eFuses: a two point
+// measurement on the S2, S3 and C3, the reference voltage on most ESP32s.
+// Early chips have none, an ESP32 then assumes a nominal 1100 mV reference
+// and the others a straight line up to the attenuation's full scale, which
+// can be off by 100 mV or more.
+//
+// SampledAdc takes them at a fixed rate instead, paced by a hardware timer.
+// ESP IDF's ADC reads take a lock, which can't be done from an ISR, so the
+// alarm ISR only wakes a thread that reads the ADC. The samples go through
+// an IsrRingBuffer to whoever drains them, which the thread makes room in
+// when it's full.
+
+use core::{
+    num::NonZeroU32,
+    sync::atomic::{AtomicBool, AtomicU32, Ordering},
+    time::Duration,
+};
+use std::{
+    iter,
+    sync::{mpsc, Arc},
+    thread::{self, JoinHandle},
+};
+
+#[cfg(any(esp32, esp32s2, esp32s3, esp32c3))]
+use esp_idf_svc::{
+    hal::adc::{config::Resolution, Adc},
+    sys::{
+        esp_adc_cal_characteristics_t, esp_adc_cal_characterize, esp_adc_cal_check_efuse,
+        esp_adc_cal_raw_to_voltage, esp_adc_cal_value_t,
+        esp_adc_cal_value_t_ESP_ADC_CAL_VAL_DEFAULT_VREF, ESP_OK,
+    },
+};
+use esp_idf_svc::{
+    hal::{
+        adc::{config::Config, AdcChannelDriver, AdcDriver},
+        delay::BLOCK,
+        gpio::ADCPin,
+        peripheral::Peripheral,
+        task::notification::{Notification, Notifier},
+    },
+    sys::{
+        adc_atten_t, adc_atten_t_ADC_ATTEN_DB_0, adc_atten_t_ADC_ATTEN_DB_11,
+        adc_atten_t_ADC_ATTEN_DB_2_5, adc_atten_t_ADC_ATTEN_DB_6, EspError, ESP_ERR_INVALID_ARG,
+    },
+};
+
+use crate::{
+    ringbuf::IsrRingBuffer,
+    timer::{divider_for_hz, HwTimer, TimerConfigBuilder, TimerError, TimerId},
+};
+
+pub use esp_idf_svc::hal::adc::attenuation;
+
+/// Largest value a raw reading takes at the default resolution.
+#[cfg(not(esp32s2))]
+pub const MAX_RAW: u16 = 4095;
+#[cfg(esp32s2)]
+pub const MAX_RAW: u16 = 8191;
+
+// The eFuse calibration data each chip carries.
+#[cfg(esp32)]
+const CALIBRATION_SCHEME: esp_adc_cal_value_t =
+    esp_idf_svc::sys::esp_adc_cal_value_t_ESP_ADC_CAL_VAL_EFUSE_VREF;
+#[cfg(any(esp32s2, esp32c3))]
+const CALIBRATION_SCHEME: esp_adc_cal_value_t =
+    esp_idf_svc::sys::esp_adc_cal_value_t_ESP_ADC_CAL_VAL_EFUSE_TP;
+#[cfg(esp32s3)]
+const CALIBRATION_SCHEME: esp_adc_cal_value_t =
+    esp_idf_svc::sys::esp_adc_cal_value_t_ESP_ADC_CAL_VAL_EFUSE_TP_FIT;
+
+// Reference voltage an ESP32 without eFuse data is taken to have.
+#[cfg(any(esp32, esp32s2, esp32s3, esp32c3))]
+const DEFAULT_VREF_MV: u32 = 1100;
+
+/// How much an input is attenuated, the larger the wider its range.
+///
+/// An AnalogInput takes it as its `A` parameter, e.g.
+/// `AnalogInput<{ Attenuation::Db11.raw() }, Gpio2>`, the same values as
+/// the `attenuation` constants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Attenuation {
+    Db0,
+    Db2_5,
+    Db6,
+    Db11,
+}
+
+impl Attenuation {
+    /// The `adc_atten_t` of this attenuation.
+    pub const fn raw(self) -> adc_atten_t {
+        match self {
+            Attenuation::Db0 => adc_atten_t_ADC_ATTEN_DB_0,
+            Attenuation::Db2_5 => adc_atten_t_ADC_ATTEN_DB_2_5,
+            Attenuation::Db6 => adc_atten_t_ADC_ATTEN_DB_6,
+            Attenuation::Db11 => adc_atten_t_ADC_ATTEN_DB_11,
+        }
+    }
+
+    /// The attenuation of an `adc_atten_t`, None for an unknown one.
+    #[allow(non_upper_case_globals)]
+    pub fn from_raw(raw: adc_atten_t) -> Option<Self> {
+        match raw {
+            adc_atten_t_ADC_ATTEN_DB_0 => Some(Attenuation::Db0),
+            adc_atten_t_ADC_ATTEN_DB_2_5 => Some(Attenuation::Db2_5),
+            adc_atten_t_ADC_ATTEN_DB_6 => Some(Attenuation::Db6),
+            adc_atten_t_ADC_ATTEN_DB_11 => Some(Attenuation::Db11),
+            _ => None,
+        }
+    }
+
+    /// Highest voltage in mV measured accurately, per Espressif's
+    /// datasheets. The uncalibrated curve maps MAX_RAW to it.
+    pub fn full_scale_mv(self) -> u32 {
+        #[cfg(esp32)]
+        let mv = [950, 1250, 1750, 2450];
+        #[cfg(esp32s3)]
+        let mv = [950, 1250, 1750, 3100];
+        #[cfg(not(any(esp32, esp32s3)))]
+        let mv = [750, 1050, 1300, 2500];
+
+        mv[self as usize]
+    }
+}
+
+/// Where the millivolt readings of an AnalogInput come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Calibration {
+    /// The chip's own eFuse calibration data.
+    Efuse,
+    /// esp_adc_cal's curve for a nominal 1100 mV reference, on an ESP32
+    /// without eFuse data.
+    DefaultVref,
+    /// A straight line from 0 to Attenuation::full_scale_mv(), without any
+    /// calibration data.
+    Linear,
+}
+
+// Characterizes the ADC at `attenuation` from the eFuses, or None when
+// there's nothing to go on. Only the ESP32's esp_adc_cal has a fallback of
+// its own, the others abort without eFuse data.
+#[cfg(any(esp32, esp32s2, esp32s3, esp32c3))]
+fn characterize<A: Adc>(
+    attenuation: Attenuation,
+) -> Option<(Calibration, esp_adc_cal_characteristics_t)> {
+    // SAFETY: esp_adc_cal_check_efuse() is an ESP32 ABI call.
+    let efuse = unsafe { esp_adc_cal_check_efuse(CALIBRATION_SCHEME) } == ESP_OK;
+    if !efuse && !cfg!(esp32) {
+        return None;
+    }
+
+    let mut characteristics = esp_adc_cal_characteristics_t::default();
+    // SAFETY: esp_adc_cal_characterize() is an ESP32 ABI call writing to
+    // `characteristics`.
+    let source = unsafe {
+        esp_adc_cal_characterize(
+            A::unit(),
+            attenuation.raw(),
+            Resolution::default().into(),
+            DEFAULT_VREF_MV,
+            &mut characteristics,
+        )
+    };
+    let calibration = if source == esp_adc_cal_value_t_ESP_ADC_CAL_VAL_DEFAULT_VREF {
+        Calibration::DefaultVref
+    } else {
+        Calibration::Efuse
+    };
+
+    Some((calibration, characteristics))
+}
+
+/// An analog input pin, attenuated by `A`.
+///
+/// Use `attenuation::DB_11` to cover as much of the 0–3.3V range as the
+/// ADC can, the smaller attenuations trade range for precision.
+pub struct AnalogInput<'d, const A: adc_atten_t, T: ADCPin> {
+    adc: AdcDriver<'d, T::Adc>,
+    channel: AdcChannelDriver<'d, A, T>,
+    calibration: Calibration,
+    #[cfg(any(esp32, esp32s2, esp32s3, esp32c3))]
+    characteristics: Option<esp_adc_cal_characteristics_t>,
+}
+
+impl<'d, const A: adc_atten_t, T: ADCPin> AnalogInput<'d, A, T> {
+    /// Sets up the pin and characterizes the ADC at `A` for read_mv().
+    ///
+    /// Fails with ESP_ERR_INVALID_ARG if `A` isn't one of the attenuations.
+    pub fn new(
+        adc: impl Peripheral<P = T::Adc> + 'd,
+        pin: impl Peripheral<P = T> + 'd,
+    ) -> Result<Self, EspError> {
+        let attenuation = Attenuation::from_raw(A)
+            .ok_or_else(EspError::from_infallible::<ESP_ERR_INVALID_ARG>)?;
+
+        #[cfg(any(esp32, esp32s2, esp32s3, esp32c3))]
+        let (calibration, characteristics) = match characterize::<T::Adc>(attenuation) {
+            Some((calibration, characteristics)) => (calibration, Some(characteristics)),
+            None => (Calibration::Linear, None),
+        };
+        #[cfg(not(any(esp32, esp32s2, esp32s3, esp32c3)))]
+        let calibration = Calibration::Linear;
+
+        Ok(Self {
+            adc: AdcDriver::new(adc, &Config::new())?,
+            channel: AdcChannelDriver::new(pin)?,
+            calibration,
+            #[cfg(any(esp32, esp32s2, esp32s3, esp32c3))]
+            characteristics,
+        })
+    }
+
+    /// The attenuation of the input, `A`.
+    pub fn attenuation(&self) -> Attenuation {
+        // Checked in new().
+        Attenuation::from_raw(A).unwrap()
+    }
+
+    /// What read_mv() bases its conversion on.
+    pub fn calibration(&self) -> Calibration {
+        self.calibration
+    }
+
+    /// A single reading, in mV.
+    pub fn read_mv(&mut self) -> Result<u16, EspError> {
+        let raw = self.read()?;
+        Ok(self.raw_to_mv(raw))
+    }
+
+    /// The mean of `samples` readings, in mV. The raw readings are
+    /// averaged before conversion.
+    pub fn read_mv_averaged(&mut self, samples: usize) -> Result<u16, EspError> {
+        let raw = self.read_averaged(samples)?;
+        Ok(self.raw_to_mv(raw))
+    }
+
+    /// Converts a raw reading of this input to mV.
+    pub fn raw_to_mv(&self, raw: u16) -> u16 {
+        #[cfg(any(esp32, esp32s2, esp32s3, esp32c3))]
+        if let Some(characteristics) = &self.characteristics {
+            // SAFETY: esp_adc_cal_raw_to_voltage() is an ESP32 ABI call
+            // reading `characteristics`.
+            let mv = unsafe { esp_adc_cal_raw_to_voltage(u32::from(raw), characteristics) };
+            return mv as u16;
+        }
+
+        let full_scale = self.attenuation().full_scale_mv();
+        (u32::from(raw.min(MAX_RAW)) * full_scale / u32::from(MAX_RAW)) as u16
+    }
+
+    /// A single raw reading, between 0 and MAX_RAW.
+    pub fn read(&mut self) -> Result<u16, EspError> {
+        self.adc.read_raw(&mut self.channel)
+    }
+
+    /// The mean of `samples` raw readings, at least one is always taken.
+    pub fn read_averaged(&mut self, samples: usize) -> Result<u16, EspError> {
+        let samples = samples.max(1);
+        let mut sum = 0u32;
+        for _ in 0..samples {
+            sum += u32::from(self.read()?);
+        }
+
+        Ok((sum / samples as u32) as u16)
+    }
+}
+
+/// Maps a raw reading to 0–100% of the input range.
+pub fn raw_to_percent(raw: u16) -> u8 {
+    (u32::from(raw.min(MAX_RAW)) * 100 / u32::from(MAX_RAW)) as u8
+}
+
+// Notification bits of a SampledAdc's thread, 0 and 1: the alarm sets
+// SAMPLE, drop() sets STOP.
+const SAMPLE: NonZeroU32 = NonZeroU32::MIN;
+const STOP: NonZeroU32 = SAMPLE.saturating_add(1);
+
+// Pushes `sample` into `ring`, overwriting the oldest one when it is
+// full. Only run by the sampling thread, the producer.
+fn push_overwriting<const N: usize>(
+    ring: &IsrRingBuffer<u16, N>,
+    dropped: &AtomicU32,
+    sample: u16,
+) {
+    let Err(sample) = ring.push(sample) else {
+        return;
+    };
+    // A pop() running elsewhere meanwhile makes this one fail, and maybe
+    // the push too. Either way at most one sample is lost.
+    let overwritten = ring.pop().is_some();
+    let pushed = ring.push(sample).is_ok();
+    if overwritten || !pushed {
+        dropped.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Readings of an AnalogInput taken every sample period, buffered until
+/// they're drained.
+///
+/// A hardware timer's alarm wakes a sampling thread, which takes each
+/// reading right away, so the readings are a few microseconds late but
+/// don't drift. The buffer holds the last `N` samples: once full, a new
+/// sample overwrites the oldest one and counts as dropped. Readings that
+/// fail are skipped. With a period shorter than a reading (about 40 µs)
+/// alarms merge, and fewer samples come in.
+///
+/// Drain it from a single thread, the buffer has one consumer.
+pub struct SampledAdc<const N: usize> {
+    ring: Arc<IsrRingBuffer<u16, N>>,
+    dropped: Arc<AtomicU32>,
+    period: Duration,
+    // Only None while dropping.
+    timer: Option<HwTimer<'static>>,
+    running: Arc<AtomicBool>,
+    notifier: Arc<Notifier>,
+    sampler: Option<JoinHandle<()>>,
+}
+
+impl<const N: usize> SampledAdc<N> {
+    /// Starts reading `input` every `period`, using `timer` for the pace.
+    ///
+    /// Fails with TimerError::InvalidFrequency for a zero period.
+    pub fn new<const A: adc_atten_t, T>(
+        mut input: AnalogInput<'static, A, T>,
+        timer: TimerId,
+        period: Duration,
+    ) -> Result<Self, TimerError>
+    where
+        T: ADCPin + 'static,
+        AnalogInput<'static, A, T>: Send,
+    {
+        if period.is_zero() {
+            return Err(TimerError::InvalidFrequency);
+        }
+
+        // Everything that can fail before the thread exists is done first.
+        // The rest runs on a SampledAdc already owning the thread, so an
+        // error drops it, and drop() stops and joins the thread.
+        let config = TimerConfigBuilder::new()
+            .divider(divider_for_hz(1_000_000)?)
+            .build()?;
+        let mut hw_timer = HwTimer::new(timer, config)?;
+        hw_timer.set_alarm_after(period)?;
+
+        let ring = Arc::new(IsrRingBuffer::new());
+        let dropped = Arc::new(AtomicU32::new(0));
+        let running = Arc::new(AtomicBool::new(true));
+
+        let (notifier_tx, notifier_rx) = mpsc::sync_channel(1);
+        let sampler = {
+            let ring = ring.clone();
+            let dropped = dropped.clone();
+            let running = running.clone();
+            thread::spawn(move || {
+                // The alarm notifies the thread that creates the Notification.
+                let notification = Notification::new();
+                let _ = notifier_tx.send(notification.notifier());
+                loop {
+                    let Some(bits) = notification.wait(BLOCK) else {
+                        continue;
+                    };
+                    // Only drop()'s wake may end the thread: ended on an
+                    // alarm left pending, drop() could be about to notify a
+                    // task that's gone.
+                    if bits.get() & STOP.get() != 0 && !running.load(Ordering::SeqCst) {
+                        return;
+                    }
+                    if let Ok(sample) = input.read() {
+                        push_overwriting(&ring, &dropped, sample);
+                    }
+                }
+            })
+        };
+        // The thread always sends it before it can exit.
+        let notifier: Arc<Notifier> = notifier_rx.recv().unwrap();
+
+        let mut adc = Self {
+            ring,
+            dropped,
+            period,
+            timer: None,
+            running,
+            notifier: notifier.clone(),
+            sampler: Some(sampler),
+        };
+        let hw_timer = adc.timer.insert(hw_timer);
+        hw_timer.on_alarm(move || {
+            // SAFETY: the notification isn't forgotten, and drop() removes
+            // this ISR before the thread owning it exits.
+            unsafe { notifier.notify_and_yield(SAMPLE) };
+        })?;
+        hw_timer.enable_interrupt()?;
+        hw_timer.start()?;
+
+        Ok(adc)
+    }
+
+    /// Time between two samples.
+    pub fn sample_period(&self) -> Duration {
+        self.period
+    }
+
+    /// Takes the oldest buffered sample, None if there's none.
+    pub fn pop(&self) -> Option<u16> {
+        self.ring.pop()
+    }
+
+    /// Takes the buffered samples, oldest first.
+    pub fn drain(&self) -> impl Iterator<Item = u16> + '_ {
+        iter::from_fn(|| self.pop())
+    }
+
+    /// Number of samples buffered.
+    pub fn len(&self) -> usize {
+        self.ring.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Samples overwritten before they were drained, since the start.
+    pub fn dropped(&self) -> u32 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl<const N: usize> Drop for SampledAdc<N> {
+    fn drop(&mut self) {
+        // No alarm may notify the thread once it's gone. Pausing isn't
+        // enough, an alarm already pending would still run the ISR:
+        // dropping the timer unregisters it first.
+        drop(self.timer.take());
+        self.running.store(false, Ordering::SeqCst);
+        // SAFETY: the thread owning the notification only exits once it saw
+        // this notification.
+        unsafe { self.notifier.notify_and_yield(STOP) };
+        if let Some(sampler) = self.sampler.take() {
+            let _ = sampler.join();
+        }
+    }
+}
@@ -0,0 +1,217 @@
+// Driver for the Bosch BMP280 temperature & pressure sensor over I2C.
+//
+// The sensor measures continuously (normal mode) and keeps the raw 20-bit
+// ADC values of the last measurement in its data registers. Turning those
+// into °C and Pa takes the trimming coefficients factory stored in each
+// chip, read once at start up, and the integer compensation formulas of
+// the datasheet (section 8.2). The pressure formula uses the temperature
+// of the same measurement, so both come from one burst read.
+
+use core::fmt;
+use std::{borrow::BorrowMut, error::Error};
+
+use esp_idf_svc::{hal::i2c::I2cDriver, sys::EspError};
+
+use crate::i2c::I2cDevice;
+
+/// Address of the BMP280 with SDO tied to GND.
+pub const PRIMARY_ADDRESS: u8 = 0x76;
+
+/// Address of the BMP280 with SDO tied to VDDIO.
+pub const SECONDARY_ADDRESS: u8 = 0x77;
+
+/// What the chip ID register of a BMP280 reads.
+pub const CHIP_ID: u8 = 0x58;
+
+const REG_CALIBRATION: u8 = 0x88;
+const REG_CHIP_ID: u8 = 0xd0;
+const REG_CTRL_MEAS: u8 = 0xf4;
+const REG_CONFIG: u8 = 0xf5;
+// Pressure then temperature, msb / lsb / xlsb each.
+const REG_DATA: u8 = 0xf7;
+
+// Temperature and pressure oversampled x1 (bits 7-5 and 4-2 at 001),
+// normal mode (bits 1-0 at 11).
+const CTRL_MEAS_NORMAL: u8 = 0x27;
+// 62.5 ms standby between measurements (bits 7-5 at 001), IIR filter off.
+const CONFIG_62_5_MS: u8 = 0x20;
+
+// What a skipped measurement reads.
+const SKIPPED: i32 = 0x80000;
+
+/// Why talking to a BMP280 failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bmp280Error {
+    /// The chip ID register read this instead of CHIP_ID, there's another
+    /// device at that address (a BME280 reads 0x60).
+    WrongChip(u8),
+    /// The sensor hasn't finished its first measurement yet.
+    NotReady,
+    /// The I2C transaction failed, e.g. nothing ACKed the address.
+    I2c(EspError),
+}
+
+impl fmt::Display for Bmp280Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Bmp280Error::WrongChip(id) => {
+                write!(f, "Chip ID is {:#04x}, not a BMP280 ({:#04x})", id, CHIP_ID)
+            }
+            Bmp280Error::NotReady => write!(f, "No measurement yet"),
+            Bmp280Error::I2c(e) => write!(f, "I2C transaction failed: {}", e),
+        }
+    }
+}
+
+impl Error for Bmp280Error {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Bmp280Error::I2c(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<EspError> for Bmp280Error {
+    fn from(e: EspError) -> Self {
+        Bmp280Error::I2c(e)
+    }
+}
+
+// The dig_T* and dig_P* trimming coefficients.
+struct Calibration {
+    t1: u16,
+    t2: i16,
+    t3: i16,
+    p1: u16,
+    p: [i16; 8],
+}
+
+impl Calibration {
+    // From the 24 bytes at REG_CALIBRATION, little endian words.
+    fn parse(raw: &[u8; 24]) -> Self {
+        let word = |i: usize| u16::from_le_bytes([raw[2 * i], raw[2 * i + 1]]);
+        let mut p = [0i16; 8];
+        for (i, p) in p.iter_mut().enumerate() {
+            *p = word(4 + i) as i16;
+        }
+
+        Self {
+            t1: word(0),
+            t2: word(1) as i16,
+            t3: word(2) as i16,
+            p1: word(3),
+            p,
+        }
+    }
+
+    // The fine resolution temperature the pressure formula takes.
+    fn t_fine(&self, adc_t: i32) -> i32 {
+        let t1 = i32::from(self.t1);
+        let var1 = (((adc_t >> 3) - (t1 << 1)) * i32::from(self.t2)) >> 11;
+        let var2 = (((((adc_t >> 4) - t1) * ((adc_t >> 4) - t1)) >> 12) * i32::from(self.t3)) >> 14;
+        var1 + var2
+    }
+
+    // In Pa, 0 when the coefficients read as all 0 (a division by zero).
+    fn pressure(&self, adc_p: i32, t_fine: i32) -> f32 {
+        let [p2, p3, p4, p5, p6, p7, p8, p9] = self.p.map(i64::from);
+
+        let mut var1 = i64::from(t_fine) - 128_000;
+        let mut var2 = var1 * var1 * p6;
+        var2 += (var1 * p5) << 17;
+        var2 += p4 << 35;
+        var1 = ((var1 * var1 * p3) >> 8) + ((var1 * p2) << 12);
+        var1 = (((1i64 << 47) + var1) * i64::from(self.p1)) >> 33;
+        if var1 == 0 {
+            return 0.0;
+        }
+
+        let mut p = 1_048_576 - i64::from(adc_p);
+        p = (((p << 31) - var2) * 3125) / var1;
+        var1 = (p9 * (p >> 13) * (p >> 13)) >> 25;
+        var2 = (p8 * p) >> 19;
+        p = ((p + var1 + var2) >> 8) + (p7 << 4);
+
+        // Q24.8 fixed point.
+        p as f32 / 256.0
+    }
+}
+
+/// A BMP280 measuring temperature and pressure continuously.
+pub struct Bmp280<'d, D>
+where
+    D: BorrowMut<I2cDriver<'d>>,
+{
+    device: I2cDevice<'d, D>,
+    calibration: Calibration,
+}
+
+impl<'d, D> Bmp280<'d, D>
+where
+    D: BorrowMut<I2cDriver<'d>>,
+{
+    /// Checks the chip ID of `device`, reads its calibration and starts it
+    /// measuring, with a new measurement about every 70 ms.
+    ///
+    /// Fails with WrongChip when `device` isn't a BMP280.
+    pub fn new(mut device: I2cDevice<'d, D>) -> Result<Self, Bmp280Error> {
+        let id = device.read_reg(REG_CHIP_ID)?;
+        if id != CHIP_ID {
+            return Err(Bmp280Error::WrongChip(id));
+        }
+
+        let mut raw = [0u8; 24];
+        device.read_regs(REG_CALIBRATION, &mut raw)?;
+
+        // The standby time can only be changed while sleeping, which the
+        // sensor does after power on.
+        device.write_reg(REG_CONFIG, CONFIG_62_5_MS)?;
+        device.write_reg(REG_CTRL_MEAS, CTRL_MEAS_NORMAL)?;
+
+        Ok(Self {
+            device,
+            calibration: Calibration::parse(&raw),
+        })
+    }
+
+    /// Temperature of the last measurement, in °C.
+    pub fn read_temperature(&mut self) -> Result<f32, Bmp280Error> {
+        Ok(self.read()?.0)
+    }
+
+    /// Pressure of the last measurement, in Pa (hPa * 100).
+    pub fn read_pressure(&mut self) -> Result<f32, Bmp280Error> {
+        Ok(self.read()?.1)
+    }
+
+    /// Reads both from the same measurement, returning (temperature in °C,
+    /// pressure in Pa).
+    ///
+    /// Fails with NotReady until the first measurement is done, right after
+    /// new().
+    pub fn read(&mut self) -> Result<(f32, f32), Bmp280Error> {
+        let mut data = [0u8; 6];
+        self.device.read_regs(REG_DATA, &mut data)?;
+
+        // 20 bits, msb / lsb / upper nibble of xlsb.
+        let adc =
+            |d: &[u8]| (i32::from(d[0]) << 12) | (i32::from(d[1]) << 4) | (i32::from(d[2]) >> 4);
+        let (adc_p, adc_t) = (adc(&data[..3]), adc(&data[3..]));
+        if adc_p == SKIPPED || adc_t == SKIPPED {
+            return Err(Bmp280Error::NotReady);
+        }
+
+        let t_fine = self.calibration.t_fine(adc_t);
+        // In 0.01 °C.
+        let temperature = ((t_fine * 5 + 128) >> 8) as f32 / 100.0;
+        let pressure = self.calibration.pressure(adc_p, t_fine);
+
+        Ok((temperature, pressure))
+    }
+
+    /// Gives the I2cDevice back, the sensor keeps measuring.
+    pub fn release(self) -> I2cDevice<'d, D> {
+        self.device
+    }
+}
@@ -0,0 +1,34 @@
+// Bring-up of the chip's singletons, with errors saying what went wrong
+// instead of an unwrap panic.
+
+use core::fmt;
+use std::error::Error;
+
+use esp_idf_svc::hal::peripherals::Peripherals;
+
+/// Errors setting up the board.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InitError {
+    /// Peripherals were already taken, by us or by other code in the app.
+    PeripheralsAlreadyTaken,
+}
+
+impl fmt::Display for InitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InitError::PeripheralsAlreadyTaken => write!(
+                f,
+                "Peripherals were already taken, they can only be taken once per boot. \
+                 Pass the pins and drivers you need down from where they were taken instead"
+            ),
+        }
+    }
+}
+
+impl Error for InitError {}
+
+/// Takes the chip's Peripherals, which only succeeds once per boot.
+pub fn take_peripherals() -> Result<Peripherals, InitError> {
+    // The only way take() fails is with ESP_ERR_INVALID_STATE once taken.
+    Peripherals::take().map_err(|_| InitError::PeripheralsAlreadyTaken)
+}
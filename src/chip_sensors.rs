@@ -0,0 +1,106 @@
+// The sensors inside the chip: the hall effect sensor of the original
+// ESP32, and the temperature sensor of the newer chips.
+//
+// Neither is on every chip, and ESP IDF 5 dropped the hall sensor driver
+// altogether, so each reading fails with ESP_ERR_NOT_SUPPORTED where the
+// sensor or its driver is missing instead of returning a made up value.
+//
+// The temperature sensor measures the die, which runs warmer than the air
+// around it, more so with the radio on. It tracks changes well but reads
+// a few degrees high.
+
+// No chip has both sensors, so one of them always fails with it.
+use esp_idf_svc::sys::{EspError, ESP_ERR_NOT_SUPPORTED};
+
+#[cfg(all(esp32, esp_idf_version_major = "4"))]
+use esp_idf_svc::sys::{
+    adc1_config_width, adc_bits_width_t_ADC_WIDTH_BIT_12, esp, hall_sensor_read,
+};
+
+#[cfg(all(
+    any(esp32s2, esp32s3, esp32c2, esp32c3, esp32c6, esp32h2),
+    not(esp_idf_version_major = "4")
+))]
+use esp_idf_svc::sys::{
+    esp, temperature_sensor_config_t, temperature_sensor_disable, temperature_sensor_enable,
+    temperature_sensor_get_celsius, temperature_sensor_handle_t, temperature_sensor_install,
+    temperature_sensor_uninstall,
+};
+
+/// Lowest temperature read_internal_temperature() is accurate at, in °C.
+pub const TEMPERATURE_MIN: i32 = -10;
+
+/// Highest temperature read_internal_temperature() is accurate at, in °C.
+pub const TEMPERATURE_MAX: i32 = 80;
+
+/// Reads the hall effect sensor, positive or negative depending on the
+/// pole of the magnet near the chip, 0 without one give or take noise.
+///
+/// Only the original ESP32 has one, and only ESP IDF 4 can read it. It
+/// takes over ADC1 channels 0 and 3 (GPIO36 and GPIO39) meanwhile, so
+/// nothing may be connected to them. Fails with ESP_ERR_NOT_SUPPORTED
+/// anywhere else.
+pub fn read_hall_sensor() -> Result<i32, EspError> {
+    #[cfg(all(esp32, esp_idf_version_major = "4"))]
+    {
+        // SAFETY: adc1_config_width() is an ESP32 ABI call.
+        esp!(unsafe { adc1_config_width(adc_bits_width_t_ADC_WIDTH_BIT_12) })?;
+        // SAFETY: hall_sensor_read() is an ESP32 ABI call.
+        Ok(unsafe { hall_sensor_read() })
+    }
+
+    #[cfg(not(all(esp32, esp_idf_version_major = "4")))]
+    Err(EspError::from_infallible::<ESP_ERR_NOT_SUPPORTED>())
+}
+
+/// Reads the temperature of the chip's die, in °C.
+///
+/// Accurate from TEMPERATURE_MIN to TEMPERATURE_MAX. The sensor is set
+/// up for each reading and released after, which takes a few ms. Fails
+/// with ESP_ERR_NOT_SUPPORTED on the original ESP32, which has none.
+pub fn read_internal_temperature() -> Result<f32, EspError> {
+    #[cfg(all(
+        any(esp32s2, esp32s3, esp32c2, esp32c3, esp32c6, esp32h2),
+        not(esp_idf_version_major = "4")
+    ))]
+    {
+        let config = temperature_sensor_config_t {
+            range_min: TEMPERATURE_MIN,
+            range_max: TEMPERATURE_MAX,
+            // The default clock source.
+            ..Default::default()
+        };
+        let mut handle: temperature_sensor_handle_t = core::ptr::null_mut();
+        // SAFETY: temperature_sensor_install() is an ESP32 ABI call.
+        esp!(unsafe { temperature_sensor_install(&config, &mut handle) })?;
+
+        let celsius = read_celsius(handle);
+        // SAFETY: temperature_sensor_uninstall() is an ESP32 ABI call.
+        esp!(unsafe { temperature_sensor_uninstall(handle) })?;
+        celsius
+    }
+
+    #[cfg(not(all(
+        any(esp32s2, esp32s3, esp32c2, esp32c3, esp32c6, esp32h2),
+        not(esp_idf_version_major = "4")
+    )))]
+    Err(EspError::from_infallible::<ESP_ERR_NOT_SUPPORTED>())
+}
+
+// Takes one reading from the installed sensor `handle`.
+#[cfg(all(
+    any(esp32s2, esp32s3, esp32c2, esp32c3, esp32c6, esp32h2),
+    not(esp_idf_version_major = "4")
+))]
+fn read_celsius(handle: temperature_sensor_handle_t) -> Result<f32, EspError> {
+    // SAFETY: temperature_sensor_enable() is an ESP32 ABI call.
+    esp!(unsafe { temperature_sensor_enable(handle) })?;
+
+    let mut celsius = 0.0;
+    // SAFETY: temperature_sensor_get_celsius() is an ESP32 ABI call.
+    let read = esp!(unsafe { temperature_sensor_get_celsius(handle, &mut celsius) });
+    // SAFETY: temperature_sensor_disable() is an ESP32 ABI call.
+    esp!(unsafe { temperature_sensor_disable(handle) })?;
+
+    read.map(|()| celsius)
+}
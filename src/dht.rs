@@ -0,0 +1,202 @@
+// Driver for the DHT22 (AM2302) and DHT11 temperature & humidity sensors.
+//
+// The sensor talks over a single open drain wire. We pull it low to ask
+// for a reading, then the sensor answers with an 80µs low / 80µs high
+// preamble followed by 40 bits, each a ~50µs low followed by a high that
+// lasts ~27µs for a 0 and ~70µs for a 1. The high times are measured with
+// a free running hardware timer.
+
+use core::fmt;
+use std::{error::Error, thread, time::Duration};
+
+use esp_idf_svc::{
+    hal::{
+        gpio::{AnyIOPin, IOPin, InputOutput, Level, PinDriver, Pull},
+        interrupt,
+        peripheral::Peripheral,
+    },
+    sys::EspError,
+};
+
+use crate::timer::{HwTimer, TimerError, TimerId};
+
+// How long we hold the line low to start a reading.
+const DHT22_START: Duration = Duration::from_millis(2);
+const DHT11_START: Duration = Duration::from_millis(20);
+
+// Longest any level of the answer lasts, with some margin.
+const LEVEL_TIMEOUT_US: u64 = 100;
+
+// Highs longer than this are a 1 bit.
+const ONE_THRESHOLD_US: u64 = 48;
+
+/// Why a reading failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DhtError {
+    /// The sensor didn't answer, or stopped halfway through the frame.
+    Timeout,
+    /// The checksum byte doesn't match the data, the frame got corrupted.
+    Checksum,
+    /// Driving or reading the data pin failed.
+    Gpio(EspError),
+    /// The timer used to measure the bits couldn't be set up or read.
+    Timer(TimerError),
+}
+
+impl fmt::Display for DhtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DhtError::Timeout => write!(f, "Sensor didn't answer in time"),
+            DhtError::Checksum => write!(f, "Checksum mismatch, the frame got corrupted"),
+            DhtError::Gpio(e) => write!(f, "Data pin call failed: {}", e),
+            DhtError::Timer(e) => write!(f, "Timer failed: {}", e),
+        }
+    }
+}
+
+impl Error for DhtError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DhtError::Gpio(e) => Some(e),
+            DhtError::Timer(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<EspError> for DhtError {
+    fn from(e: EspError) -> Self {
+        DhtError::Gpio(e)
+    }
+}
+
+impl From<TimerError> for DhtError {
+    fn from(e: TimerError) -> Self {
+        DhtError::Timer(e)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Model {
+    Dht11,
+    Dht22,
+}
+
+/// A DHT22 (or DHT11) on a data pin, with a hardware timer for the timing.
+///
+/// Leave at least 2 seconds between two readings (1 for the DHT11), the
+/// sensor repeats its last measurement when asked sooner.
+pub struct Dht22<'d> {
+    pin: PinDriver<'d, AnyIOPin, InputOutput>,
+    timer: HwTimer<'d>,
+    model: Model,
+}
+
+impl<'d> Dht22<'d> {
+    pub fn new(
+        pin: impl Peripheral<P = impl IOPin> + 'd,
+        timer: TimerId,
+    ) -> Result<Self, DhtError> {
+        Self::with_model(pin, timer, Model::Dht22)
+    }
+
+    /// For the DHT11, which needs a longer start signal and encodes its
+    /// readings differently.
+    pub fn new_dht11(
+        pin: impl Peripheral<P = impl IOPin> + 'd,
+        timer: TimerId,
+    ) -> Result<Self, DhtError> {
+        Self::with_model(pin, timer, Model::Dht11)
+    }
+
+    fn with_model(
+        pin: impl Peripheral<P = impl IOPin> + 'd,
+        timer: TimerId,
+        model: Model,
+    ) -> Result<Self, DhtError> {
+        // Open drain so the same driver can pull the line low and read it.
+        let mut pin = PinDriver::input_output_od(pin.into_ref().map_into::<AnyIOPin>())?;
+        pin.set_pull(Pull::Up)?;
+        pin.set_high()?;
+
+        Ok(Self {
+            pin,
+            timer: HwTimer::free_running(timer)?,
+            model,
+        })
+    }
+
+    /// Takes a reading, returning (temperature in °C, relative humidity in %).
+    pub fn read(&mut self) -> Result<(f32, f32), DhtError> {
+        self.pin.set_low()?;
+        thread::sleep(match self.model {
+            Model::Dht11 => DHT11_START,
+            Model::Dht22 => DHT22_START,
+        });
+
+        // The bits are only microseconds apart, don't let anything preempt us.
+        let frame = interrupt::free(|| self.read_frame())?;
+
+        let sum = frame[..4].iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+        if sum != frame[4] {
+            return Err(DhtError::Checksum);
+        }
+
+        Ok(self.decode(&frame))
+    }
+
+    fn read_frame(&mut self) -> Result<[u8; 5], DhtError> {
+        // Release the line, the sensor answers by pulling it low.
+        self.pin.set_high()?;
+        self.wait_while(Level::High)?;
+
+        // Preamble.
+        self.wait_while(Level::Low)?;
+        self.wait_while(Level::High)?;
+
+        let mut frame = [0u8; 5];
+        for bit in 0..40 {
+            self.wait_while(Level::Low)?;
+            if self.wait_while(Level::High)? > ONE_THRESHOLD_US {
+                frame[bit / 8] |= 0x80 >> (bit % 8);
+            }
+        }
+
+        Ok(frame)
+    }
+
+    // Busy waits for the line to leave `level`, returning how many
+    // microseconds it stayed there.
+    fn wait_while(&self, level: Level) -> Result<u64, DhtError> {
+        let start = self.timer.counter()?;
+        loop {
+            let elapsed = self.timer.counter()? - start;
+            if self.pin.get_level() != level {
+                return Ok(elapsed);
+            }
+            if elapsed > LEVEL_TIMEOUT_US {
+                return Err(DhtError::Timeout);
+            }
+        }
+    }
+
+    fn decode(&self, frame: &[u8; 5]) -> (f32, f32) {
+        match self.model {
+            // Integral and decimal bytes, the sign in the top bit of the
+            // temperature decimal.
+            Model::Dht11 => {
+                let humidity = f32::from(frame[0]) + f32::from(frame[1]) / 10.0;
+                let temperature = f32::from(frame[2]) + f32::from(frame[3] & 0x7f) / 10.0;
+                let sign = if frame[3] & 0x80 != 0 { -1.0 } else { 1.0 };
+                (sign * temperature, humidity)
+            }
+            // Tenths, big endian, the temperature in sign & magnitude.
+            Model::Dht22 => {
+                let humidity = f32::from(u16::from_be_bytes([frame[0], frame[1]])) / 10.0;
+                let temperature = f32::from(u16::from_be_bytes([frame[2] & 0x7f, frame[3]])) / 10.0;
+                let sign = if frame[2] & 0x80 != 0 { -1.0 } else { 1.0 };
+                (sign * temperature, humidity)
+            }
+        }
+    }
+}
@@ -0,0 +1,157 @@
+// Sending init sequences and pixel data to small SPI displays, such as the
+// SSD1306 and ST7789.
+//
+// These controllers tell commands and data apart by the level of a DC pin:
+// low while a command byte is clocked in, high for its parameters and for
+// pixel data.
+
+use std::{borrow::Borrow, thread, time::Duration};
+
+use esp_idf_svc::{
+    hal::{
+        gpio::{AnyOutputPin, Output, OutputPin, PinDriver},
+        peripheral::Peripheral,
+        spi::{SpiDeviceDriver, SpiDriver},
+    },
+    sys::EspError,
+};
+
+// How long RST is held low, and how long the controller takes to come out
+// of reset.
+const RESET_PULSE: Duration = Duration::from_millis(10);
+const RESET_RECOVERY: Duration = Duration::from_millis(120);
+
+/// A step of a display's init sequence.
+#[derive(Debug, Clone, Copy)]
+pub enum InitStep {
+    /// A command byte, sent with DC low.
+    Command(u8),
+    /// Parameters of the previous command, sent with DC high.
+    Data(&'static [u8]),
+    /// Gives the controller time to carry out the previous command.
+    Delay(Duration),
+}
+
+/// Powers up a 128x64 SSD1306 OLED, with horizontal addressing.
+///
+/// The SSD1306 takes the parameters of its commands with DC low too.
+pub const SSD1306_INIT: &[InitStep] = &[
+    InitStep::Command(0xae), // Display off
+    InitStep::Command(0xd5), // Clock divider
+    InitStep::Command(0x80),
+    InitStep::Command(0xa8), // Multiplex ratio, 64 rows
+    InitStep::Command(0x3f),
+    InitStep::Command(0xd3), // No display offset
+    InitStep::Command(0x00),
+    InitStep::Command(0x40), // Start line 0
+    InitStep::Command(0x8d), // Charge pump on
+    InitStep::Command(0x14),
+    InitStep::Command(0x20), // Horizontal addressing
+    InitStep::Command(0x00),
+    InitStep::Command(0xa1), // Segment remap
+    InitStep::Command(0xc8), // COM scan direction, top to bottom
+    InitStep::Command(0xda), // COM pins
+    InitStep::Command(0x12),
+    InitStep::Command(0x81), // Contrast
+    InitStep::Command(0xcf),
+    InitStep::Command(0xd9), // Pre-charge period
+    InitStep::Command(0xf1),
+    InitStep::Command(0xdb), // VCOMH deselect level
+    InitStep::Command(0x40),
+    InitStep::Command(0xa4), // Show the RAM contents
+    InitStep::Command(0xa6), // Not inverted
+    InitStep::Command(0xaf), // Display on
+];
+
+/// Powers up a 240x240 ST7789 TFT, with 16-bit RGB565 pixels.
+pub const ST7789_INIT: &[InitStep] = &[
+    InitStep::Command(0x01), // Software reset
+    InitStep::Delay(Duration::from_millis(150)),
+    InitStep::Command(0x11), // Out of sleep
+    InitStep::Delay(Duration::from_millis(120)),
+    InitStep::Command(0x3a), // 16 bits per pixel
+    InitStep::Data(&[0x55]),
+    InitStep::Command(0x36), // Top to bottom, left to right, RGB
+    InitStep::Data(&[0x00]),
+    InitStep::Command(0x21), // Inverted, most panels are wired for it
+    InitStep::Command(0x13), // Normal display mode
+    InitStep::Delay(Duration::from_millis(10)),
+    InitStep::Command(0x29), // Display on
+    InitStep::Delay(Duration::from_millis(10)),
+];
+
+/// A display controller on an SPI bus, with its DC and RST pins.
+///
+/// CS is driven by the SpiDeviceDriver, pass it to SpiDeviceDriver::new().
+pub struct SpiDisplay<'d, T>
+where
+    T: Borrow<SpiDriver<'d>> + 'd,
+{
+    device: SpiDeviceDriver<'d, T>,
+    dc: PinDriver<'d, AnyOutputPin, Output>,
+    rst: PinDriver<'d, AnyOutputPin, Output>,
+}
+
+impl<'d, T> SpiDisplay<'d, T>
+where
+    T: Borrow<SpiDriver<'d>> + 'd,
+{
+    pub fn new(
+        device: SpiDeviceDriver<'d, T>,
+        dc: impl Peripheral<P = impl OutputPin> + 'd,
+        rst: impl Peripheral<P = impl OutputPin> + 'd,
+    ) -> Result<Self, EspError> {
+        let mut rst = PinDriver::output(rst.into_ref().map_into::<AnyOutputPin>())?;
+        // RST is active low, keep the controller running until init().
+        rst.set_high()?;
+
+        Ok(Self {
+            device,
+            dc: PinDriver::output(dc.into_ref().map_into::<AnyOutputPin>())?,
+            rst,
+        })
+    }
+
+    /// Resets the controller through RST, then runs `sequence`.
+    pub fn init(&mut self, sequence: &[InitStep]) -> Result<(), EspError> {
+        self.reset()?;
+
+        for step in sequence {
+            match *step {
+                InitStep::Command(cmd) => self.command(cmd)?,
+                InitStep::Data(data) => self.data(data)?,
+                InitStep::Delay(delay) => thread::sleep(delay),
+            }
+        }
+        Ok(())
+    }
+
+    /// Pulses RST, which puts the controller back in its power on state.
+    pub fn reset(&mut self) -> Result<(), EspError> {
+        self.rst.set_low()?;
+        thread::sleep(RESET_PULSE);
+        self.rst.set_high()?;
+        thread::sleep(RESET_RECOVERY);
+        Ok(())
+    }
+
+    /// Sends a command byte.
+    pub fn command(&mut self, cmd: u8) -> Result<(), EspError> {
+        self.dc.set_low()?;
+        self.device.write(&[cmd])
+    }
+
+    /// Sends command parameters.
+    pub fn data(&mut self, data: &[u8]) -> Result<(), EspError> {
+        self.dc.set_high()?;
+        self.device.write(data)
+    }
+
+    /// Sends pixel data, in the format the controller was configured for.
+    ///
+    /// Most controllers first need a memory write command, e.g. 0x2c on the
+    /// ST7789.
+    pub fn write_pixels(&mut self, pixels: &[u8]) -> Result<(), EspError> {
+        self.data(pixels)
+    }
+}
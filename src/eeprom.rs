@@ -0,0 +1,197 @@
+// Driver for the 24Cxx family of I2C EEPROMs (AT24C02, AT24C32, 24LC256...).
+//
+// Reads can cover any range, the chip's address pointer just keeps going.
+// Writes can't: the chip buffers at most one page, and a write running
+// past the end of a page wraps back to its start, overwriting what's
+// there. So writes are split at the page boundaries, and after each page
+// the chip spends a few ms programming it, NACKing its address until done.
+// The driver polls the address for that ACK before writing the next page.
+
+use std::{
+    borrow::BorrowMut,
+    time::{Duration, Instant},
+};
+
+use esp_idf_svc::{
+    hal::{delay::Ets, i2c::I2cDriver},
+    sys::{EspError, ESP_ERR_INVALID_ARG, ESP_ERR_TIMEOUT},
+};
+
+use crate::i2c::I2cDevice;
+
+/// Address of a 24Cxx with A0, A1 and A2 tied to GND.
+pub const BASE_ADDRESS: u8 = 0x50;
+
+// Datasheets give 5 ms at most for the write cycle, this leaves a margin.
+const WRITE_CYCLE_TIMEOUT: Duration = Duration::from_millis(20);
+
+// Between two ACK polls.
+const POLL_INTERVAL_US: u32 = 100;
+
+/// How a 24Cxx takes the memory address, which depends on its size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressWidth {
+    /// One byte, for chips up to 2 kbit (256 bytes) like the 24C02.
+    ///
+    /// The 24C04 to 24C16 take the extra address bits in their I2C
+    /// address, each 256 bytes block of those is an Eeprom24 of its own
+    /// at BASE_ADDRESS + block.
+    OneByte,
+    /// Two bytes, big endian, for chips from 32 kbit like the 24C32.
+    TwoBytes,
+}
+
+impl AddressWidth {
+    // Bytes sent before the data.
+    const fn len(self) -> usize {
+        match self {
+            AddressWidth::OneByte => 1,
+            AddressWidth::TwoBytes => 2,
+        }
+    }
+
+    // The most bytes the address can reach.
+    const fn max_capacity(self) -> usize {
+        match self {
+            AddressWidth::OneByte => 0x100,
+            AddressWidth::TwoBytes => 0x1_0000,
+        }
+    }
+}
+
+/// A 24Cxx EEPROM.
+pub struct Eeprom24<'d, D>
+where
+    D: BorrowMut<I2cDriver<'d>>,
+{
+    device: I2cDevice<'d, D>,
+    width: AddressWidth,
+    capacity: usize,
+    page_size: usize,
+    // Memory address followed by a page of data, reused across writes.
+    buf: Vec<u8>,
+}
+
+impl<'d, D> Eeprom24<'d, D>
+where
+    D: BorrowMut<I2cDriver<'d>>,
+{
+    /// A chip of `capacity` bytes written `page_size` bytes at a time, both
+    /// from its datasheet, e.g. 4096 and 32 for a 24C32.
+    ///
+    /// Fails with ESP_ERR_INVALID_ARG if `page_size` isn't a power of two
+    /// dividing `capacity`, or `capacity` is more than `width` can address.
+    pub fn new(
+        device: I2cDevice<'d, D>,
+        width: AddressWidth,
+        capacity: usize,
+        page_size: usize,
+    ) -> Result<Self, EspError> {
+        if !page_size.is_power_of_two()
+            || capacity % page_size != 0
+            || capacity > width.max_capacity()
+        {
+            return Err(EspError::from_infallible::<ESP_ERR_INVALID_ARG>());
+        }
+
+        Ok(Self {
+            device,
+            width,
+            capacity,
+            page_size,
+            buf: Vec::with_capacity(width.len() + page_size),
+        })
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    /// Reads `buf.len()` bytes starting at `addr`, in one transaction.
+    ///
+    /// Fails with ESP_ERR_INVALID_ARG if that goes past the end of the chip.
+    pub fn read(&mut self, addr: usize, buf: &mut [u8]) -> Result<(), EspError> {
+        self.check_range(addr, buf.len())?;
+        if buf.is_empty() {
+            return Ok(());
+        }
+
+        let (bytes, len) = self.address_bytes(addr);
+        self.device.write_read(&bytes[..len], buf)
+    }
+
+    /// Writes `data` starting at `addr`, a page at a time, and returns once
+    /// the chip has programmed all of it.
+    ///
+    /// Fails with ESP_ERR_INVALID_ARG if that goes past the end of the chip,
+    /// or ESP_ERR_TIMEOUT if the chip stays busy after a page. The pages
+    /// before a failure stay written.
+    pub fn write(&mut self, addr: usize, data: &[u8]) -> Result<(), EspError> {
+        self.check_range(addr, data.len())?;
+
+        let mut addr = addr;
+        let mut data = data;
+        while !data.is_empty() {
+            // Up to the end of the page addr is in.
+            let room = self.page_size - addr % self.page_size;
+            let (chunk, rest) = data.split_at(room.min(data.len()));
+
+            self.write_page(addr, chunk)?;
+            self.wait_write_cycle()?;
+
+            addr += chunk.len();
+            data = rest;
+        }
+
+        Ok(())
+    }
+
+    /// Gives the device back.
+    pub fn release(self) -> I2cDevice<'d, D> {
+        self.device
+    }
+
+    // Writes `chunk`, which doesn't cross a page boundary, in one transaction.
+    fn write_page(&mut self, addr: usize, chunk: &[u8]) -> Result<(), EspError> {
+        let (bytes, len) = self.address_bytes(addr);
+        self.buf.clear();
+        self.buf.extend_from_slice(&bytes[..len]);
+        self.buf.extend_from_slice(chunk);
+
+        self.device.write(&self.buf)
+    }
+
+    // Polls the chip until it ACKs its address again, i.e. it's done
+    // programming the last page.
+    fn wait_write_cycle(&mut self) -> Result<(), EspError> {
+        let start = Instant::now();
+        loop {
+            if self.device.write(&[]).is_ok() {
+                return Ok(());
+            }
+            if start.elapsed() > WRITE_CYCLE_TIMEOUT {
+                return Err(EspError::from_infallible::<ESP_ERR_TIMEOUT>());
+            }
+            Ets::delay_us(POLL_INTERVAL_US);
+        }
+    }
+
+    fn check_range(&self, addr: usize, len: usize) -> Result<(), EspError> {
+        match addr.checked_add(len) {
+            Some(end) if end <= self.capacity => Ok(()),
+            _ => Err(EspError::from_infallible::<ESP_ERR_INVALID_ARG>()),
+        }
+    }
+
+    // `addr` as the chip takes it, and how many of the bytes it uses.
+    fn address_bytes(&self, addr: usize) -> ([u8; 2], usize) {
+        match self.width {
+            AddressWidth::OneByte => ([addr as u8, 0], 1),
+            AddressWidth::TwoBytes => ((addr as u16).to_be_bytes(), 2),
+        }
+    }
+}
@@ -0,0 +1,1365 @@
+// Quadrature decoding for mechanical rotary encoders.
+//
+// The A and B pins go through the 2-bit gray code 00 -> 01 -> 11 -> 10 in
+// one direction and the reverse in the other. The encoder is polled, e.g.
+// from a timer alarm, fast enough not to miss a state, and each transition
+// to a neighbouring state counts as one step.
+//
+// Mechanical contacts bounce when they open or close, so each pin can be
+// debounced: a new level only counts once it was read on several polls in
+// a row. Around a detent the decoder can still see a lone step the wrong
+// way, the direction filter only believes a change of direction once it
+// was seen for several steps.
+//
+// The decoding is done by a QuadratureDecoder reading any pair of
+// embedded-hal InputPins, so it runs on other HALs too. RotaryEncoder is
+// the one reading PinDrivers, a decoder over MockLevelSources can be
+// polled through scripted turns off the chip. Without ESP IDF, e.g. in
+// host tests, only the chip specific parts are left out: RotaryEncoder,
+// EncoderBank, persistence and the pull resistors. Blocking events then
+// poll every 10 ms, there's no task notification to sleep on.
+//
+// The usual encoder module has its contacts to GND, so RotaryEncoder turns
+// the internal pull-ups on by default. Modules with their own pull-up
+// resistors (often 10k, on the board) work with Pull::Floating.
+//
+// A detent is where the knob rests between clicks, in full-step mode the
+// 00 state. Steps in between are counted as they come, but a detent
+// callback only hears about the position once the knob lands on one.
+//
+// Many encoders go through the whole gray code, 4 transitions, from one
+// detent to the next. With steps_per_detent at 4 the transitions are
+// accumulated and the position moves once per detent, a turn partway and
+// back adds up to nothing. Landing on the detent state rounds what's left
+// to the nearest detent, so a missed state doesn't leave the count off by
+// a fraction of a detent from then on.
+//
+// A saturating position can be bounded to a range, e.g. 0 to 100 for a
+// volume. A limit callback hears about the steps the bounds clamp, once per
+// push against an end stop.
+//
+// For wiring problems, the raw pin levels of the last poll can be read
+// back, and a debug trace records every gray code change with the step it
+// counted. Polls usually run in an ISR, which can't log, so the trace is
+// queued and logged by log_trace() from a thread.
+//
+// Each step is also queued as an EncoderEvent, for main loops iterating
+// over events() rather than registering callbacks.
+//
+// What the hardware does as the knob turns is up to an on_direction()
+// callback, hearing about every poll with the step it took if any. The
+// decoder itself drives nothing, clockwise_indicator() is the one lighting
+// an LED while it turns clockwise.
+//
+// With acceleration, each step counts several times when it comes soon
+// after the previous one, so a quick spin covers a large range while slow
+// turns still move one step at a time.
+
+use core::{
+    cell::UnsafeCell,
+    sync::atomic::{AtomicBool, AtomicI32, AtomicI8, AtomicU32, AtomicU8, Ordering},
+    time::Duration,
+};
+#[cfg(target_os = "espidf")]
+use core::{num::NonZeroU32, ptr, sync::atomic::AtomicPtr};
+
+#[cfg(target_os = "espidf")]
+use std::sync::Arc;
+use std::thread;
+#[cfg(not(target_os = "espidf"))]
+use std::{sync::OnceLock, time::Instant};
+
+use embedded_hal::digital;
+#[cfg(target_os = "espidf")]
+use esp_idf_svc::{
+    hal::{
+        delay::{TickType, BLOCK},
+        gpio::{AnyInputPin, Input, InputPin, Level, PinDriver},
+        peripheral::Peripheral,
+        task::notification::{Notification, Notifier},
+    },
+    sys::{EspError, ESP_ERR_INVALID_ARG},
+    systime::EspSystemTime,
+};
+
+#[cfg(target_os = "espidf")]
+use crate::{
+    gpio::{self, Pull},
+    power::{wake_cause, WakeCause},
+    storage::KvStore,
+};
+use crate::{levels::Debouncer, ringbuf::IsrRingBuffer};
+
+/// How many steps a decoder keeps queued for events(). Steps past that
+/// while nothing reads them are still counted, but get no event.
+pub const EVENT_QUEUE_LEN: usize = 16;
+
+/// How many transitions the debug trace keeps until log_trace() logs them,
+/// later ones are dropped.
+pub const TRACE_LEN: usize = 32;
+
+// How long a blocking EncoderEvents sleeps between its own polls, until it
+// sees something else poll the decoder. One FreeRTOS tick at the default
+// 100 Hz, shorter sleeps busy wait.
+const EVENT_WAIT: Duration = Duration::from_millis(10);
+
+// Where save_position() keeps the position in the KvStore.
+#[cfg(target_os = "espidf")]
+const POSITION_KEY: &str = "enc_position";
+
+// Gray code of the detents, both pins low.
+const DETENT: i8 = 0;
+
+// Called with the position on landing on a detent.
+type DetentCallback = Box<dyn FnMut(i32) + Send>;
+
+// Called with the limit a step got clamped at.
+type LimitCallback = Box<dyn FnMut(Limit) + Send>;
+
+// Called with the step of each poll, if any.
+type DirectionCallback = Box<dyn FnMut(Option<Direction>) + Send>;
+
+// Maps the time since the previous step to how many steps to count.
+type AccelFn = Box<dyn Fn(Duration) -> i32 + Send + Sync>;
+
+/// Which way the knob turned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Clockwise,
+    CounterClockwise,
+}
+
+impl Direction {
+    /// The other way.
+    pub fn reversed(self) -> Self {
+        match self {
+            Direction::Clockwise => Direction::CounterClockwise,
+            Direction::CounterClockwise => Direction::Clockwise,
+        }
+    }
+}
+
+/// A change of the gray code seen by poll(), recorded by the debug trace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transition {
+    /// Gray code before and after, see gray_code().
+    pub from: i8,
+    pub to: i8,
+    /// The step decoded from the two, None when a state was skipped.
+    pub direction: Option<Direction>,
+    /// What it added to the position, after the direction filter and
+    /// acceleration. 0 for a step held back, a skipped state or one short
+    /// of a detent.
+    pub delta: i32,
+}
+
+/// Whether an EncoderEvents waits for the knob to move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventMode {
+    /// Ends once there's no step left, e.g. to handle them all on each pass
+    /// of a main loop.
+    NonBlocking,
+    /// Waits for the next step, never ends.
+    Blocking,
+}
+
+/// One end of the range of a bounded encoder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Min,
+    Max,
+}
+
+/// The acceleration curve of QuadratureDecoder::with_acceleration():
+/// steps less than 10 ms apart count 8 times, less than 25 ms 4 times,
+/// less than 50 ms twice, and slower ones once.
+pub fn stepped_acceleration(since_last: Duration) -> i32 {
+    match since_last.as_millis() {
+        0..=9 => 8,
+        10..=24 => 4,
+        25..=49 => 2,
+        _ => 1,
+    }
+}
+
+/// An on_direction() callback driving `pin` high on polls that stepped
+/// clockwise and low on every other poll, e.g. for an LED lighting up
+/// while the knob turns clockwise. A pin failing to be set is ignored.
+pub fn clockwise_indicator<P>(mut pin: P) -> impl FnMut(Option<Direction>) + Send
+where
+    P: digital::OutputPin + Send,
+{
+    move |direction| {
+        let _ = pin.set_state((direction == Some(Direction::Clockwise)).into());
+    }
+}
+
+// The levels of A and B packed for QuadratureDecoder::raw.
+fn raw_bits(a_high: bool, b_high: bool) -> u8 {
+    u8::from(a_high) | (u8::from(b_high) << 1)
+}
+
+// Time since boot, for the acceleration.
+#[cfg(target_os = "espidf")]
+fn now() -> Duration {
+    EspSystemTime.now()
+}
+
+// Off the chip, time since the first call.
+#[cfg(not(target_os = "espidf"))]
+fn now() -> Duration {
+    static START: OnceLock<Instant> = OnceLock::new();
+    START.get_or_init(Instant::now).elapsed()
+}
+
+/// Converts the levels of the A and B pins into their position (0-3)
+/// along the gray code sequence.
+#[cfg(target_os = "espidf")]
+pub const fn gray_code(a: Level, b: Level) -> i8 {
+    greycode_from_bits(matches!(a, Level::High), matches!(b, Level::High))
+}
+
+/// Like gray_code(), from whether A and B are high. Doesn't touch the HAL,
+/// so it also runs on the host.
+pub const fn greycode_from_bits(a: bool, b: bool) -> i8 {
+    match (a, b) {
+        (false, false) => 0,
+        (false, true) => 1,
+        (true, true) => 2,
+        (true, false) => 3,
+    }
+}
+
+/// The step between two gray codes, None if the state didn't change or
+/// jumped two states (a missed poll, the direction is unknown).
+pub fn step(previous: i8, current: i8) -> Option<Direction> {
+    match (current - previous).rem_euclid(4) {
+        1 => Some(Direction::Clockwise),
+        3 => Some(Direction::CounterClockwise),
+        _ => None,
+    }
+}
+
+/// How a RotaryEncoder decodes its pins.
+///
+/// Defaults to a wrapping position, no debouncing and pull-ups on the pins.
+#[derive(Debug, Clone, Copy)]
+pub struct EncoderConfig {
+    #[cfg(target_os = "espidf")]
+    pull: Pull,
+    saturating: bool,
+    min: i32,
+    max: i32,
+    debounce_samples: u8,
+    confirm_steps: u8,
+    steps_per_detent: u8,
+    invert: bool,
+}
+
+impl EncoderConfig {
+    pub fn new() -> Self {
+        Self {
+            #[cfg(target_os = "espidf")]
+            pull: Pull::Up,
+            saturating: false,
+            min: i32::MIN,
+            max: i32::MAX,
+            debounce_samples: 1,
+            confirm_steps: 1,
+            steps_per_detent: 1,
+            invert: false,
+        }
+    }
+
+    /// The pull resistors of a RotaryEncoder's pins. Pull::Up for contacts
+    /// to GND, Pull::Floating for a module with pull-up resistors of its
+    /// own. from_pins() leaves the pins as they are.
+    #[cfg(target_os = "espidf")]
+    pub fn pull(mut self, pull: Pull) -> Self {
+        self.pull = pull;
+        self
+    }
+
+    /// Clamp the position at i32::MIN / i32::MAX instead of wrapping around.
+    pub fn saturating(mut self, saturating: bool) -> Self {
+        self.saturating = saturating;
+        self
+    }
+
+    /// Clamp the position to `min..=max`, which also turns saturating on.
+    /// The decoder starts from the bound closest to 0 when 0 is outside.
+    pub fn bounds(mut self, min: i32, max: i32) -> Self {
+        self.saturating = true;
+        self.min = min.min(max);
+        self.max = min.max(max);
+        self
+    }
+
+    /// Number of consecutive polls a pin must read the same new level for
+    /// it to be accepted. 0 and 1 accept every change right away.
+    ///
+    /// Each sample adds a poll period of latency, and the knob can't be
+    /// turned faster than `debounce_samples` polls per state.
+    pub fn debounce_samples(mut self, debounce_samples: u8) -> Self {
+        self.debounce_samples = debounce_samples;
+        self
+    }
+
+    /// Number of consecutive steps against the current direction needed to
+    /// believe the knob really reversed. 0 and 1 turn the filter off.
+    ///
+    /// Steps the other way are held back until they reach `confirm_steps`,
+    /// and then all counted at once. Fewer followed by steps back in the
+    /// current direction are dropped as noise, and so are as many of the
+    /// steps back: they only return to where the glitch started.
+    pub fn confirm_steps(mut self, confirm_steps: u8) -> Self {
+        self.confirm_steps = confirm_steps;
+        self
+    }
+
+    /// Number of transitions between two detents of the knob, e.g. 4 for
+    /// an encoder going through the whole gray code per click. The position
+    /// then moves by one per detent, 0 and 1 count every transition.
+    ///
+    /// poll() only reports a step, queues an event and applies the
+    /// acceleration once a whole detent is reached. With 2 or 4, landing on
+    /// the detent state drops what's short of a detent, or counts it if
+    /// it's at least half of one.
+    pub fn steps_per_detent(mut self, steps_per_detent: u8) -> Self {
+        self.steps_per_detent = steps_per_detent;
+        self
+    }
+
+    /// Swap the directions reported and counted, for an encoder whose A and
+    /// B pins are wired the other way round.
+    pub fn invert(mut self, invert: bool) -> Self {
+        self.invert = invert;
+        self
+    }
+}
+
+impl Default for EncoderConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EncoderConfig {
+    // `position` brought within the bounds, if saturating.
+    fn clamp(&self, position: i32) -> i32 {
+        if self.saturating {
+            position.clamp(self.min, self.max)
+        } else {
+            position
+        }
+    }
+}
+
+// The direction filter: only believes the knob reversed once enough steps
+// went the other way in a row. Atomics so it can be updated through &self,
+// e.g. from an ISR.
+struct DirectionFilter {
+    // Sign of the last counted step (0 before the first one), and how many
+    // steps the other way are waiting to be confirmed.
+    direction: AtomicI8,
+    reversing: AtomicU8,
+}
+
+impl DirectionFilter {
+    const fn new() -> Self {
+        Self {
+            direction: AtomicI8::new(0),
+            reversing: AtomicU8::new(0),
+        }
+    }
+
+    // How many steps of `sign` to count now, `needed` being confirm_steps.
+    // None while a reversal is still unconfirmed.
+    fn confirm(&self, sign: i8, needed: u8) -> Option<u8> {
+        let direction = self.direction.load(Ordering::SeqCst);
+        if needed <= 1 || direction == 0 {
+            self.direction.store(sign, Ordering::SeqCst);
+            return Some(1);
+        }
+
+        let reversing = self.reversing.load(Ordering::SeqCst);
+        if direction == sign {
+            // A step back over one held back cancels it: the pair was a
+            // glitch, leaving the knob where it was.
+            if reversing > 0 {
+                self.reversing.store(reversing - 1, Ordering::SeqCst);
+                return None;
+            }
+            return Some(1);
+        }
+
+        let reversing = reversing + 1;
+        if reversing < needed {
+            self.reversing.store(reversing, Ordering::SeqCst);
+            return None;
+        }
+
+        self.reversing.store(0, Ordering::SeqCst);
+        self.direction.store(sign, Ordering::SeqCst);
+        Some(reversing)
+    }
+}
+
+/// Counts the steps read from the A and B levels of an encoder into a
+/// position.
+///
+/// All methods take `&self` so the decoder can be polled from an ISR while
+/// the position is read elsewhere. The position is an i32, so it covers
+/// ±2147483647 steps. Past that it wraps around, or stays at the limit when
+/// configured as saturating.
+pub struct QuadratureDecoder<A, B> {
+    // Only touched by the poll() that set `polling`.
+    pins: UnsafeCell<(A, B)>,
+    polling: AtomicBool,
+    a_level: Debouncer,
+    b_level: Debouncer,
+    // Gray code seen on the previous poll.
+    previous: AtomicI8,
+    // Undebounced levels read on the previous poll, A in bit 0, B in bit 1.
+    raw: AtomicU8,
+    tracing: AtomicBool,
+    trace: IsrRingBuffer<Transition, TRACE_LEN>,
+    // Transitions dropped from a full trace since log_trace() last ran.
+    trace_dropped: AtomicU32,
+    position: AtomicI32,
+    filter: DirectionFilter,
+    // Transitions counted since the last whole detent, clockwise being
+    // positive, with steps_per_detent above 1.
+    pending: AtomicI32,
+    // Only called by the poll() that set `polling`, like the pins.
+    on_detent: UnsafeCell<Option<DetentCallback>>,
+    // Position at the last detent landed on.
+    detent_position: AtomicI32,
+    // Only called by the poll() that set `polling`, like the pins.
+    on_limit: UnsafeCell<Option<LimitCallback>>,
+    // Only called by the poll() that set `polling`, like the pins.
+    on_direction: UnsafeCell<Option<DirectionCallback>>,
+    // Limit the last step got clamped at, -1 for min, 1 for max and 0 if it
+    // wasn't.
+    clamped_at: AtomicI8,
+    // Filled by poll(), emptied by events().
+    events: IsrRingBuffer<EncoderEvent, EVENT_QUEUE_LEN>,
+    // Notified by poll() on each step, registered by a blocking
+    // EncoderEvents. Null while there's none.
+    #[cfg(target_os = "espidf")]
+    waiter: AtomicPtr<Notifier>,
+    // Polls that read the pins, wrapping around, for an EncoderEvents to
+    // tell whether something else polls the decoder.
+    polls: AtomicU32,
+    accel: Option<AccelFn>,
+    // When the last step was counted, only touched by the poll() that set
+    // `polling`.
+    last_step: UnsafeCell<Option<Duration>>,
+    config: EncoderConfig,
+}
+
+// SAFETY: the pins, the callbacks and the last step time
+// are the only state that isn't atomic or Sync, and poll() makes sure a
+// single caller at a time accesses them.
+unsafe impl<A: Send, B: Send> Sync for QuadratureDecoder<A, B> {}
+
+/// A rotary encoder on two input pins, counting steps into a position.
+#[cfg(target_os = "espidf")]
+pub type RotaryEncoder<'d> =
+    QuadratureDecoder<PinDriver<'d, AnyInputPin, Input>, PinDriver<'d, AnyInputPin, Input>>;
+
+#[cfg(target_os = "espidf")]
+impl<'d> RotaryEncoder<'d> {
+    /// Starts counting from 0 at the current knob position.
+    pub fn new(
+        a: impl Peripheral<P = impl InputPin> + 'd,
+        b: impl Peripheral<P = impl InputPin> + 'd,
+    ) -> Result<Self, EspError> {
+        Self::with_config(a, b, EncoderConfig::new())
+    }
+
+    /// Like new(), but the position clamps at i32::MIN / i32::MAX instead
+    /// of wrapping around.
+    pub fn saturating(
+        a: impl Peripheral<P = impl InputPin> + 'd,
+        b: impl Peripheral<P = impl InputPin> + 'd,
+    ) -> Result<Self, EspError> {
+        Self::with_config(a, b, EncoderConfig::new().saturating(true))
+    }
+
+    /// Like new(), but picks up the position save_position() stored before
+    /// the chip went to deep sleep.
+    ///
+    /// The position is only restored when waking up from deep sleep. On a
+    /// power on, reset, or if nothing was saved yet, it starts at `default`.
+    pub fn with_persistence(
+        a: impl Peripheral<P = impl InputPin> + 'd,
+        b: impl Peripheral<P = impl InputPin> + 'd,
+        kv: &KvStore,
+        default: i32,
+    ) -> Result<Self, EspError> {
+        Self::with_persistence_config(a, b, EncoderConfig::new(), kv, default)
+    }
+
+    /// Like with_persistence(), decoding as set in `config`. The restored
+    /// position is clamped to its bounds.
+    pub fn with_persistence_config(
+        a: impl Peripheral<P = impl InputPin> + 'd,
+        b: impl Peripheral<P = impl InputPin> + 'd,
+        config: EncoderConfig,
+        kv: &KvStore,
+        default: i32,
+    ) -> Result<Self, EspError> {
+        let encoder = Self::with_config(a, b, config)?;
+
+        let woke_up = matches!(wake_cause(), WakeCause::Timer | WakeCause::Gpio);
+        if !(woke_up && encoder.restore_position(kv)?) {
+            encoder.set_position(default);
+        }
+
+        Ok(encoder)
+    }
+
+    /// Starts counting from 0 at the current knob position, decoding as
+    /// set in `config`.
+    pub fn with_config(
+        a: impl Peripheral<P = impl InputPin> + 'd,
+        b: impl Peripheral<P = impl InputPin> + 'd,
+        config: EncoderConfig,
+    ) -> Result<Self, EspError> {
+        let a = PinDriver::input(a.into_ref().map_into::<AnyInputPin>())?;
+        let b = PinDriver::input(b.into_ref().map_into::<AnyInputPin>())?;
+        gpio::set_pull(&a, config.pull)?;
+        gpio::set_pull(&b, config.pull)?;
+
+        Ok(Self::from_pins(a, b, config))
+    }
+}
+
+impl<A: digital::InputPin, B: digital::InputPin> QuadratureDecoder<A, B> {
+    /// A decoder reading the A and B levels from `a` and `b`, counting
+    /// from 0.
+    ///
+    /// Each pin is read once here for the starting state, a pin failing
+    /// that starts out low. After that it's read once per poll().
+    pub fn from_pins(mut a: A, mut b: B, config: EncoderConfig) -> Self {
+        // Starting from the current levels, so the first poll doesn't count
+        // a step out of nowhere.
+        let a_high = a.is_high().unwrap_or(false);
+        let b_high = b.is_high().unwrap_or(false);
+        let start = config.clamp(0);
+        Self {
+            pins: UnsafeCell::new((a, b)),
+            polling: AtomicBool::new(false),
+            a_level: Debouncer::new(a_high),
+            b_level: Debouncer::new(b_high),
+            previous: AtomicI8::new(greycode_from_bits(a_high, b_high)),
+            raw: AtomicU8::new(raw_bits(a_high, b_high)),
+            tracing: AtomicBool::new(false),
+            trace: IsrRingBuffer::new(),
+            trace_dropped: AtomicU32::new(0),
+            position: AtomicI32::new(start),
+            filter: DirectionFilter::new(),
+            pending: AtomicI32::new(0),
+            on_detent: UnsafeCell::new(None),
+            detent_position: AtomicI32::new(start),
+            on_limit: UnsafeCell::new(None),
+            on_direction: UnsafeCell::new(None),
+            clamped_at: AtomicI8::new(0),
+            events: IsrRingBuffer::new(),
+            #[cfg(target_os = "espidf")]
+            waiter: AtomicPtr::new(ptr::null_mut()),
+            polls: AtomicU32::new(0),
+            accel: None,
+            last_step: UnsafeCell::new(None),
+            config,
+        }
+    }
+
+    /// Turns on acceleration with the stepped_acceleration() curve.
+    pub fn with_acceleration(self) -> Self {
+        self.with_accel_fn(stepped_acceleration)
+    }
+
+    /// Turns on acceleration with a custom curve: each step counts `f(time
+    /// since the previous step)` times. Results below 1 count once, and the
+    /// first step always counts once.
+    ///
+    /// `f` runs from poll(), so from the ISR when the decoder is polled from
+    /// one. It should be a quick computation and must not block, allocate
+    /// or log.
+    pub fn with_accel_fn<F>(mut self, f: F) -> Self
+    where
+        F: Fn(Duration) -> i32 + Send + Sync + 'static,
+    {
+        self.accel = Some(Box::new(f));
+        self
+    }
+
+    /// Samples the pins and updates the position, returning the step taken
+    /// since the previous poll if any, or the detent completed with
+    /// EncoderConfig::steps_per_detent().
+    ///
+    /// Doesn't block or allocate, so it can be called from an ISR. Returns
+    /// None without doing anything when a pin can't be read, or when called
+    /// while another poll() is still running, e.g. from an ISR that
+    /// interrupted it.
+    ///
+    /// Calls the on_detent() callback when the knob landed on a detent at
+    /// a new position, the on_limit() one when a step got clamped, and the
+    /// on_direction() one after every poll that read the pins.
+    pub fn poll(&self) -> Option<Direction> {
+        if self.polling.swap(true, Ordering::Acquire) {
+            return None;
+        }
+        // SAFETY: `polling` was unset, so nothing else accesses the pins, the
+        // callbacks or the last step time until it's cleared again.
+        let ((a, b), on_detent, on_limit, on_direction, last_step) = unsafe {
+            (
+                &mut *self.pins.get(),
+                &mut *self.on_detent.get(),
+                &mut *self.on_limit.get(),
+                &mut *self.on_direction.get(),
+                &mut *self.last_step.get(),
+            )
+        };
+        let Some(current) = self.read(a, b) else {
+            self.polling.store(false, Ordering::Release);
+            return None;
+        };
+        self.polls.fetch_add(1, Ordering::Relaxed);
+
+        let previous = self.previous.load(Ordering::SeqCst);
+        let before = self.position();
+        let stepped = self.count(current, on_limit, last_step);
+        if previous != current && self.tracing.load(Ordering::Relaxed) {
+            self.record(Transition {
+                from: previous,
+                to: current,
+                direction: step(previous, current),
+                delta: self.position().wrapping_sub(before),
+            });
+        }
+        if current == DETENT {
+            self.land(on_detent);
+        }
+        if let Some(direction) = stepped {
+            // A full queue drops the event, the step is still counted.
+            let _ = self.events.push(EncoderEvent {
+                index: 0,
+                direction,
+                position: self.position(),
+            });
+            #[cfg(target_os = "espidf")]
+            self.wake_waiter();
+        }
+        if let Some(callback) = on_direction {
+            callback(stepped);
+        }
+        self.polling.store(false, Ordering::Release);
+
+        stepped
+    }
+
+    /// Runs `callback` with the new position each time the knob comes to
+    /// rest on a detent, replacing any previously registered one.
+    ///
+    /// Turning partway to the next detent and back to the same one doesn't
+    /// call it. When the decoder is polled from an ISR so is the callback,
+    /// it should be short and must not block, allocate or log.
+    pub fn on_detent<F>(&mut self, callback: F)
+    where
+        F: FnMut(i32) + Send + 'static,
+    {
+        *self.on_detent.get_mut() = Some(Box::new(callback));
+    }
+
+    /// Runs `callback` when a step gets clamped because it would take the
+    /// position past the bounds, replacing any previously registered one.
+    ///
+    /// Only the first clamped step calls it, steps that keep pushing against
+    /// the same limit don't until the knob turned back in range. Reaching
+    /// the limit exactly isn't clamped, the next step past it is. The
+    /// bounds are i32::MIN / i32::MAX unless set with EncoderConfig::bounds(),
+    /// and a wrapping decoder never calls it. Runs from poll() like the
+    /// on_detent() callback, with the same restrictions.
+    pub fn on_limit<F>(&mut self, callback: F)
+    where
+        F: FnMut(Limit) + Send + 'static,
+    {
+        *self.on_limit.get_mut() = Some(Box::new(callback));
+    }
+
+    /// Runs `callback` after every poll that read the pins, with the step
+    /// poll() returned, replacing any previously registered one.
+    ///
+    /// It decides what the hardware does as the knob turns, e.g. drive a
+    /// motor or light an LED, see clockwise_indicator(). Runs from poll()
+    /// like the on_detent() callback, with the same restrictions.
+    pub fn on_direction<F>(&mut self, callback: F)
+    where
+        F: FnMut(Option<Direction>) + Send + 'static,
+    {
+        *self.on_direction.get_mut() = Some(Box::new(callback));
+    }
+
+    /// The steps taken, as EncoderEvents with index 0, oldest first.
+    ///
+    /// poll() queues up to EVENT_QUEUE_LEN of them. When those run out the
+    /// iterator polls the decoder itself, so it works with or without a
+    /// timer alarm polling it. Only one iterator gets each event.
+    ///
+    /// A Blocking one polls every 10 ms while waiting, too slow for a quick
+    /// turn, until it sees something else poll the decoder, e.g. an alarm
+    /// with the decoder in an Arc. From then on it sleeps until poll()
+    /// queues a step, so an idle knob costs the iterating thread nothing.
+    /// Only one Blocking iterator at a time is woken up like that, others
+    /// keep polling every 10 ms.
+    pub fn events(&self, mode: EventMode) -> EncoderEvents<'_, A, B> {
+        EncoderEvents {
+            decoder: self,
+            mode,
+            #[cfg(target_os = "espidf")]
+            notification: None,
+            #[cfg(target_os = "espidf")]
+            registered: false,
+            #[cfg(target_os = "espidf")]
+            polled_elsewhere: false,
+        }
+    }
+
+    /// The A and B levels read by the last poll, before debouncing, for
+    /// checking the wiring. The levels of from_pins() until the first poll.
+    #[cfg(target_os = "espidf")]
+    pub fn raw_state(&self) -> (Level, Level) {
+        let raw = self.raw.load(Ordering::Relaxed);
+        ((raw & 1 != 0).into(), (raw & 2 != 0).into())
+    }
+
+    /// Turns the debug trace on or off. While on, poll() records every
+    /// gray code change for log_trace(), up to TRACE_LEN of them.
+    ///
+    /// While off it costs poll() a single atomic load.
+    pub fn debug_trace(&self, enabled: bool) {
+        self.tracing.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Logs the transitions the debug trace recorded since the last call,
+    /// returning how many. Call it from a thread, e.g. the main loop.
+    pub fn log_trace(&self) -> usize {
+        // The A and B levels of each gray code.
+        const LEVELS: [&str; 4] = ["00", "01", "11", "10"];
+
+        let mut logged = 0;
+        while let Some(transition) = self.trace.pop() {
+            let step = match transition.direction {
+                Some(Direction::Clockwise) => "CW",
+                Some(Direction::CounterClockwise) => "CCW",
+                None => "skipped a state",
+            };
+            log::info!(
+                "Encoder AB {} -> {}: {}, delta {:+}",
+                LEVELS[(transition.from & 3) as usize],
+                LEVELS[(transition.to & 3) as usize],
+                step,
+                transition.delta
+            );
+            logged += 1;
+        }
+
+        let dropped = self.trace_dropped.swap(0, Ordering::Relaxed);
+        if dropped > 0 {
+            log::warn!("Encoder trace full, {} transitions dropped", dropped);
+        }
+        logged
+    }
+
+    /// Steps counted so far, clockwise being positive.
+    pub fn position(&self) -> i32 {
+        self.position.load(Ordering::SeqCst)
+    }
+
+    /// Also makes `position` the current detent, so it isn't reported as a
+    /// landing. It's clamped to the bounds of a saturating decoder.
+    pub fn set_position(&self, position: i32) {
+        let position = self.config.clamp(position);
+        self.clamped_at.store(0, Ordering::SeqCst);
+        self.pending.store(0, Ordering::SeqCst);
+        self.position.store(position, Ordering::SeqCst);
+        self.detent_position.store(position, Ordering::SeqCst);
+    }
+
+    /// Stores the position in `kv`, e.g. right before deep sleeping.
+    ///
+    /// Every encoder uses the same key, give each its own KvStore namespace.
+    /// `kv` is taken as `&mut` since every KvStore setter is, writing a key
+    /// changes the store.
+    #[cfg(target_os = "espidf")]
+    pub fn save_position(&self, kv: &mut KvStore) -> Result<(), EspError> {
+        kv.set_i32(POSITION_KEY, self.position())
+    }
+
+    /// Sets the position to the one save_position() stored in `kv`,
+    /// returning false and leaving it as is if none was.
+    #[cfg(target_os = "espidf")]
+    pub fn restore_position(&self, kv: &KvStore) -> Result<bool, EspError> {
+        match kv.get_i32(POSITION_KEY)? {
+            Some(position) => {
+                self.set_position(position);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    // Gray code of the debounced pin levels, None if a pin can't be read.
+    fn read(&self, a: &mut A, b: &mut B) -> Option<i8> {
+        let (a_high, b_high) = (a.is_high().ok()?, b.is_high().ok()?);
+        self.raw.store(raw_bits(a_high, b_high), Ordering::Relaxed);
+        let samples = self.config.debounce_samples;
+        let a = self.a_level.update(a_high, samples);
+        let b = self.b_level.update(b_high, samples);
+        Some(greycode_from_bits(a, b))
+    }
+
+    // Counts the step from the previous gray code to `current`, if any,
+    // returning its direction once it completes a detent.
+    fn count(
+        &self,
+        current: i8,
+        on_limit: &mut Option<LimitCallback>,
+        last_step: &mut Option<Duration>,
+    ) -> Option<Direction> {
+        let previous = self.previous.swap(current, Ordering::SeqCst);
+
+        // A skipped state isn't counted, but can still land on the detent.
+        let steps = match step(previous, current) {
+            Some(mut direction) => {
+                if self.config.invert {
+                    direction = direction.reversed();
+                }
+                let sign = match direction {
+                    Direction::Clockwise => 1,
+                    Direction::CounterClockwise => -1,
+                };
+                let steps = self.filter.confirm(sign, self.config.confirm_steps)?;
+                i32::from(sign) * i32::from(steps)
+            }
+            None => 0,
+        };
+        let detents = self.detents(steps, current);
+        let direction = match detents.signum() {
+            1 => Direction::Clockwise,
+            -1 => Direction::CounterClockwise,
+            _ => return None,
+        };
+
+        let multiplier = self.multiplier(last_step);
+        let clamped = self.add(detents.saturating_mul(multiplier));
+        self.clamp_at(clamped, on_limit);
+
+        Some(direction)
+    }
+
+    // Adds `steps` transitions to the pending ones, returning the whole
+    // detents they make, see EncoderConfig::steps_per_detent().
+    fn detents(&self, steps: i32, current: i8) -> i32 {
+        let per_detent = i32::from(self.config.steps_per_detent.max(1));
+        if per_detent == 1 {
+            return steps;
+        }
+
+        let mut pending = self.pending.load(Ordering::SeqCst) + steps;
+        // Rounds toward 0, a reversal partway takes back what it added.
+        let mut detents = pending / per_detent;
+        pending -= detents * per_detent;
+        // The detent state is only ever reached on a detent when those are
+        // a whole number of gray code cycles apart, or half of one.
+        if current == DETENT && 4 % per_detent == 0 {
+            if 2 * pending.abs() >= per_detent {
+                detents += pending.signum();
+            }
+            pending = 0;
+        }
+        self.pending.store(pending, Ordering::SeqCst);
+
+        detents
+    }
+
+    // Calls the limit callback when a step got clamped at a limit the one
+    // before wasn't.
+    fn clamp_at(&self, limit: Option<Limit>, on_limit: &mut Option<LimitCallback>) {
+        let code = match limit {
+            Some(Limit::Min) => -1,
+            Some(Limit::Max) => 1,
+            None => 0,
+        };
+        if self.clamped_at.swap(code, Ordering::SeqCst) == code {
+            return;
+        }
+        if let (Some(limit), Some(callback)) = (limit, on_limit) {
+            callback(limit);
+        }
+    }
+
+    // How many times the step counted now counts with acceleration.
+    fn multiplier(&self, last_step: &mut Option<Duration>) -> i32 {
+        let Some(accel) = &self.accel else {
+            return 1;
+        };
+
+        let now = now();
+        match last_step.replace(now) {
+            Some(last) => accel(now.saturating_sub(last)).max(1),
+            None => 1,
+        }
+    }
+
+    fn record(&self, transition: Transition) {
+        if self.trace.push(transition).is_err() {
+            self.trace_dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    // Calls the detent callback if the detent landed on isn't the last one.
+    fn land(&self, on_detent: &mut Option<DetentCallback>) {
+        let position = self.position();
+        if self.detent_position.swap(position, Ordering::SeqCst) == position {
+            return;
+        }
+        if let Some(callback) = on_detent {
+            callback(position);
+        }
+    }
+
+    // Moves the position by `delta`, returning the limit it got clamped at.
+    fn add(&self, delta: i32) -> Option<Limit> {
+        if !self.config.saturating {
+            self.position.fetch_add(delta, Ordering::SeqCst);
+            return None;
+        }
+
+        let (min, max) = (i64::from(self.config.min), i64::from(self.config.max));
+        let mut clamped = None;
+        // The closure always returns Some, so this can't fail. Newer
+        // toolchains call it try_update(), missing from rust-version.
+        #[allow(deprecated)]
+        let _ = self
+            .position
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |position| {
+                let wanted = i64::from(position) + i64::from(delta);
+                clamped = if wanted > max {
+                    Some(Limit::Max)
+                } else if wanted < min {
+                    Some(Limit::Min)
+                } else {
+                    None
+                };
+                Some(wanted.clamp(min, max) as i32)
+            });
+        clamped
+    }
+
+    // Wakes the Blocking EncoderEvents waiting for a step, if there's one.
+    #[cfg(target_os = "espidf")]
+    fn wake_waiter(&self) {
+        let waiter = self.waiter.load(Ordering::SeqCst);
+        if !waiter.is_null() {
+            // SAFETY: the EncoderEvents that registered the notifier only
+            // drops it after clearing `waiter` and waiting for the poll()
+            // running meanwhile, this one, to finish.
+            unsafe { (*waiter).notify_and_yield(NonZeroU32::MIN) };
+        }
+    }
+}
+
+/// Iterator over the steps of a QuadratureDecoder, see
+/// QuadratureDecoder::events().
+pub struct EncoderEvents<'a, A, B> {
+    decoder: &'a QuadratureDecoder<A, B>,
+    mode: EventMode,
+    // Registered as the decoder's waiter on the first wait, on the thread
+    // iterating, unless another iterator is already.
+    #[cfg(target_os = "espidf")]
+    notification: Option<Notification>,
+    #[cfg(target_os = "espidf")]
+    registered: bool,
+    // Whether another poller showed up, so waits need no timeout.
+    #[cfg(target_os = "espidf")]
+    polled_elsewhere: bool,
+}
+
+#[cfg(target_os = "espidf")]
+impl<A, B> EncoderEvents<'_, A, B> {
+    // Sleeps until poll() queues a step, or for EVENT_WAIT while nothing
+    // else may be polling the decoder.
+    fn wait(&mut self) {
+        if !self.registered {
+            self.registered = true;
+            let notification = Notification::new();
+            let notifier = Arc::as_ptr(&notification.notifier()).cast_mut();
+            let waiter = &self.decoder.waiter;
+            if waiter
+                .compare_exchange(
+                    ptr::null_mut(),
+                    notifier,
+                    Ordering::SeqCst,
+                    Ordering::SeqCst,
+                )
+                .is_ok()
+            {
+                self.notification = Some(notification);
+            }
+        }
+
+        let polls = self.decoder.polls.load(Ordering::Relaxed);
+        match &self.notification {
+            Some(notification) if self.polled_elsewhere => {
+                notification.wait(BLOCK);
+            }
+            Some(notification) => {
+                notification.wait(TickType::from(EVENT_WAIT).ticks());
+            }
+            None => thread::sleep(EVENT_WAIT),
+        }
+        // This iterator didn't poll meanwhile.
+        if self.decoder.polls.load(Ordering::Relaxed) != polls {
+            self.polled_elsewhere = true;
+        }
+    }
+}
+
+#[cfg(not(target_os = "espidf"))]
+impl<A, B> EncoderEvents<'_, A, B> {
+    // No task notification to sleep on, so this polls every EVENT_WAIT.
+    fn wait(&mut self) {
+        thread::sleep(EVENT_WAIT);
+    }
+}
+
+impl<A: digital::InputPin, B: digital::InputPin> Iterator for EncoderEvents<'_, A, B> {
+    type Item = EncoderEvent;
+
+    fn next(&mut self) -> Option<EncoderEvent> {
+        loop {
+            if let Some(event) = self.decoder.events.pop() {
+                return Some(event);
+            }
+            // A step queues an event, picked up on the next pass.
+            if self.decoder.poll().is_none() {
+                match self.mode {
+                    EventMode::NonBlocking => return None,
+                    EventMode::Blocking => self.wait(),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "espidf")]
+impl<A, B> Drop for EncoderEvents<'_, A, B> {
+    fn drop(&mut self) {
+        if self.notification.is_none() {
+            return;
+        }
+
+        self.decoder.waiter.store(ptr::null_mut(), Ordering::SeqCst);
+        // A poll() running meanwhile may have read the waiter before, the
+        // notification has to outlive it. Taking `polling` like a poll()
+        // does waits for it to finish, sleeping in case it runs in a lower
+        // priority thread.
+        let polling = &self.decoder.polling;
+        while polling
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            thread::sleep(EVENT_WAIT);
+        }
+        polling.store(false, Ordering::Release);
+    }
+}
+
+/// A step of one of the encoders of an EncoderBank, or of a lone decoder's
+/// events().
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncoderEvent {
+    /// Index of the encoder in the bank, as returned by EncoderBank::add().
+    /// Always 0 from QuadratureDecoder::events().
+    pub index: usize,
+    pub direction: Direction,
+    /// Position of the encoder after the step.
+    pub position: i32,
+}
+
+// Called with every step of one encoder.
+#[cfg(target_os = "espidf")]
+type EventCallback<'d> = Box<dyn FnMut(EncoderEvent) + Send + 'd>;
+
+/// Several encoders polled together, e.g. from a single timer alarm, with a
+/// callback per encoder.
+#[cfg(target_os = "espidf")]
+pub struct EncoderBank<'d> {
+    encoders: Vec<RotaryEncoder<'d>>,
+    callbacks: Vec<Option<EventCallback<'d>>>,
+}
+
+#[cfg(target_os = "espidf")]
+impl<'d> EncoderBank<'d> {
+    pub fn new() -> Self {
+        Self {
+            encoders: Vec::new(),
+            callbacks: Vec::new(),
+        }
+    }
+
+    /// Adds `encoder` to the bank, returning its index.
+    pub fn add(&mut self, encoder: RotaryEncoder<'d>) -> usize {
+        self.encoders.push(encoder);
+        self.callbacks.push(None);
+        self.encoders.len() - 1
+    }
+
+    /// Runs `callback` on every step of encoder `index`, replacing any
+    /// previously registered one.
+    ///
+    /// When the bank is polled from an ISR so are the callbacks, they should
+    /// be short and must not block, allocate or log. Fails with
+    /// ESP_ERR_INVALID_ARG if there is no encoder `index`.
+    pub fn on_event<F>(&mut self, index: usize, callback: F) -> Result<(), EspError>
+    where
+        F: FnMut(EncoderEvent) + Send + 'd,
+    {
+        let slot = self
+            .callbacks
+            .get_mut(index)
+            .ok_or_else(EspError::from_infallible::<ESP_ERR_INVALID_ARG>)?;
+        *slot = Some(Box::new(callback));
+        Ok(())
+    }
+
+    pub fn encoder(&self, index: usize) -> Option<&RotaryEncoder<'d>> {
+        self.encoders.get(index)
+    }
+
+    /// Number of encoders in the bank.
+    pub fn len(&self) -> usize {
+        self.encoders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.encoders.is_empty()
+    }
+
+    /// Polls every encoder, calling the callback of each one that stepped.
+    ///
+    /// Doesn't allocate, so it can be called from an ISR.
+    pub fn poll(&mut self) {
+        let encoders = self.encoders.iter().zip(self.callbacks.iter_mut());
+        for (index, (encoder, callback)) in encoders.enumerate() {
+            let Some(direction) = encoder.poll() else {
+                continue;
+            };
+            if let Some(callback) = callback {
+                callback(EncoderEvent {
+                    index,
+                    direction,
+                    position: encoder.position(),
+                });
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "espidf")]
+impl Default for EncoderBank<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::levels::MockLevelSource;
+
+    // Gray codes of one turn from a detent to the next.
+    const CW: [i8; 4] = [1, 2, 3, 0];
+    const CCW: [i8; 4] = [3, 2, 1, 0];
+
+    type MockDecoder = QuadratureDecoder<MockLevelSource, MockLevelSource>;
+
+    // A decoder starting on the detent, whose pins then read `codes`, one
+    // gray code per poll.
+    fn decoder(config: EncoderConfig, codes: &[i8]) -> MockDecoder {
+        // The inverse of greycode_from_bits().
+        let levels = |high: fn(i8) -> bool| -> Vec<bool> {
+            core::iter::once(DETENT)
+                .chain(codes.iter().copied())
+                .map(high)
+                .collect()
+        };
+        let a = MockLevelSource::new(levels(|code| matches!(code, 2 | 3)));
+        let b = MockLevelSource::new(levels(|code| matches!(code, 1 | 2)));
+        QuadratureDecoder::from_pins(a, b, config)
+    }
+
+    // Polls `n` times, returning what each poll reported.
+    fn poll(decoder: &MockDecoder, n: usize) -> Vec<Option<Direction>> {
+        (0..n).map(|_| decoder.poll()).collect()
+    }
+
+    #[test]
+    fn greycode_from_bits_maps_all_four_states() {
+        assert_eq!(greycode_from_bits(false, false), 0);
+        assert_eq!(greycode_from_bits(false, true), 1);
+        assert_eq!(greycode_from_bits(true, true), 2);
+        assert_eq!(greycode_from_bits(true, false), 3);
+    }
+
+    #[test]
+    #[cfg(target_os = "espidf")]
+    fn gray_code_matches_greycode_from_bits() {
+        for (a, b) in [(false, false), (false, true), (true, true), (true, false)] {
+            assert_eq!(gray_code(a.into(), b.into()), greycode_from_bits(a, b));
+        }
+    }
+
+    #[test]
+    fn full_clockwise_turn() {
+        let encoder = decoder(EncoderConfig::new(), &CW);
+        assert_eq!(poll(&encoder, 4), [Some(Direction::Clockwise); 4]);
+        assert_eq!(encoder.position(), 4);
+    }
+
+    #[test]
+    fn full_counter_clockwise_turn() {
+        let encoder = decoder(EncoderConfig::new(), &CCW);
+        assert_eq!(poll(&encoder, 4), [Some(Direction::CounterClockwise); 4]);
+        assert_eq!(encoder.position(), -4);
+    }
+
+    #[test]
+    fn stuck_input_doesnt_count() {
+        let encoder = decoder(EncoderConfig::new(), &[DETENT; 8]);
+        assert_eq!(poll(&encoder, 8), [None; 8]);
+        assert_eq!(encoder.position(), 0);
+    }
+
+    #[test]
+    fn bouncy_input_doesnt_drift() {
+        // A contact chattering between two states, settling on the second.
+        let encoder = decoder(EncoderConfig::new(), &[1, 0, 1, 0, 1]);
+        poll(&encoder, 5);
+        assert_eq!(encoder.position(), 1);
+    }
+
+    #[test]
+    fn skipped_state_isnt_counted() {
+        let encoder = decoder(EncoderConfig::new(), &[2, 0]);
+        assert_eq!(poll(&encoder, 2), [None, None]);
+        assert_eq!(encoder.position(), 0);
+    }
+
+    #[test]
+    fn counts_200_steps_without_wrapping() {
+        let encoder = decoder(EncoderConfig::new(), &CW.repeat(50));
+        poll(&encoder, 200);
+        assert_eq!(encoder.position(), 200);
+    }
+
+    #[test]
+    fn saturating_stops_at_the_limit() {
+        let encoder = decoder(EncoderConfig::new().saturating(true), &CW.repeat(50));
+        encoder.set_position(i32::MAX - 100);
+        poll(&encoder, 200);
+        assert_eq!(encoder.position(), i32::MAX);
+    }
+
+    #[test]
+    fn debounce_rejects_a_bouncing_contact() {
+        // B chattering before settling high, one step clockwise.
+        let bouncing = [1, 0, 1, 0, 1, 1, 1];
+
+        let encoder = decoder(EncoderConfig::new().debounce_samples(3), &bouncing);
+        let steps = poll(&encoder, bouncing.len());
+        assert_eq!(steps.iter().flatten().count(), 1);
+        assert_eq!(encoder.position(), 1);
+
+        // Without debouncing every bounce is a step.
+        let encoder = decoder(EncoderConfig::new(), &bouncing);
+        let steps = poll(&encoder, bouncing.len());
+        assert_eq!(steps.iter().flatten().count(), 5);
+    }
+
+    #[test]
+    fn direction_filter_drops_a_lone_glitch() {
+        // CW, CW, a CCW glitch and back over it.
+        let filter = DirectionFilter::new();
+        let steps: Vec<_> = [1, 1, -1, 1].map(|sign| filter.confirm(sign, 2)).into();
+        assert_eq!(steps, [Some(1), Some(1), None, None]);
+
+        // Without the filter the glitch takes a step back.
+        let filter = DirectionFilter::new();
+        let steps: Vec<_> = [1, 1, -1, 1].map(|sign| filter.confirm(sign, 1)).into();
+        assert_eq!(steps, [Some(1); 4]);
+    }
+
+    #[test]
+    fn direction_filter_follows_a_real_reversal() {
+        let filter = DirectionFilter::new();
+        let steps: Vec<_> = [1, 1, -1, -1, -1]
+            .map(|sign| filter.confirm(sign, 2))
+            .into();
+        // The first step back is held until the second confirms both.
+        assert_eq!(steps, [Some(1), Some(1), None, Some(2), Some(1)]);
+    }
+
+    #[test]
+    fn invert_flips_direction_and_count() {
+        let encoder = decoder(EncoderConfig::new().invert(false), &CW);
+        assert_eq!(poll(&encoder, 4), [Some(Direction::Clockwise); 4]);
+        assert_eq!(encoder.position(), 4);
+
+        let inverted = decoder(EncoderConfig::new().invert(true), &CW);
+        assert_eq!(poll(&inverted, 4), [Some(Direction::CounterClockwise); 4]);
+        assert_eq!(inverted.position(), -4);
+    }
+
+    #[test]
+    fn steps_per_detent_counts_once_per_click() {
+        let encoder = decoder(EncoderConfig::new().steps_per_detent(4), &CW.repeat(3));
+        let click = [None, None, None, Some(Direction::Clockwise)];
+        assert_eq!(poll(&encoder, 12), click.repeat(3));
+        assert_eq!(encoder.position(), 3);
+    }
+
+    #[test]
+    fn steps_per_detent_ignores_a_partial_turn_and_back() {
+        let encoder = decoder(EncoderConfig::new().steps_per_detent(4), &[1, 2, 1, 0]);
+        assert_eq!(poll(&encoder, 4), [None; 4]);
+        assert_eq!(encoder.position(), 0);
+    }
+
+    #[test]
+    fn steps_per_detent_reverses_mid_detent() {
+        // A click clockwise, then halfway and a whole click back.
+        let codes = [&CW[..], &[1, 2, 1, 0], &CCW[..]].concat();
+        let encoder = decoder(EncoderConfig::new().steps_per_detent(4), &codes);
+        poll(&encoder, codes.len());
+        assert_eq!(encoder.position(), 0);
+    }
+
+    #[test]
+    fn non_blocking_events_drain_the_queue_then_poll() {
+        let encoder = decoder(EncoderConfig::new(), &CW);
+        // Queued by polls elsewhere, then polled by the iterator itself.
+        poll(&encoder, 2);
+        let positions: Vec<i32> = encoder
+            .events(EventMode::NonBlocking)
+            .inspect(|event| assert_eq!(event.direction, Direction::Clockwise))
+            .map(|event| event.position)
+            .collect();
+        assert_eq!(positions, [1, 2, 3, 4]);
+        assert_eq!(encoder.events(EventMode::NonBlocking).next(), None);
+    }
+}
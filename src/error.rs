@@ -0,0 +1,153 @@
+// Crate wide error type, so code using several of the modules can `?`
+// any of their errors into a single Result.
+//
+// The drivers keep their own error enums, e.g. DhtError holds a TimerError
+// to tell a timer failure from a sensor timeout. Error wraps all of them
+// and converts from each, the helpers calling straight to ESP IDF return
+// it directly.
+//
+// An EspError only holds the code, EspResultExt::context() wraps it with
+// the operation that failed so logs and panics say which call it was.
+
+use core::fmt;
+use std::error::Error as StdError;
+
+use esp_idf_svc::sys::{esp_err_t, EspError};
+
+use crate::{
+    bmp280::Bmp280Error, board::InitError, dht::DhtError, thermistor::ThermistorError,
+    timer::TimerError, ultrasonic::UltrasonicError, wifi::ConnectError,
+};
+
+/// `Result` with the crate's Error.
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Describes what a failing ESP IDF call was doing.
+///
+/// `EspError::convert(unsafe { timer_start(..) }).context("Starting the
+/// timer")?` fails with an Error displaying as "Starting the timer failed:
+/// ESP_ERR_INVALID_STATE" instead of only the code.
+pub trait EspResultExt<T> {
+    /// Wraps an error into Error::Context with `what`, a short description
+    /// of the operation.
+    fn context(self, what: &'static str) -> Result<T>;
+}
+
+impl<T> EspResultExt<T> for core::result::Result<T, EspError> {
+    fn context(self, what: &'static str) -> Result<T> {
+        self.map_err(|e| Error::Context(what, e))
+    }
+}
+
+/// Any error returned by the buds modules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// An ESP IDF call failed.
+    Esp(EspError),
+    Timer(TimerError),
+    Init(InitError),
+    Dht(DhtError),
+    Ultrasonic(UltrasonicError),
+    Bmp280(Bmp280Error),
+    Thermistor(ThermistorError),
+    Connect(ConnectError),
+    /// An ESP IDF call failed while doing the operation described.
+    Context(&'static str, EspError),
+}
+
+impl Error {
+    /// The esp_err_t code behind the error, if it came from an ESP IDF call.
+    pub fn esp_code(&self) -> Option<esp_err_t> {
+        match self {
+            Error::Esp(e)
+            | Error::Timer(TimerError::Esp(e))
+            | Error::Bmp280(Bmp280Error::I2c(e))
+            | Error::Thermistor(ThermistorError::Adc(e))
+            | Error::Connect(ConnectError::Wifi(e))
+            | Error::Context(_, e) => Some(e.code()),
+            Error::Timer(TimerError::InitFailed(code)) => Some(*code),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Esp(e) => write!(f, "ESP IDF call failed: {}", e),
+            // These already say what failed.
+            Error::Timer(e) => write!(f, "{}", e),
+            Error::Init(e) => write!(f, "{}", e),
+            Error::Dht(e) => write!(f, "{}", e),
+            Error::Ultrasonic(e) => write!(f, "{}", e),
+            Error::Bmp280(e) => write!(f, "{}", e),
+            Error::Thermistor(e) => write!(f, "{}", e),
+            Error::Connect(e) => write!(f, "{}", e),
+            Error::Context(what, e) => write!(f, "{} failed: {}", what, e),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Error::Esp(e) => Some(e),
+            Error::Timer(e) => Some(e),
+            Error::Init(e) => Some(e),
+            Error::Dht(e) => Some(e),
+            Error::Ultrasonic(e) => Some(e),
+            Error::Bmp280(e) => Some(e),
+            Error::Thermistor(e) => Some(e),
+            Error::Connect(e) => Some(e),
+            Error::Context(_, e) => Some(e),
+        }
+    }
+}
+
+impl From<EspError> for Error {
+    fn from(e: EspError) -> Self {
+        Error::Esp(e)
+    }
+}
+
+impl From<TimerError> for Error {
+    fn from(e: TimerError) -> Self {
+        Error::Timer(e)
+    }
+}
+
+impl From<InitError> for Error {
+    fn from(e: InitError) -> Self {
+        Error::Init(e)
+    }
+}
+
+impl From<DhtError> for Error {
+    fn from(e: DhtError) -> Self {
+        Error::Dht(e)
+    }
+}
+
+impl From<UltrasonicError> for Error {
+    fn from(e: UltrasonicError) -> Self {
+        Error::Ultrasonic(e)
+    }
+}
+
+impl From<Bmp280Error> for Error {
+    fn from(e: Bmp280Error) -> Self {
+        Error::Bmp280(e)
+    }
+}
+
+impl From<ThermistorError> for Error {
+    fn from(e: ThermistorError) -> Self {
+        Error::Thermistor(e)
+    }
+}
+
+impl From<ConnectError> for Error {
+    fn from(e: ConnectError) -> Self {
+        Error::Connect(e)
+    }
+}
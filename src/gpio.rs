@@ -0,0 +1,405 @@
+// Clean input from mechanical switches.
+//
+// Contacts bounce for a few milliseconds when they open or close. Polling
+// them through a Debouncer only lets a new level through once it was read
+// several times in a row. A SmartButton waits on edge interrupts instead and
+// reads the level once it settled.
+//
+// The pins get the internal pull resistor toward the released level by
+// default: pull-up for a button wired to GND, pull-down for one wired to
+// 3.3V. With external resistors, Pull::Floating leaves them off.
+
+use core::{
+    num::NonZeroU32,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use std::{
+    sync::{mpsc, Arc},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use esp_idf_svc::{
+    hal::{
+        delay::{TickType, BLOCK},
+        gpio::{AnyInputPin, Input, InputPin, InterruptType, PinDriver},
+        peripheral::Peripheral,
+        task::notification::{Notification, Notifier},
+    },
+    sys::{esp, gpio_set_pull_mode, EspError},
+};
+
+pub use esp_idf_svc::hal::gpio::Pull;
+
+use crate::levels::Debouncer;
+
+/// What changed on a DebouncedButton since the previous poll.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ButtonEvent {
+    /// Nothing, the button is still up or still held.
+    Idle,
+    Pressed,
+    /// Let go after being held for `held`.
+    Released {
+        held: Duration,
+    },
+}
+
+/// A push button on an input pin, debounced by polling.
+pub struct DebouncedButton<'d> {
+    pin: PinDriver<'d, AnyInputPin, Input>,
+    level: Debouncer,
+    stable_samples: u8,
+    active_low: bool,
+    // When the current press started, None while released.
+    pressed_at: Option<Instant>,
+}
+
+impl<'d> DebouncedButton<'d> {
+    /// A press only registers once `pin` read pressed on `stable_samples`
+    /// polls in a row, and the same goes for the release. 0 and 1 take
+    /// every change right away.
+    ///
+    /// With `active_low` set the button reads pressed when the pin is low,
+    /// e.g. wired to GND with a pull-up, otherwise when it's high.
+    ///
+    /// The pin is pulled toward the released level, see with_pull() to
+    /// change that.
+    pub fn new(
+        pin: impl Peripheral<P = impl InputPin> + 'd,
+        stable_samples: u8,
+        active_low: bool,
+    ) -> Result<Self, EspError> {
+        Self::with_pull(pin, stable_samples, active_low, released_pull(active_low))
+    }
+
+    /// Like new(), but with `pull` on the pin, e.g. Pull::Floating for a
+    /// button with an external pull resistor.
+    pub fn with_pull(
+        pin: impl Peripheral<P = impl InputPin> + 'd,
+        stable_samples: u8,
+        active_low: bool,
+        pull: Pull,
+    ) -> Result<Self, EspError> {
+        let pin = PinDriver::input(pin.into_ref().map_into::<AnyInputPin>())?;
+        set_pull(&pin, pull)?;
+
+        Ok(Self {
+            pin,
+            // Starts released, a button held at boot shows up as a press.
+            level: Debouncer::new(false),
+            stable_samples,
+            active_low,
+            pressed_at: None,
+        })
+    }
+
+    /// Samples the pin, call it periodically, every few milliseconds.
+    pub fn poll(&mut self) -> ButtonEvent {
+        let raw = self.pin.is_high() != self.active_low;
+        let pressed = self.level.update(raw, self.stable_samples);
+
+        match (pressed, self.pressed_at) {
+            (true, None) => {
+                self.pressed_at = Some(Instant::now());
+                ButtonEvent::Pressed
+            }
+            (false, Some(since)) => {
+                self.pressed_at = None;
+                ButtonEvent::Released {
+                    held: since.elapsed(),
+                }
+            }
+            _ => ButtonEvent::Idle,
+        }
+    }
+
+    /// Whether the button is held down, as of the last poll.
+    pub fn is_pressed(&self) -> bool {
+        self.pressed_at.is_some()
+    }
+
+    /// Whether the button has been held down for at least `threshold`.
+    pub fn is_long_press(&self, threshold: Duration) -> bool {
+        self.pressed_at
+            .is_some_and(|since| since.elapsed() >= threshold)
+    }
+}
+
+// The pull holding a button at its released level.
+fn released_pull(active_low: bool) -> Pull {
+    if active_low {
+        Pull::Up
+    } else {
+        Pull::Down
+    }
+}
+
+// Sets the pull resistor of an input pin.
+//
+// PinDriver::set_pull() is only there for pins that can also be outputs,
+// ESP IDF rejects pulls on input only pins with ESP_ERR_INVALID_ARG itself.
+pub(crate) fn set_pull(pin: &PinDriver<AnyInputPin, Input>, pull: Pull) -> Result<(), EspError> {
+    // SAFETY: gpio_set_pull_mode() is an ESP32 ABI call.
+    esp!(unsafe { gpio_set_pull_mode(pin.pin(), pull.into()) })
+}
+
+// How long a SmartButton lets the contacts bounce after an edge before
+// reading the level.
+const SETTLE_TIME: Duration = Duration::from_millis(20);
+
+// Notification bits of a SmartButton's thread, 0 and 1: the ISR sets EDGE,
+// drop() sets STOP.
+const EDGE: NonZeroU32 = NonZeroU32::MIN;
+const STOP: NonZeroU32 = EDGE.saturating_add(1);
+
+/// A gesture recognised by a SmartButton.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClickEvent {
+    /// Pressed and released once, with no second press in the double click
+    /// window.
+    Click,
+    /// Released a second time within the double click window.
+    DoubleClick,
+    /// Held for the long press threshold, sent while still held. The
+    /// release that follows isn't a click.
+    LongPress,
+}
+
+/// Timings of a SmartButton.
+#[derive(Debug, Clone, Copy)]
+pub struct SmartButtonConfig {
+    double_click_window: Duration,
+    long_press: Duration,
+    active_low: bool,
+    // None pulls toward the released level.
+    pull: Option<Pull>,
+}
+
+impl SmartButtonConfig {
+    /// 300 ms double click window, 800 ms long press, active low, pulled
+    /// toward the released level.
+    pub fn new() -> Self {
+        Self {
+            double_click_window: Duration::from_millis(300),
+            long_press: Duration::from_millis(800),
+            active_low: true,
+            pull: None,
+        }
+    }
+
+    /// How long after a click's release a second one makes it a double
+    /// click. Also how long a Click is held back waiting for that.
+    pub fn double_click_window(mut self, window: Duration) -> Self {
+        self.double_click_window = window;
+        self
+    }
+
+    /// How long the button has to be held for a LongPress.
+    pub fn long_press(mut self, threshold: Duration) -> Self {
+        self.long_press = threshold;
+        self
+    }
+
+    /// Whether the button reads pressed when the pin is low.
+    pub fn active_low(mut self, active_low: bool) -> Self {
+        self.active_low = active_low;
+        self
+    }
+
+    /// The pull resistor on the pin, instead of the one toward the released
+    /// level, e.g. Pull::Floating with an external one.
+    pub fn pull(mut self, pull: Pull) -> Self {
+        self.pull = Some(pull);
+        self
+    }
+}
+
+impl Default for SmartButtonConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A push button turning edge interrupts into clicks, double clicks and
+/// long presses.
+///
+/// The pin is handed to a thread that sleeps until an edge, or until a
+/// double click window or long press threshold runs out, and sends the
+/// events down a channel. Dropping the SmartButton stops the thread and
+/// waits for it to exit.
+pub struct SmartButton {
+    events: mpsc::Receiver<ClickEvent>,
+    running: Arc<AtomicBool>,
+    notifier: Arc<Notifier>,
+    recogniser: Option<JoinHandle<()>>,
+}
+
+impl SmartButton {
+    pub fn new(
+        pin: impl Peripheral<P = impl InputPin> + 'static,
+        config: SmartButtonConfig,
+    ) -> Result<Self, EspError> {
+        let mut pin = PinDriver::input(pin.into_ref().map_into::<AnyInputPin>())?;
+        set_pull(
+            &pin,
+            config.pull.unwrap_or(released_pull(config.active_low)),
+        )?;
+        pin.set_interrupt_type(InterruptType::AnyEdge)?;
+
+        let (events_tx, events_rx) = mpsc::channel();
+        let running = Arc::new(AtomicBool::new(true));
+        let (ready_tx, ready_rx) = mpsc::sync_channel(1);
+        let recogniser = {
+            let running = running.clone();
+            thread::spawn(move || {
+                // The ISR notifies the thread that creates the Notification.
+                let notification = Notification::new();
+                let notifier = notification.notifier();
+                let isr_notifier = notifier.clone();
+                // Declared after the notification so it's dropped first,
+                // which unsubscribes the ISR before the notifier goes away.
+                let mut pin = pin;
+
+                let on_edge = move || {
+                    // SAFETY: the notification isn't forgotten, it outlives
+                    // the subscription.
+                    unsafe { isr_notifier.notify_and_yield(EDGE) };
+                };
+                // SAFETY: the callback only notifies a task, which is ISR safe.
+                let subscribed =
+                    unsafe { pin.subscribe(on_edge) }.and_then(|_| pin.enable_interrupt());
+                let failed = subscribed.is_err();
+                let _ = ready_tx.send(subscribed.map(|_| notifier));
+                if failed {
+                    return;
+                }
+
+                if !recognise(&mut pin, &notification, config, &running, &events_tx) {
+                    // Given up early, drop() still has to notify this thread.
+                    while !stopped(notification.wait(BLOCK), &running) {}
+                }
+            })
+        };
+
+        // The thread always answers before it can exit.
+        let notifier = ready_rx.recv().unwrap()?;
+
+        Ok(Self {
+            events: events_rx,
+            running,
+            notifier,
+            recogniser: Some(recogniser),
+        })
+    }
+
+    /// The events, in the order they happened. Once a Click is sent it's
+    /// at least a double click window old.
+    pub fn events(&self) -> &mpsc::Receiver<ClickEvent> {
+        &self.events
+    }
+}
+
+impl Drop for SmartButton {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        // SAFETY: the thread owning the notification only exits once it saw
+        // this notification.
+        unsafe { self.notifier.notify_and_yield(STOP) };
+        if let Some(recogniser) = self.recogniser.take() {
+            let _ = recogniser.join();
+        }
+    }
+}
+
+// Whether a wake of a SmartButton's thread is drop()'s. Only that one may
+// end the thread: ended on an edge's instead, drop() could be about to
+// notify a task that's gone.
+fn stopped(bits: Option<NonZeroU32>, running: &AtomicBool) -> bool {
+    bits.is_some_and(|bits| bits.get() & STOP.get() != 0) && !running.load(Ordering::SeqCst)
+}
+
+// Runs a SmartButton until it's dropped, returning true, or until its pin
+// fails, returning false.
+fn recognise(
+    pin: &mut PinDriver<AnyInputPin, Input>,
+    notification: &Notification,
+    config: SmartButtonConfig,
+    running: &AtomicBool,
+    events: &mpsc::Sender<ClickEvent>,
+) -> bool {
+    let emit = |event| events.send(event).is_ok();
+
+    let mut pressed = false;
+    let mut pressed_at = Instant::now();
+    let mut long_press_sent = false;
+    // When a click was released that a second one could make a double click.
+    let mut pending_click: Option<Instant> = None;
+
+    loop {
+        // Sleep until an edge or the next deadline, whichever comes first.
+        let now = Instant::now();
+        let long_press_due = (pressed && !long_press_sent)
+            .then(|| config.long_press.saturating_sub(now - pressed_at));
+        let click_due =
+            pending_click.map(|released| config.double_click_window.saturating_sub(now - released));
+        let timeout = match (long_press_due, click_due) {
+            (Some(a), Some(b)) => TickType::from(a.min(b)).ticks(),
+            (Some(due), None) | (None, Some(due)) => TickType::from(due).ticks(),
+            (None, None) => BLOCK,
+        };
+
+        let bits = notification.wait(timeout);
+        if stopped(bits, running) {
+            return true;
+        }
+        if bits.is_some_and(|bits| bits.get() & EDGE.get() != 0) {
+            thread::sleep(SETTLE_TIME);
+            // The driver disables the interrupt after each edge. Re-armed
+            // before reading so an edge right after the read isn't missed.
+            if let Err(e) = pin.enable_interrupt() {
+                log::error!("Button interrupt couldn't be re-enabled: {}", e);
+                return false;
+            }
+
+            let level = pin.is_high() != config.active_low;
+            if level != pressed {
+                pressed = level;
+                if pressed {
+                    pressed_at = Instant::now();
+                    long_press_sent = false;
+                } else if !long_press_sent {
+                    if pending_click.take().is_some() {
+                        if !emit(ClickEvent::DoubleClick) {
+                            return false;
+                        }
+                    } else {
+                        pending_click = Some(Instant::now());
+                    }
+                }
+            }
+        }
+
+        let now = Instant::now();
+        // A second press still held when the window runs out is too slow
+        // for a double click. The first one is a Click and the held press
+        // goes on as a new one, turning into a click or long press itself.
+        if pending_click.is_some_and(|released| now - released >= config.double_click_window) {
+            pending_click = None;
+            if !emit(ClickEvent::Click) {
+                return false;
+            }
+        }
+        if pressed && !long_press_sent && now - pressed_at >= config.long_press {
+            long_press_sent = true;
+            // With a window longer than the threshold, the click before
+            // still has to come first.
+            if pending_click.take().is_some() && !emit(ClickEvent::Click) {
+                return false;
+            }
+            if !emit(ClickEvent::LongPress) {
+                return false;
+            }
+        }
+    }
+}
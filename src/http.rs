@@ -0,0 +1,78 @@
+// One call HTTP(S) requests, for fetching data once WiFi is connected.
+//
+// HTTPS servers are checked against the certificate bundle shipped with
+// ESP IDF (CONFIG_MBEDTLS_CERTIFICATE_BUNDLE, on by default).
+
+use std::time::Duration;
+
+use esp_idf_svc::{
+    http::{
+        client::{Configuration, EspHttpConnection, FollowRedirectsPolicy},
+        Method,
+    },
+    sys::{esp_crt_bundle_attach, EspError, ESP_ERR_INVALID_RESPONSE},
+};
+
+/// How long http_get() waits on the server by default.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Size of the chunks the body is read in.
+const READ_CHUNK: usize = 512;
+
+/// Fetches `url` and returns the body of the response.
+///
+/// Same as http_get_with() with the DEFAULT_TIMEOUT and no extra headers.
+pub fn http_get(url: &str) -> Result<Vec<u8>, EspError> {
+    http_get_with(url, DEFAULT_TIMEOUT, &[])
+}
+
+/// Fetches `url`, sending the `headers` (name, value) pairs along, and
+/// returns the body of the response.
+///
+/// Redirects are followed. Fails with ESP_ERR_INVALID_RESPONSE when the
+/// final status isn't a 2xx, or with ESP_ERR_HTTP_* / ESP_ERR_TIMEOUT when
+/// the server can't be reached within `timeout`.
+pub fn http_get_with(
+    url: &str,
+    timeout: Duration,
+    headers: &[(&str, &str)],
+) -> Result<Vec<u8>, EspError> {
+    let mut connection = get(url, timeout, headers)?;
+
+    let mut body = Vec::new();
+    let mut chunk = [0u8; READ_CHUNK];
+    loop {
+        match connection.read(&mut chunk)? {
+            0 => break,
+            n => body.extend_from_slice(&chunk[..n]),
+        }
+    }
+
+    Ok(body)
+}
+
+// Sends the GET request, returning the connection with a 2xx response
+// ready for reading its body.
+pub(crate) fn get(
+    url: &str,
+    timeout: Duration,
+    headers: &[(&str, &str)],
+) -> Result<EspHttpConnection, EspError> {
+    let mut connection = EspHttpConnection::new(&Configuration {
+        timeout: Some(timeout),
+        follow_redirects_policy: FollowRedirectsPolicy::FollowAll,
+        crt_bundle_attach: Some(esp_crt_bundle_attach),
+        ..Default::default()
+    })?;
+
+    connection.initiate_request(Method::Get, url, headers)?;
+    connection.initiate_response()?;
+
+    let status = connection.status();
+    if !(200..300).contains(&status) {
+        log::warn!("GET {} answered {}", url, status);
+        return Err(EspError::from_infallible::<ESP_ERR_INVALID_RESPONSE>());
+    }
+
+    Ok(connection)
+}
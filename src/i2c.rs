@@ -0,0 +1,126 @@
+// Helpers for talking to devices on an I2C bus.
+
+use std::{borrow::BorrowMut, marker::PhantomData, time::Duration};
+
+use esp_idf_svc::{
+    hal::{delay::TickType, i2c::I2cDriver},
+    sys::{EspError, TickType_t, ESP_ERR_INVALID_ARG},
+};
+
+/// Lowest address probed by `scan_i2c`, the ones below are reserved.
+pub const FIRST_ADDRESS: u8 = 0x03;
+
+/// Highest address probed by `scan_i2c`, the ones above are reserved.
+pub const LAST_ADDRESS: u8 = 0x77;
+
+// How long a device gets to ACK its address.
+const PROBE_TIMEOUT_MS: u64 = 10;
+
+// Default time an I2cDevice transaction may take, clock stretching included.
+const TRANSACTION_TIMEOUT_MS: u64 = 100;
+
+/// Probes every address in `FIRST_ADDRESS..=LAST_ADDRESS` and returns the
+/// ones a device ACKed.
+///
+/// Each address is sent with an empty write, which devices ACK without
+/// changing state.
+pub fn scan_i2c(driver: &mut I2cDriver) -> Vec<u8> {
+    let timeout = TickType::new_millis(PROBE_TIMEOUT_MS).ticks();
+
+    (FIRST_ADDRESS..=LAST_ADDRESS)
+        .filter(|&addr| driver.write(addr, &[], timeout).is_ok())
+        .collect()
+}
+
+/// A device at a fixed 7-bit address, accessed through 8-bit registers.
+///
+/// `driver` can be an owned `I2cDriver` or a `&mut` one, so several devices
+/// can take turns on the same bus.
+pub struct I2cDevice<'d, D>
+where
+    D: BorrowMut<I2cDriver<'d>>,
+{
+    driver: D,
+    address: u8,
+    timeout: TickType_t,
+    _driver: PhantomData<I2cDriver<'d>>,
+}
+
+impl<'d, D> I2cDevice<'d, D>
+where
+    D: BorrowMut<I2cDriver<'d>>,
+{
+    /// Fails with ESP_ERR_INVALID_ARG if `address` doesn't fit in 7 bits.
+    pub fn new(driver: D, address: u8) -> Result<Self, EspError> {
+        if address > 0x7f {
+            return Err(EspError::from_infallible::<ESP_ERR_INVALID_ARG>());
+        }
+
+        Ok(Self {
+            driver,
+            address,
+            timeout: TickType::new_millis(TRANSACTION_TIMEOUT_MS).ticks(),
+            _driver: PhantomData,
+        })
+    }
+
+    /// Changes how long a transaction may take before failing with
+    /// ESP_ERR_TIMEOUT.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = TickType::from(timeout).ticks();
+    }
+
+    /// 7-bit address of the device.
+    pub fn address(&self) -> u8 {
+        self.address
+    }
+
+    /// Reads the register at `reg`.
+    pub fn read_reg(&mut self, reg: u8) -> Result<u8, EspError> {
+        let mut val = [0u8];
+        self.read_regs(reg, &mut val)?;
+        Ok(val[0])
+    }
+
+    /// Reads `buf.len()` consecutive registers starting at `reg`, relying on
+    /// the device auto-incrementing its register pointer.
+    ///
+    /// The register is written and read back in one transaction with a
+    /// repeated start in between, so no other master can change the
+    /// register pointer in the meantime.
+    pub fn read_regs(&mut self, reg: u8, buf: &mut [u8]) -> Result<(), EspError> {
+        let (address, timeout) = (self.address, self.timeout);
+        self.driver
+            .borrow_mut()
+            .write_read(address, &[reg], buf, timeout)
+    }
+
+    /// Writes `val` to the register at `reg`.
+    pub fn write_reg(&mut self, reg: u8, val: u8) -> Result<(), EspError> {
+        let (address, timeout) = (self.address, self.timeout);
+        self.driver
+            .borrow_mut()
+            .write(address, &[reg, val], timeout)
+    }
+
+    /// Writes `bytes` as they are, for devices not organized in 8-bit
+    /// registers. An empty write only checks that the device ACKs.
+    pub fn write(&mut self, bytes: &[u8]) -> Result<(), EspError> {
+        let (address, timeout) = (self.address, self.timeout);
+        self.driver.borrow_mut().write(address, bytes, timeout)
+    }
+
+    /// Writes `bytes` then reads `buf.len()` bytes, with a repeated start
+    /// in between like read_regs().
+    pub fn write_read(&mut self, bytes: &[u8], buf: &mut [u8]) -> Result<(), EspError> {
+        let (address, timeout) = (self.address, self.timeout);
+        self.driver
+            .borrow_mut()
+            .write_read(address, bytes, buf, timeout)
+    }
+
+    /// Gives the driver back.
+    pub fn release(self) -> D {
+        self.driver
+    }
+}
@@ -0,0 +1,324 @@
+// NEC infrared remote codes, sent and received with the RMT peripheral.
+//
+// An NEC frame is a 9 ms mark (carrier on) and a 4.5 ms space, then 32
+// bits LSB first: the address, its complement, the command and its
+// complement. Every bit is a 562.5 µs mark followed by a 562.5 µs space
+// for a 0 or a 1687.5 µs one for a 1, and a last mark ends the frame. A
+// held button sends a repeat code every 108 ms instead of the frame again,
+// a 9 ms mark, a 2.25 ms space and the final mark.
+//
+// The transmitter lets the RMT modulate the marks onto the 38 kHz carrier.
+// The receiver expects a demodulating module like the TSOP38238 on its
+// pin, which pulls its output low during marks. The RMT records the pulses
+// until the line stays idle for longer than any space, and a thread
+// decodes them, within TOLERANCE_PERCENT of each timing.
+
+use core::time::Duration;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Instant,
+};
+
+use esp_idf_svc::{
+    hal::{
+        delay::TickType,
+        gpio::{InputPin, OutputPin},
+        peripheral::Peripheral,
+        rmt::{
+            config::{CarrierConfig, ReceiveConfig, TransmitConfig},
+            PinState, Pulse, PulseTicks, Receive, RmtChannel, RxRmtDriver, TxRmtDriver,
+            VariableLengthSignal,
+        },
+    },
+    sys::EspError,
+};
+
+/// How far off the NEC timings a received pulse may be, in percent.
+pub const TOLERANCE_PERCENT: u32 = 25;
+
+/// Time between the start of two frames of a held button, send_repeat()
+/// is to be called at this pace after send().
+pub const REPEAT_PERIOD: Duration = Duration::from_millis(108);
+
+// NEC timings, in µs.
+const LEADER_MARK_US: u16 = 9000;
+const LEADER_SPACE_US: u16 = 4500;
+const REPEAT_SPACE_US: u16 = 2250;
+const BIT_MARK_US: u16 = 562;
+const ZERO_SPACE_US: u16 = 562;
+const ONE_SPACE_US: u16 = 1687;
+
+// A 1 MHz RMT clock, the ticks are µs.
+const CLOCK_DIVIDER: u8 = 80;
+
+// A frame is over once the line is idle for this long, more than the
+// longest space but less than the ~40 ms gap before a repeat code.
+const IDLE_THRESHOLD_US: u16 = 12_000;
+
+// Pulse pairs the RMT may record for one frame: the leader, 32 bits and
+// the final mark, plus room for glitches.
+const MAX_PAIRS: usize = 64;
+
+// Recorded frames the RMT ring buffer can hold before the thread gets
+// them.
+const RING_BUFFER_PAIRS: usize = 4 * MAX_PAIRS;
+
+// A repeat code received longer than this after the previous frame has
+// nothing to repeat, the first one of a press got lost.
+const REPEAT_TIMEOUT: Duration = Duration::from_millis(200);
+
+// How long the receiving thread waits for a frame before checking whether
+// it should stop.
+const RECEIVE_POLL_MS: u64 = 100;
+
+/// The address and command bytes of an NEC frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NecCode {
+    pub address: u8,
+    pub command: u8,
+}
+
+/// What an IrReceiver decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NecFrame {
+    pub code: NecCode,
+    /// The button is still held: a repeat code came, `code` is the frame
+    /// it repeats.
+    pub repeat: bool,
+}
+
+// One frame, as decoded from the pulses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Decoded {
+    Code(NecCode),
+    Repeat,
+}
+
+/// Sends NEC codes from an IR LED on an output pin.
+pub struct IrTransmitter<'d> {
+    tx: TxRmtDriver<'d>,
+}
+
+impl<'d> IrTransmitter<'d> {
+    /// The LED on `pin`, usually through a transistor, lights with the pin
+    /// high.
+    pub fn new<C: RmtChannel>(
+        channel: impl Peripheral<P = C> + 'd,
+        pin: impl Peripheral<P = impl OutputPin> + 'd,
+    ) -> Result<Self, EspError> {
+        // 38 kHz at a 33% duty cycle, what NEC receivers are tuned for.
+        let config = TransmitConfig::new()
+            .clock_divider(CLOCK_DIVIDER)
+            .carrier(Some(CarrierConfig::new()))
+            .idle(Some(PinState::Low));
+
+        Ok(Self {
+            tx: TxRmtDriver::new(channel, pin, &config)?,
+        })
+    }
+
+    /// Sends the frame of `address` and `command`, blocking for the ~68 ms
+    /// it takes.
+    pub fn send(&mut self, address: u8, command: u8) -> Result<(), EspError> {
+        let data = u32::from_le_bytes([address, !address, command, !command]);
+
+        // The leader, a mark and a space per bit, and the final mark.
+        let mut signal = VariableLengthSignal::with_capacity(2 + 2 * 32 + 1);
+        signal.push(&[mark(LEADER_MARK_US)?, space(LEADER_SPACE_US)?])?;
+        for bit in 0..32 {
+            let space_us = if data & (1 << bit) != 0 {
+                ONE_SPACE_US
+            } else {
+                ZERO_SPACE_US
+            };
+            signal.push(&[mark(BIT_MARK_US)?, space(space_us)?])?;
+        }
+        signal.push(&[mark(BIT_MARK_US)?])?;
+
+        self.tx.start_blocking(&signal)
+    }
+
+    /// Sends a repeat code, telling the receiver the button of the last
+    /// frame is still held. Send them REPEAT_PERIOD apart.
+    pub fn send_repeat(&mut self) -> Result<(), EspError> {
+        let mut signal = VariableLengthSignal::with_capacity(3);
+        signal.push(&[
+            mark(LEADER_MARK_US)?,
+            space(REPEAT_SPACE_US)?,
+            mark(BIT_MARK_US)?,
+        ])?;
+
+        self.tx.start_blocking(&signal)
+    }
+
+    /// Sends `code`, see send().
+    pub fn send_code(&mut self, code: NecCode) -> Result<(), EspError> {
+        self.send(code.address, code.command)
+    }
+}
+
+/// Receives NEC codes from a demodulating IR receiver module.
+///
+/// The RMT channel is handed to a thread that decodes what it records and
+/// runs the callback with each frame, repeat codes included. The thread
+/// stops once the IrReceiver is dropped.
+pub struct IrReceiver {
+    running: Arc<AtomicBool>,
+    receiver: Option<JoinHandle<()>>,
+}
+
+impl IrReceiver {
+    /// Listens on `pin` and runs `callback` with every frame decoded.
+    ///
+    /// Pulse trains that aren't NEC frames, or have a complement byte that
+    /// doesn't match, are dropped, and so are repeat codes without a frame
+    /// right before them.
+    pub fn new<C, F>(
+        channel: impl Peripheral<P = C> + 'static,
+        pin: impl Peripheral<P = impl InputPin> + 'static,
+        callback: F,
+    ) -> Result<Self, EspError>
+    where
+        C: RmtChannel,
+        F: FnMut(NecFrame) + Send + 'static,
+    {
+        let config = ReceiveConfig::new()
+            .clock_divider(CLOCK_DIVIDER)
+            .idle_threshold(IDLE_THRESHOLD_US);
+        let rx = RxRmtDriver::new(channel, pin, &config, RING_BUFFER_PAIRS)?;
+        rx.start()?;
+
+        let running = Arc::new(AtomicBool::new(true));
+        let receiver = {
+            let running = running.clone();
+            thread::spawn(move || receive(rx, &running, callback))
+        };
+
+        Ok(Self {
+            running,
+            receiver: Some(receiver),
+        })
+    }
+}
+
+impl Drop for IrReceiver {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(receiver) = self.receiver.take() {
+            let _ = receiver.join();
+        }
+    }
+}
+
+// The receiving thread, decodes frames until `running` is cleared.
+fn receive<F>(mut rx: RxRmtDriver<'static>, running: &AtomicBool, mut callback: F)
+where
+    F: FnMut(NecFrame),
+{
+    let timeout = TickType::new_millis(RECEIVE_POLL_MS).ticks();
+    // Room for the largest item the ring buffer can hold, so a receive
+    // doesn't overflow in the first place. On the heap, it's a few KB.
+    let mut pairs = vec![(Pulse::zero(), Pulse::zero()); RING_BUFFER_PAIRS];
+    // The last code and when it, or a repeat of it, was received.
+    let mut last: Option<(NecCode, Instant)> = None;
+
+    while running.load(Ordering::SeqCst) {
+        let len = match rx.receive(&mut pairs, timeout) {
+            Ok(Receive::Read(len)) => len,
+            Ok(Receive::Timeout) => continue,
+            // The driver keeps an item that didn't fit and hands it out
+            // again on the next receive, which would overflow forever. With
+            // enough room it's read and gets rid of it, too long to be a
+            // frame but decode() just rejects it.
+            Ok(Receive::Overflow(len)) => {
+                log::warn!("IR burst of {} pulse pairs, longer than a frame", len);
+                pairs.resize(len, (Pulse::zero(), Pulse::zero()));
+                continue;
+            }
+            Err(e) => {
+                log::error!("IR receive failed: {}", e);
+                continue;
+            }
+        };
+
+        let frame = match decode(&pairs[..len]) {
+            Some(Decoded::Code(code)) => NecFrame {
+                code,
+                repeat: false,
+            },
+            Some(Decoded::Repeat) => match last {
+                Some((code, at)) if at.elapsed() < REPEAT_TIMEOUT => {
+                    NecFrame { code, repeat: true }
+                }
+                _ => continue,
+            },
+            None => continue,
+        };
+
+        last = Some((frame.code, Instant::now()));
+        callback(frame);
+    }
+}
+
+// Decodes the pulses of one frame, None if it isn't a valid NEC frame.
+fn decode(pairs: &[(Pulse, Pulse)]) -> Option<Decoded> {
+    // Marks and spaces alternating, from the first mark (the line is low
+    // during marks) up to the idle level ending the frame.
+    let mut durations = pairs
+        .iter()
+        .flat_map(|(a, b)| [a, b])
+        .skip_while(|pulse| pulse.pin_state == PinState::High)
+        .map(|pulse| pulse.ticks.ticks())
+        .take_while(|&ticks| ticks != 0);
+
+    if !near(durations.next()?, LEADER_MARK_US) {
+        return None;
+    }
+    let space = durations.next()?;
+    if near(space, REPEAT_SPACE_US) {
+        return near(durations.next()?, BIT_MARK_US).then_some(Decoded::Repeat);
+    }
+    if !near(space, LEADER_SPACE_US) {
+        return None;
+    }
+
+    let mut data = 0u32;
+    for bit in 0..32 {
+        if !near(durations.next()?, BIT_MARK_US) {
+            return None;
+        }
+        match durations.next()? {
+            space if near(space, ONE_SPACE_US) => data |= 1 << bit,
+            space if near(space, ZERO_SPACE_US) => {}
+            _ => return None,
+        }
+    }
+    // No space after the final mark, the line just went idle.
+    if !near(durations.next()?, BIT_MARK_US) {
+        return None;
+    }
+
+    let [address, address_inv, command, command_inv] = data.to_le_bytes();
+    (address == !address_inv && command == !command_inv)
+        .then_some(Decoded::Code(NecCode { address, command }))
+}
+
+// Whether `measured` is within TOLERANCE_PERCENT of `expected`, both in µs.
+fn near(measured: u16, expected: u16) -> bool {
+    let slack = u32::from(expected) * TOLERANCE_PERCENT / 100;
+    u32::from(measured.abs_diff(expected)) <= slack
+}
+
+// A carrier burst of `us` µs.
+fn mark(us: u16) -> Result<Pulse, EspError> {
+    Ok(Pulse::new(PinState::High, PulseTicks::new(us)?))
+}
+
+// Nothing sent for `us` µs.
+fn space(us: u16) -> Result<Pulse, EspError> {
+    Ok(Pulse::new(PinState::Low, PulseTicks::new(us)?))
+}
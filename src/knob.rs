@@ -0,0 +1,164 @@
+// A rotary encoder with a push button, e.g. the switch of a KY-040 module:
+// turning moves its counter, a click selects the value, and a long press
+// can put the counter back where it started.
+//
+// The button is a SmartButton, which already keeps the release ending a
+// long press from counting as a click, so a reset never selects too. The
+// callbacks run from update(), on the thread owning the Knob, while the
+// encoder is polled elsewhere, usually from a timer ISR.
+
+use core::time::Duration;
+use std::sync::{mpsc::RecvTimeoutError, Arc};
+
+use embedded_hal::digital;
+use esp_idf_svc::{
+    hal::{gpio::InputPin, peripheral::Peripheral},
+    sys::EspError,
+};
+
+use crate::{
+    encoder::QuadratureDecoder,
+    gpio::{ClickEvent, SmartButton, SmartButtonConfig},
+};
+
+// Called with the encoder's position.
+type PositionCallback = Box<dyn FnMut(i32) + Send>;
+
+/// What a long press of a Knob's button does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResetPolicy {
+    /// Nothing, update() still returns the LongPress.
+    Never,
+    /// Held at least this long, the counter goes back to its initial value.
+    LongPress(Duration),
+}
+
+/// A rotary encoder and the push button on its shaft.
+pub struct Knob<A, B> {
+    encoder: Arc<QuadratureDecoder<A, B>>,
+    button: SmartButton,
+    initial: i32,
+    policy: ResetPolicy,
+    on_click: Option<PositionCallback>,
+    on_reset: Option<PositionCallback>,
+}
+
+impl<A, B> Knob<A, B>
+where
+    A: digital::InputPin,
+    B: digital::InputPin,
+{
+    /// Pairs `encoder` with a button on `pin`. The encoder's position now
+    /// is the initial value resets go back to.
+    ///
+    /// With ResetPolicy::LongPress its threshold replaces the long press
+    /// of `config`.
+    pub fn new(
+        encoder: Arc<QuadratureDecoder<A, B>>,
+        pin: impl Peripheral<P = impl InputPin> + 'static,
+        config: SmartButtonConfig,
+        policy: ResetPolicy,
+    ) -> Result<Self, EspError> {
+        let config = match policy {
+            ResetPolicy::Never => config,
+            ResetPolicy::LongPress(threshold) => config.long_press(threshold),
+        };
+
+        Ok(Self {
+            initial: encoder.position(),
+            encoder,
+            button: SmartButton::new(pin, config)?,
+            policy,
+            on_click: None,
+            on_reset: None,
+        })
+    }
+
+    /// Runs `callback` with the position on every click, replacing any
+    /// previously registered one. A double click runs it twice.
+    pub fn on_click<F>(&mut self, callback: F)
+    where
+        F: FnMut(i32) + Send + 'static,
+    {
+        self.on_click = Some(Box::new(callback));
+    }
+
+    /// Runs `callback` with the position after every reset, replacing any
+    /// previously registered one.
+    pub fn on_reset<F>(&mut self, callback: F)
+    where
+        F: FnMut(i32) + Send + 'static,
+    {
+        self.on_reset = Some(Box::new(callback));
+    }
+
+    /// Waits up to `timeout` for a button event and handles it, returning
+    /// it. Call it from the main loop, it also paces it.
+    pub fn update(&mut self, timeout: Duration) -> Option<ClickEvent> {
+        match self.button.events().recv_timeout(timeout) {
+            Ok(event) => {
+                self.handle(event);
+                Some(event)
+            }
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => {
+                log::error!("Knob button stopped");
+                None
+            }
+        }
+    }
+
+    /// Runs the callbacks `event` calls for.
+    pub fn handle(&mut self, event: ClickEvent) {
+        match event {
+            ClickEvent::Click => self.click(),
+            ClickEvent::DoubleClick => {
+                self.click();
+                self.click();
+            }
+            ClickEvent::LongPress => {
+                if let ResetPolicy::LongPress(_) = self.policy {
+                    self.reset();
+                }
+            }
+        }
+    }
+
+    /// Puts the counter back to its initial value, within the encoder's
+    /// bounds, and runs the on_reset() callback.
+    pub fn reset(&mut self) {
+        self.encoder.set_position(self.initial);
+        let position = self.encoder.position();
+        if let Some(callback) = &mut self.on_reset {
+            callback(position);
+        }
+    }
+
+    /// The value reset() goes back to.
+    pub fn initial(&self) -> i32 {
+        self.initial
+    }
+
+    pub fn set_initial(&mut self, initial: i32) {
+        self.initial = initial;
+    }
+
+    pub fn policy(&self) -> ResetPolicy {
+        self.policy
+    }
+
+    pub fn position(&self) -> i32 {
+        self.encoder.position()
+    }
+
+    pub fn encoder(&self) -> &Arc<QuadratureDecoder<A, B>> {
+        &self.encoder
+    }
+
+    fn click(&mut self) {
+        let position = self.encoder.position();
+        if let Some(callback) = &mut self.on_click {
+            callback(position);
+        }
+    }
+}
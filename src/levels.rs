@@ -0,0 +1,98 @@
+// Digital levels without a HAL, so this builds and is tested on the host.
+//
+// A Debouncer filters the levels polled from a contact, for the encoder
+// and gpio::DebouncedButton. A MockLevelSource stands in for a pin.
+
+use core::{
+    convert::Infallible,
+    sync::atomic::{AtomicBool, AtomicU8, Ordering},
+};
+
+/// An embedded-hal InputPin replaying a scripted sequence of levels, to
+/// drive the decoding logic without hardware.
+///
+/// Each read returns the next level of the script, and the last one again
+/// once the script ran out. An empty script reads low.
+pub struct MockLevelSource {
+    levels: Vec<bool>,
+    next: usize,
+}
+
+impl MockLevelSource {
+    /// `levels` are the reads in order, true being high.
+    pub fn new(levels: impl Into<Vec<bool>>) -> Self {
+        Self {
+            levels: levels.into(),
+            next: 0,
+        }
+    }
+
+    /// Number of reads so far.
+    pub fn reads(&self) -> usize {
+        self.next
+    }
+}
+
+impl embedded_hal::digital::ErrorType for MockLevelSource {
+    type Error = Infallible;
+}
+
+impl embedded_hal::digital::InputPin for MockLevelSource {
+    fn is_high(&mut self) -> Result<bool, Infallible> {
+        let read = self.next;
+        self.next += 1;
+        let Some(last) = self.levels.len().checked_sub(1) else {
+            return Ok(false);
+        };
+        Ok(self.levels[read.min(last)])
+    }
+
+    fn is_low(&mut self) -> Result<bool, Infallible> {
+        self.is_high().map(|high| !high)
+    }
+}
+
+// The accepted level of a pin, only changed once a new level was sampled
+// `samples` times in a row. Atomics so it can be updated through &self,
+// e.g. from an ISR.
+pub(crate) struct Debouncer {
+    stable: AtomicBool,
+    // Level being confirmed, and for how many samples so far.
+    candidate: AtomicBool,
+    count: AtomicU8,
+}
+
+impl Debouncer {
+    pub(crate) fn new(level: bool) -> Self {
+        Self {
+            stable: AtomicBool::new(level),
+            candidate: AtomicBool::new(level),
+            count: AtomicU8::new(0),
+        }
+    }
+
+    // Feeds a sample, returning the accepted level.
+    pub(crate) fn update(&self, level: bool, samples: u8) -> bool {
+        let stable = self.stable.load(Ordering::SeqCst);
+        if level == stable {
+            self.count.store(0, Ordering::SeqCst);
+            return stable;
+        }
+
+        // A bounce back and forth restarts the count.
+        let count = if self.candidate.swap(level, Ordering::SeqCst) == level {
+            self.count.load(Ordering::SeqCst).saturating_add(1)
+        } else {
+            1
+        };
+
+        if count >= samples {
+            self.stable.store(level, Ordering::SeqCst);
+            self.count.store(0, Ordering::SeqCst);
+            level
+        } else {
+            self.count.store(count, Ordering::SeqCst);
+            stable
+        }
+    }
+}
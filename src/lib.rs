@@ -0,0 +1,82 @@
+//! Reusable drivers and helpers for the buds ESP32 firmware.
+//!
+//! The examples in `examples/` are built on top of these modules.
+//! `use buds::prelude::*;` brings in the types most of them start with.
+//!
+//! Off the chip only the modules that don't need ESP IDF build, so their
+//! tests run on the host: encoder (without RotaryEncoder), levels and
+//! ringbuf.
+
+#[cfg(target_os = "espidf")]
+pub mod adc;
+#[cfg(target_os = "espidf")]
+pub mod bmp280;
+#[cfg(target_os = "espidf")]
+pub mod board;
+#[cfg(target_os = "espidf")]
+pub mod chip_sensors;
+#[cfg(target_os = "espidf")]
+pub mod dht;
+#[cfg(target_os = "espidf")]
+pub mod display;
+#[cfg(target_os = "espidf")]
+pub mod eeprom;
+pub mod encoder;
+#[cfg(target_os = "espidf")]
+pub mod error;
+#[cfg(target_os = "espidf")]
+pub mod gpio;
+#[cfg(target_os = "espidf")]
+pub mod http;
+#[cfg(target_os = "espidf")]
+pub mod i2c;
+#[cfg(target_os = "espidf")]
+pub mod ir;
+#[cfg(target_os = "espidf")]
+pub mod knob;
+pub mod levels;
+#[cfg(target_os = "espidf")]
+pub mod mdns;
+#[cfg(target_os = "espidf")]
+pub mod menu;
+#[cfg(target_os = "espidf")]
+pub mod mqtt;
+#[cfg(target_os = "espidf")]
+pub mod neopixel;
+#[cfg(target_os = "espidf")]
+pub mod ota;
+// target_os first, host builds don't know the chip cfgs.
+#[cfg(target_os = "espidf")]
+#[cfg(any(esp32, esp32s2, esp32s3))]
+pub mod pcnt;
+#[cfg(target_os = "espidf")]
+pub mod power;
+#[cfg(target_os = "espidf")]
+pub mod prelude;
+#[cfg(target_os = "espidf")]
+pub mod provision;
+#[cfg(target_os = "espidf")]
+pub mod pwm;
+pub mod ringbuf;
+#[cfg(target_os = "espidf")]
+pub mod scheduler;
+#[cfg(target_os = "espidf")]
+pub mod status_led;
+#[cfg(target_os = "espidf")]
+pub mod stopwatch;
+#[cfg(target_os = "espidf")]
+pub mod storage;
+#[cfg(target_os = "espidf")]
+pub mod thermistor;
+#[cfg(target_os = "espidf")]
+pub mod throttle;
+#[cfg(target_os = "espidf")]
+pub mod timer;
+#[cfg(target_os = "espidf")]
+pub mod uart;
+#[cfg(target_os = "espidf")]
+pub mod ultrasonic;
+#[cfg(target_os = "espidf")]
+pub mod watchdog;
+#[cfg(target_os = "espidf")]
+pub mod wifi;
@@ -0,0 +1,27 @@
+// Advertises the device on the local network as `<hostname>.local`.
+//
+// Needs the `espressif/mdns` component, which is pulled in through the
+// `extra_components` section of Cargo.toml on ESP IDF 5.
+
+use esp_idf_svc::{mdns::EspMdns, sys::EspError};
+
+/// Port advertised for the HTTP service by `add_http_service`.
+pub const HTTP_PORT: u16 = 80;
+
+/// Starts the mDNS responder so the device answers to `<hostname>.local`.
+///
+/// The responder stops when the returned `EspMdns` is dropped, so keep it
+/// alive for as long as the device should stay discoverable.
+pub fn start_mdns(hostname: &str, instance_name: &str) -> Result<EspMdns, EspError> {
+    let mut mdns = EspMdns::take()?;
+    mdns.set_hostname(hostname)?;
+    mdns.set_instance_name(instance_name)?;
+
+    Ok(mdns)
+}
+
+/// Registers an `_http._tcp` service on `port` so browsers and service
+/// discovery tools can find the device's web server.
+pub fn add_http_service(mdns: &mut EspMdns, port: u16) -> Result<(), EspError> {
+    mdns.add_service(None, "_http", "_tcp", port, &[])
+}
@@ -0,0 +1,126 @@
+// A list of entries navigated with a rotary encoder and a push button.
+//
+// Each detent the knob lands on moves the selection one entry, wrapping
+// around at both ends, and a click selects the entry. The detent callback
+// runs from wherever the encoder is polled, usually an ISR, so it only
+// adds up the detents in an atomic. The menu applies them on update(),
+// from the thread owning it, where the selection callback can do anything.
+
+use core::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Arc;
+
+use embedded_hal::digital;
+use esp_idf_svc::sys::{EspError, ESP_ERR_INVALID_ARG};
+
+use crate::{encoder::QuadratureDecoder, gpio::ClickEvent};
+
+// Called with the entry selected.
+type SelectCallback<T> = Box<dyn FnMut(&T) + Send>;
+
+/// A selection among `items`, moved by an encoder's detents and selected by
+/// clicks.
+pub struct Menu<T> {
+    items: Vec<T>,
+    selected: usize,
+    // Detents landed on since the last update(), clockwise positive.
+    pending: Arc<AtomicI32>,
+    on_select: Option<SelectCallback<T>>,
+}
+
+impl<T> Menu<T> {
+    /// A menu with the first of `items` selected.
+    ///
+    /// Fails with ESP_ERR_INVALID_ARG if `items` is empty.
+    pub fn new(items: Vec<T>) -> Result<Self, EspError> {
+        if items.is_empty() {
+            return Err(EspError::from_infallible::<ESP_ERR_INVALID_ARG>());
+        }
+
+        Ok(Self {
+            items,
+            selected: 0,
+            pending: Arc::new(AtomicI32::new(0)),
+            on_select: None,
+        })
+    }
+
+    /// Moves the selection with the detents of `encoder`, replacing its
+    /// on_detent() callback: one entry per detent, forward for clockwise.
+    ///
+    /// The moves are applied on update(). Call it before the encoder moves
+    /// to the ISR polling it.
+    pub fn attach<A, B>(&self, encoder: &mut QuadratureDecoder<A, B>)
+    where
+        A: digital::InputPin,
+        B: digital::InputPin,
+    {
+        let pending = self.pending.clone();
+        let mut last = encoder.position();
+        encoder.on_detent(move |position| {
+            pending.fetch_add(position.wrapping_sub(last).signum(), Ordering::SeqCst);
+            last = position;
+        });
+    }
+
+    /// Runs `callback` with the entry on every select(), replacing any
+    /// previously registered one.
+    pub fn on_select<F>(&mut self, callback: F)
+    where
+        F: FnMut(&T) + Send + 'static,
+    {
+        self.on_select = Some(Box::new(callback));
+    }
+
+    /// Applies the detents landed on since the last call, returning whether
+    /// the selection moved. Call it from the main loop.
+    pub fn update(&mut self) -> bool {
+        let moves = self.pending.swap(0, Ordering::SeqCst);
+        let before = self.selected;
+        self.move_by(moves);
+        self.selected != before
+    }
+
+    /// Selects the current entry on a Click, other events are ignored.
+    pub fn handle_click(&mut self, event: ClickEvent) {
+        if event == ClickEvent::Click {
+            self.select();
+        }
+    }
+
+    /// Runs the on_select() callback with the current entry.
+    pub fn select(&mut self) {
+        if let Some(callback) = &mut self.on_select {
+            callback(&self.items[self.selected]);
+        }
+    }
+
+    /// Moves to the next entry, the first one after the last.
+    pub fn next(&mut self) {
+        self.move_by(1);
+    }
+
+    /// Moves to the previous entry, the last one before the first.
+    pub fn previous(&mut self) {
+        self.move_by(-1);
+    }
+
+    /// The entry selected.
+    pub fn current(&self) -> &T {
+        &self.items[self.selected]
+    }
+
+    /// Index of the entry selected.
+    pub fn index(&self) -> usize {
+        self.selected
+    }
+
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+
+    fn move_by(&mut self, moves: i32) {
+        let len = self.items.len() as i64;
+        let selected = (self.selected as i64 + i64::from(moves)).rem_euclid(len);
+        self.selected = selected as usize;
+    }
+}
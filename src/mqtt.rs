@@ -0,0 +1,185 @@
+// Publishing to an MQTT broker, over a connection that comes back by itself.
+//
+// ESP IDF's MQTT client runs its own task: it connects, keeps the session
+// alive, and reconnects after reconnect_delay() whenever the broker or the
+// network drops. Messages are queued in its outbox and sent from that task,
+// so publishing doesn't wait for the broker. QoS 1 and 2 messages stay in
+// the outbox across a reconnection until the broker acknowledges them, or
+// CONFIG_MQTT_OUTBOX_EXPIRED_TIMEOUT_MS (30s by default) passes. QoS 0 ones
+// are only worth sending while connected, and are refused otherwise.
+
+use core::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+use std::{sync::Arc, thread, time::Instant};
+
+use esp_idf_svc::{
+    mqtt::client::{EspMqttClient, EspMqttEvent, EventPayload, MessageId, MqttClientConfiguration},
+    sys::{EspError, ESP_ERR_INVALID_STATE, ESP_ERR_TIMEOUT},
+};
+
+pub use esp_idf_svc::mqtt::client::QoS;
+
+// How often wait_connected() checks the connection.
+const CONNECT_POLL: Duration = Duration::from_millis(100);
+
+/// Settings of an MqttPublisher.
+///
+/// Defaults to a client id picked by ESP IDF from the MAC address, no
+/// credentials, QoS 1 without retain, and a 5 second reconnect delay.
+#[derive(Debug, Clone, Copy)]
+pub struct MqttConfig<'a> {
+    client_id: Option<&'a str>,
+    credentials: Option<(&'a str, &'a str)>,
+    qos: QoS,
+    retain: bool,
+    reconnect_delay: Duration,
+}
+
+impl<'a> MqttConfig<'a> {
+    pub fn new() -> Self {
+        Self {
+            client_id: None,
+            credentials: None,
+            qos: QoS::AtLeastOnce,
+            retain: false,
+            reconnect_delay: Duration::from_secs(5),
+        }
+    }
+
+    /// Identifies this client to the broker, which drops any other
+    /// connection using the same id.
+    pub fn client_id(mut self, client_id: &'a str) -> Self {
+        self.client_id = Some(client_id);
+        self
+    }
+
+    /// User name and password to log in with.
+    pub fn credentials(mut self, username: &'a str, password: &'a str) -> Self {
+        self.credentials = Some((username, password));
+        self
+    }
+
+    /// QoS of publish(): AtMostOnce can lose messages but costs the least,
+    /// AtLeastOnce may deliver one twice, ExactlyOnce takes two round trips.
+    pub fn qos(mut self, qos: QoS) -> Self {
+        self.qos = qos;
+        self
+    }
+
+    /// Whether the broker keeps the last message of a topic for clients
+    /// subscribing later.
+    pub fn retain(mut self, retain: bool) -> Self {
+        self.retain = retain;
+        self
+    }
+
+    /// How long to wait before reconnecting after the connection dropped.
+    pub fn reconnect_delay(mut self, reconnect_delay: Duration) -> Self {
+        self.reconnect_delay = reconnect_delay;
+        self
+    }
+}
+
+impl Default for MqttConfig<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A connection to an MQTT broker to publish messages on.
+pub struct MqttPublisher {
+    client: EspMqttClient<'static>,
+    // Updated from the client's task on every (dis)connection.
+    connected: Arc<AtomicBool>,
+    qos: QoS,
+    retain: bool,
+}
+
+impl MqttPublisher {
+    /// Starts connecting to the broker at `url`, e.g.
+    /// `mqtt://192.168.1.10:1883`, and returns without waiting for it.
+    ///
+    /// Getting there, and back after a disconnection, is logged.
+    pub fn new(url: &str, config: MqttConfig) -> Result<Self, EspError> {
+        let connected = Arc::new(AtomicBool::new(false));
+
+        let event_connected = connected.clone();
+        let on_event = move |event: EspMqttEvent<'_>| {
+            match event.payload() {
+                EventPayload::Connected(_) => {
+                    event_connected.store(true, Ordering::SeqCst);
+                    log::info!("MQTT connected");
+                }
+                EventPayload::Disconnected => {
+                    // Only logged once, not on every failed reconnection.
+                    if event_connected.swap(false, Ordering::SeqCst) {
+                        log::warn!("MQTT disconnected, reconnecting");
+                    }
+                }
+                EventPayload::Error(e) => log::warn!("MQTT error: {}", e),
+                _ => {}
+            }
+        };
+
+        let mqtt_config = MqttClientConfiguration {
+            client_id: config.client_id,
+            username: config.credentials.map(|(username, _)| username),
+            password: config.credentials.map(|(_, password)| password),
+            reconnect_timeout: Some(config.reconnect_delay),
+            ..Default::default()
+        };
+        let client = EspMqttClient::new_cb(url, &mqtt_config, on_event)?;
+
+        Ok(Self {
+            client,
+            connected,
+            qos: config.qos,
+            retain: config.retain,
+        })
+    }
+
+    /// Whether the broker is connected right now.
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::SeqCst)
+    }
+
+    /// Waits up to `timeout` for the broker to be connected, failing with
+    /// ESP_ERR_TIMEOUT if it doesn't.
+    pub fn wait_connected(&self, timeout: Duration) -> Result<(), EspError> {
+        let start = Instant::now();
+        while !self.is_connected() {
+            if start.elapsed() >= timeout {
+                return Err(EspError::from_infallible::<ESP_ERR_TIMEOUT>());
+            }
+            thread::sleep(CONNECT_POLL);
+        }
+        Ok(())
+    }
+
+    /// Queues `payload` for `topic` with the configured QoS and retain flag.
+    ///
+    /// Returns right away. Fails with ESP_ERR_INVALID_STATE for QoS 0 while
+    /// disconnected, see publish_with().
+    pub fn publish(&mut self, topic: &str, payload: &[u8]) -> Result<MessageId, EspError> {
+        self.publish_with(topic, payload, self.qos, self.retain)
+    }
+
+    /// Like publish(), with its own QoS and retain flag.
+    ///
+    /// A QoS 1 or 2 message published while disconnected is sent once the
+    /// connection is back.
+    pub fn publish_with(
+        &mut self,
+        topic: &str,
+        payload: &[u8],
+        qos: QoS,
+        retain: bool,
+    ) -> Result<MessageId, EspError> {
+        if qos == QoS::AtMostOnce && !self.is_connected() {
+            return Err(EspError::from_infallible::<ESP_ERR_INVALID_STATE>());
+        }
+        self.client.enqueue(topic, qos, retain, payload)
+    }
+}
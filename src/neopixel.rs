@@ -0,0 +1,126 @@
+// Driver for WS2812 ("NeoPixel") RGB LED strips, clocked out by the RMT
+// peripheral.
+//
+// Each bit is an 800 kHz (1.25µs) period, high for 0.4µs for a 0 or 0.8µs
+// for a 1. The pixels take 24 bits each, green first, and latch what they
+// received once the line stays low for a while.
+
+use std::time::Duration;
+
+use esp_idf_svc::{
+    hal::{
+        delay::Ets,
+        gpio::OutputPin,
+        peripheral::Peripheral,
+        rmt::{
+            config::TransmitConfig, PinState, Pulse, RmtChannel, TxRmtDriver, VariableLengthSignal,
+        },
+    },
+    sys::{EspError, ESP_ERR_INVALID_ARG},
+};
+
+// Bit timings from the WS2812B datasheet.
+const T0H: Duration = Duration::from_nanos(400);
+const T0L: Duration = Duration::from_nanos(850);
+const T1H: Duration = Duration::from_nanos(800);
+const T1L: Duration = Duration::from_nanos(450);
+
+// Low time after which the pixels latch the new colors, newer WS2812B
+// revisions need more than the 50µs of the original datasheet.
+const RESET_US: u32 = 300;
+
+/// A strip of WS2812 pixels on one data pin.
+///
+/// set_pixel() only updates a buffer, the strip changes on flush().
+pub struct NeoPixel<'d> {
+    tx: TxRmtDriver<'d>,
+    // Kept in the GRB order the pixels expect.
+    pixels: Vec<[u8; 3]>,
+    zero: [Pulse; 2],
+    one: [Pulse; 2],
+}
+
+impl<'d> NeoPixel<'d> {
+    /// Drives `len` pixels from `pin`, all starting off.
+    pub fn new<C: RmtChannel>(
+        channel: impl Peripheral<P = C> + 'd,
+        pin: impl Peripheral<P = impl OutputPin> + 'd,
+        len: usize,
+    ) -> Result<Self, EspError> {
+        // No divider, the 80 MHz ticks are precise enough for the timings.
+        let config = TransmitConfig::new().clock_divider(1);
+        let tx = TxRmtDriver::new(channel, pin, &config)?;
+
+        let ticks_hz = tx.counter_clock()?;
+        let pulse = |state, duration| Pulse::new_with_duration(ticks_hz, state, &duration);
+
+        Ok(Self {
+            zero: [pulse(PinState::High, T0H)?, pulse(PinState::Low, T0L)?],
+            one: [pulse(PinState::High, T1H)?, pulse(PinState::Low, T1L)?],
+            pixels: vec![[0; 3]; len],
+            tx,
+        })
+    }
+
+    /// Number of pixels on the strip.
+    pub fn len(&self) -> usize {
+        self.pixels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pixels.is_empty()
+    }
+
+    /// Sets the color of pixel `index`, shown on the next flush().
+    ///
+    /// Fails with ESP_ERR_INVALID_ARG if `index` is past the end of the strip.
+    pub fn set_pixel(&mut self, index: usize, r: u8, g: u8, b: u8) -> Result<(), EspError> {
+        let pixel = self
+            .pixels
+            .get_mut(index)
+            .ok_or_else(EspError::from_infallible::<ESP_ERR_INVALID_ARG>)?;
+        *pixel = [g, r, b];
+        Ok(())
+    }
+
+    /// Sets every pixel to the same color, shown on the next flush().
+    pub fn fill(&mut self, r: u8, g: u8, b: u8) {
+        self.pixels.fill([g, r, b]);
+    }
+
+    /// Sends the colors to the strip, blocking until the pixels latched them.
+    pub fn flush(&mut self) -> Result<(), EspError> {
+        let mut signal = VariableLengthSignal::with_capacity(self.pixels.len() * 24 * 2);
+        for byte in self.pixels.iter().flatten() {
+            for bit in (0..8).rev() {
+                let pulses = if byte & (1 << bit) != 0 {
+                    &self.one
+                } else {
+                    &self.zero
+                };
+                signal.push(pulses)?;
+            }
+        }
+
+        self.tx.start_blocking(&signal)?;
+        Ets::delay_us(RESET_US);
+        Ok(())
+    }
+}
+
+/// Color at `position` (0–255) around the color wheel, going from red
+/// through blue and green back to red, for rainbow effects.
+pub fn wheel(position: u8) -> (u8, u8, u8) {
+    let position = 255 - position;
+    match position {
+        0..=84 => (255 - position * 3, 0, position * 3),
+        85..=169 => {
+            let position = position - 85;
+            (0, position * 3, 255 - position * 3)
+        }
+        _ => {
+            let position = position - 170;
+            (position * 3, 255 - position * 3, 0)
+        }
+    }
+}
@@ -0,0 +1,77 @@
+// Firmware updates downloaded over HTTP(S) into the OTA partitions.
+//
+// The partition table needs two OTA app slots, e.g. with
+// CONFIG_PARTITION_TABLE_TWO_OTA. The new image goes into the slot that
+// isn't running, and the bootloader switches to it on the next restart.
+
+use esp_idf_svc::{
+    ota::EspOta,
+    sys::{EspError, ESP_ERR_INVALID_SIZE},
+};
+
+use crate::http::{get, DEFAULT_TIMEOUT};
+
+// Size of the chunks the image is downloaded and written in.
+const WRITE_CHUNK: usize = 1024;
+
+// Progress is logged every PROGRESS_PERCENT of the image, or every
+// PROGRESS_BYTES if the server didn't send its size.
+const PROGRESS_PERCENT: usize = 10;
+const PROGRESS_BYTES: usize = 64 * 1024;
+
+/// Downloads the firmware image at `url` into the next OTA slot, and sets
+/// it as the one to boot. Restart afterwards to run it.
+///
+/// ESP IDF checks the image once it's written and fails the update if it's
+/// not a valid app. If anything fails, including the download stopping
+/// short of its Content-Length, the update is aborted and the running
+/// firmware stays the one booted.
+pub fn ota_update(url: &str) -> Result<(), EspError> {
+    let mut connection = get(url, DEFAULT_TIMEOUT, &[])?;
+    let total = connection
+        .header("Content-Length")
+        .and_then(|len| len.parse::<usize>().ok());
+
+    let mut ota = EspOta::new()?;
+    // Dropped on any error below, which aborts the update.
+    let mut update = ota.initiate_update()?;
+
+    log::info!("Downloading firmware from {}", url);
+    let mut chunk = [0u8; WRITE_CHUNK];
+    let mut written = 0;
+    loop {
+        let n = connection.read(&mut chunk)?;
+        if n == 0 {
+            break;
+        }
+        update.write(&chunk[..n])?;
+
+        let before = written;
+        written += n;
+        match total {
+            Some(total) if total > 0 => {
+                let percent = written * 100 / total;
+                if percent / PROGRESS_PERCENT != before * 100 / total / PROGRESS_PERCENT {
+                    log::info!("Firmware update: {}% ({} bytes)", percent, written);
+                }
+            }
+            _ if written / PROGRESS_BYTES != before / PROGRESS_BYTES => {
+                log::info!("Firmware update: {} bytes", written);
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(total) = total.filter(|&total| written != total) {
+        log::error!("Download stopped after {} of {} bytes", written, total);
+        return Err(EspError::from_infallible::<ESP_ERR_INVALID_SIZE>());
+    }
+
+    update.complete()?;
+    log::info!(
+        "Firmware update of {} bytes done, restart to run it",
+        written
+    );
+
+    Ok(())
+}
@@ -0,0 +1,170 @@
+// Quadrature decoding in hardware, with the pulse counter (PCNT) of the
+// ESP32, ESP32-S2 and ESP32-S3. The ESP32-C3 has none, use a RotaryEncoder
+// there.
+//
+// Each of the unit's two channels counts the edges of one pin, up or down
+// depending on the level of the other, which makes four counts per gray
+// code cycle like the software decoder. The counter is only 16 bits: it
+// restarts from 0 on reaching ±PCNT_LIMIT, and the ISR of that event
+// carries the count over into an i32.
+
+use core::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Arc;
+
+use esp_idf_svc::{
+    hal::{
+        gpio::{AnyInputPin, InputPin},
+        pcnt::{
+            Pcnt, PcntChannel, PcntChannelConfig, PcntControlMode, PcntCountMode, PcntDriver,
+            PcntEvent, PcntEventType, PinIndex,
+        },
+        peripheral::Peripheral,
+    },
+    sys::EspError,
+};
+
+/// Count at which the hardware counter restarts from 0, and -PCNT_LIMIT.
+pub const PCNT_LIMIT: i16 = 10_000;
+
+// Glitch filter, in APB cycles (12.8 µs at the 1023 maximum).
+const FILTER_CYCLES: u16 = 1023;
+
+/// The hardware counter reached one of its limits and restarted from 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PcntLimit {
+    /// Counted up to PCNT_LIMIT.
+    Overflow,
+    /// Counted down to -PCNT_LIMIT.
+    Underflow,
+}
+
+/// A rotary encoder decoded by a PCNT unit, counting without any CPU time
+/// but an interrupt every PCNT_LIMIT steps.
+///
+/// Fast encoders, e.g. on motor shafts, that a polled RotaryEncoder would
+/// miss states of are fine. Counts the same way as a RotaryEncoder on the
+/// same pins, clockwise being positive.
+pub struct PcntEncoder<'d> {
+    unit: PcntDriver<'d>,
+    // Steps counted before the hardware counter last restarted.
+    carried: Arc<AtomicI32>,
+}
+
+impl<'d> PcntEncoder<'d> {
+    /// Starts counting from 0 at the current knob position.
+    pub fn new<PCNT: Pcnt>(
+        pcnt: impl Peripheral<P = PCNT> + 'd,
+        a: impl Peripheral<P = impl InputPin> + 'd,
+        b: impl Peripheral<P = impl InputPin> + 'd,
+    ) -> Result<Self, EspError> {
+        let mut unit = PcntDriver::new(
+            pcnt,
+            Some(a),
+            Some(b),
+            Option::<AnyInputPin>::None,
+            Option::<AnyInputPin>::None,
+        )?;
+
+        // Edges of A count up with B high, edges of B count up with A low,
+        // and the other way round otherwise.
+        unit.channel_config(
+            PcntChannel::Channel0,
+            PinIndex::Pin0,
+            PinIndex::Pin1,
+            &PcntChannelConfig {
+                lctrl_mode: PcntControlMode::Reverse,
+                hctrl_mode: PcntControlMode::Keep,
+                pos_mode: PcntCountMode::Increment,
+                neg_mode: PcntCountMode::Decrement,
+                counter_h_lim: PCNT_LIMIT,
+                counter_l_lim: -PCNT_LIMIT,
+            },
+        )?;
+        unit.channel_config(
+            PcntChannel::Channel1,
+            PinIndex::Pin1,
+            PinIndex::Pin0,
+            &PcntChannelConfig {
+                lctrl_mode: PcntControlMode::Reverse,
+                hctrl_mode: PcntControlMode::Keep,
+                pos_mode: PcntCountMode::Decrement,
+                neg_mode: PcntCountMode::Increment,
+                counter_h_lim: PCNT_LIMIT,
+                counter_l_lim: -PCNT_LIMIT,
+            },
+        )?;
+
+        // Contact bounce is much shorter than a step.
+        unit.set_filter_value(FILTER_CYCLES)?;
+        unit.filter_enable()?;
+
+        let mut encoder = Self {
+            unit,
+            carried: Arc::new(AtomicI32::new(0)),
+        };
+        encoder.subscribe(|_| {})?;
+        encoder.unit.event_enable(PcntEvent::HighLimit)?;
+        encoder.unit.event_enable(PcntEvent::LowLimit)?;
+        encoder.unit.counter_pause()?;
+        encoder.unit.counter_clear()?;
+        encoder.unit.counter_resume()?;
+
+        Ok(encoder)
+    }
+
+    /// Steps counted so far, clockwise being positive.
+    ///
+    /// Wraps around past ±2147483647 steps.
+    pub fn count(&self) -> Result<i32, EspError> {
+        let counter = self.unit.get_counter_value()?;
+        Ok(self
+            .carried
+            .load(Ordering::SeqCst)
+            .wrapping_add(i32::from(counter)))
+    }
+
+    /// Starts counting from 0 again.
+    pub fn reset(&mut self) -> Result<(), EspError> {
+        self.unit.counter_clear()?;
+        self.carried.store(0, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Runs `callback` from the PCNT ISR each time the hardware counter
+    /// reaches a limit, replacing any previously registered one.
+    ///
+    /// count() keeps counting past the limits by itself, this is for
+    /// watching how far the encoder went, e.g. a revolution counter with
+    /// PCNT_LIMIT steps per turn. The callback must not block, allocate or
+    /// log.
+    pub fn on_limit<F>(&mut self, callback: F) -> Result<(), EspError>
+    where
+        F: FnMut(PcntLimit) + Send + 'static,
+    {
+        self.subscribe(callback)
+    }
+
+    // Replaces the ISR, which carries the count over and then calls
+    // `callback`.
+    fn subscribe<F>(&mut self, mut callback: F) -> Result<(), EspError>
+    where
+        F: FnMut(PcntLimit) + Send + 'static,
+    {
+        let carried = self.carried.clone();
+        let on_event = move |status| {
+            let status = PcntEventType::from_repr_truncated(status);
+            if status.contains(PcntEvent::HighLimit) {
+                carried.fetch_add(i32::from(PCNT_LIMIT), Ordering::SeqCst);
+                callback(PcntLimit::Overflow);
+            }
+            if status.contains(PcntEvent::LowLimit) {
+                carried.fetch_sub(i32::from(PCNT_LIMIT), Ordering::SeqCst);
+                callback(PcntLimit::Underflow);
+            }
+        };
+
+        // SAFETY: the closure only touches atomics and what `callback`
+        // owns, it's 'static so nothing it captures can go away first.
+        unsafe { self.unit.subscribe(on_event) }
+    }
+}
@@ -0,0 +1,90 @@
+// Deep sleep between readings for battery powered projects.
+//
+// In deep sleep everything but the RTC domain is powered down, waking up
+// goes through a full reboot. wake_cause() tells after boot whether that
+// reboot was a wake up, and from what.
+
+use std::time::Duration;
+
+use esp_idf_svc::{
+    hal::gpio::{Level, RTCPin},
+    sys::{
+        esp, esp_deep_sleep, esp_deep_sleep_start, esp_sleep_get_wakeup_cause,
+        esp_sleep_source_t_ESP_SLEEP_WAKEUP_EXT0, esp_sleep_source_t_ESP_SLEEP_WAKEUP_EXT1,
+        esp_sleep_source_t_ESP_SLEEP_WAKEUP_GPIO, esp_sleep_source_t_ESP_SLEEP_WAKEUP_TIMER,
+        esp_sleep_source_t_ESP_SLEEP_WAKEUP_UNDEFINED, EspError,
+    },
+};
+
+#[cfg(any(esp32, esp32s2, esp32s3))]
+use esp_idf_svc::sys::esp_sleep_enable_ext0_wakeup;
+#[cfg(not(any(esp32, esp32s2, esp32s3)))]
+use esp_idf_svc::sys::{
+    esp_deep_sleep_enable_gpio_wakeup, esp_deepsleep_gpio_wake_up_mode_t_ESP_GPIO_WAKEUP_GPIO_HIGH,
+    esp_deepsleep_gpio_wake_up_mode_t_ESP_GPIO_WAKEUP_GPIO_LOW,
+};
+
+/// What brought the chip out of deep sleep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WakeCause {
+    /// The deep_sleep_for() duration ran out.
+    Timer,
+    /// The deep_sleep_until_gpio() pin reached its level.
+    Gpio,
+    /// Not a wake up from deep sleep: power on, reset button or restart.
+    PowerOn,
+    /// Some other wake up source, with its esp_sleep_source_t.
+    Unknown(u32),
+}
+
+/// Why this boot happened, read with esp_sleep_get_wakeup_cause().
+pub fn wake_cause() -> WakeCause {
+    // SAFETY: esp_sleep_get_wakeup_cause() is an ESP32 ABI call.
+    let cause = unsafe { esp_sleep_get_wakeup_cause() };
+    match cause {
+        esp_sleep_source_t_ESP_SLEEP_WAKEUP_TIMER => WakeCause::Timer,
+        esp_sleep_source_t_ESP_SLEEP_WAKEUP_EXT0
+        | esp_sleep_source_t_ESP_SLEEP_WAKEUP_EXT1
+        | esp_sleep_source_t_ESP_SLEEP_WAKEUP_GPIO => WakeCause::Gpio,
+        esp_sleep_source_t_ESP_SLEEP_WAKEUP_UNDEFINED => WakeCause::PowerOn,
+        other => WakeCause::Unknown(other),
+    }
+}
+
+/// Powers down into deep sleep, to reboot once `dur` has passed.
+pub fn deep_sleep_for(dur: Duration) -> ! {
+    let us = u64::try_from(dur.as_micros()).unwrap_or(u64::MAX);
+    log::info!("Deep sleeping for {:?}", dur);
+
+    // SAFETY: esp_deep_sleep() is an ESP32 ABI call.
+    unsafe { esp_deep_sleep(us) }
+}
+
+/// Powers down into deep sleep, to reboot once `pin` is at `level`.
+///
+/// Only RTC capable pins can wake the chip, GPIO0 to GPIO5 on the
+/// ESP32-C3. Fails without sleeping if the pin can't be used.
+pub fn deep_sleep_until_gpio(pin: &impl RTCPin, level: Level) -> Result<(), EspError> {
+    enable_gpio_wakeup(pin, level)?;
+    log::info!("Deep sleeping until GPIO{} is {:?}", pin.pin(), level);
+
+    // SAFETY: esp_deep_sleep_start() is an ESP32 ABI call.
+    unsafe { esp_deep_sleep_start() }
+}
+
+#[cfg(any(esp32, esp32s2, esp32s3))]
+fn enable_gpio_wakeup(pin: &impl RTCPin, level: Level) -> Result<(), EspError> {
+    // SAFETY: esp_sleep_enable_ext0_wakeup() is an ESP32 ABI call.
+    esp!(unsafe { esp_sleep_enable_ext0_wakeup(pin.pin(), (level == Level::High) as i32) })
+}
+
+// These chips have no ext0, but can wake up from the GPIO controller.
+#[cfg(not(any(esp32, esp32s2, esp32s3)))]
+fn enable_gpio_wakeup(pin: &impl RTCPin, level: Level) -> Result<(), EspError> {
+    let mode = match level {
+        Level::High => esp_deepsleep_gpio_wake_up_mode_t_ESP_GPIO_WAKEUP_GPIO_HIGH,
+        Level::Low => esp_deepsleep_gpio_wake_up_mode_t_ESP_GPIO_WAKEUP_GPIO_LOW,
+    };
+    // SAFETY: esp_deep_sleep_enable_gpio_wakeup() is an ESP32 ABI call.
+    esp!(unsafe { esp_deep_sleep_enable_gpio_wakeup(1 << pin.pin(), mode) })
+}
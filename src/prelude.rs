@@ -0,0 +1,31 @@
+// The types most programs built on buds start with, for a single
+// `use buds::prelude::*;` instead of a long list of esp_idf_svc and buds
+// paths.
+//
+// Only what nearly every program touches is here: the peripherals and
+// pins, the event loop and NVS partition the wifi needs, the wifi
+// station, the hardware timers, the rotary encoder and the error types.
+// Everything else is imported from its module as before. Names generic
+// enough to clash are renamed, the encoder's Direction is EncoderDirection.
+//
+// Result isn't re-exported, so the glob doesn't shadow the std one. Use
+// buds::error::Result for the crate's.
+
+pub use esp_idf_svc::{
+    eventloop::EspSystemEventLoop,
+    hal::{
+        gpio::{InterruptType, Level, PinDriver, Pull},
+        peripherals::Peripherals,
+        units::{FromValueType, Hertz},
+    },
+    nvs::EspDefaultNvsPartition,
+    sys::EspError,
+    wifi::{BlockingWifi, ClientConfiguration, Configuration, EspWifi},
+};
+
+pub use crate::{
+    board::take_peripherals,
+    encoder::{Direction as EncoderDirection, EncoderConfig, RotaryEncoder},
+    error::{Error, EspResultExt},
+    timer::{divider_for_hz, HwTimer, TimerConfigBuilder, TimerError, TimerId},
+};
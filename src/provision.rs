@@ -0,0 +1,165 @@
+// First boot WiFi setup: the device opens an AP serving a credentials form,
+// stores what gets submitted in NVS and reboots to join that network.
+
+use std::{sync::mpsc, thread, time::Duration};
+
+use esp_idf_svc::{
+    hal::reset,
+    http::{
+        server::{Configuration as HttpConfiguration, EspHttpServer},
+        Method,
+    },
+    io::{EspIOError, Read, Write},
+    nvs::EspDefaultNvsPartition,
+    sys::{EspError, ESP_ERR_INVALID_ARG},
+    wifi::{AccessPointConfiguration, AuthMethod, ClientConfiguration, Configuration, EspWifi},
+};
+
+use crate::{
+    error::Result,
+    wifi::{store_credentials, stored_credentials, MIN_PASSWORD_LEN},
+};
+
+/// Channel the provisioning AP is started on.
+pub const AP_CHANNEL: u8 = 1;
+
+// Longest SSID and WPA2 passphrase WiFi allows.
+const MAX_SSID_LEN: usize = 32;
+const MAX_PASSWORD_LEN: usize = 64;
+
+// Plenty for both fields, even fully percent-encoded.
+const MAX_FORM_LEN: usize = 512;
+
+// Gives the browser time to receive the reply before we reboot.
+const RESTART_DELAY: Duration = Duration::from_secs(2);
+
+const FORM_HTML: &str = r#"<!DOCTYPE html>
+<html>
+<head><meta name="viewport" content="width=device-width, initial-scale=1"><title>buds setup</title></head>
+<body>
+<h1>WiFi setup</h1>
+<form method="post" action="/">
+<p><label>SSID <input name="ssid" maxlength="32" required></label></p>
+<p><label>Password <input name="password" type="password" maxlength="64"></label></p>
+<p><button type="submit">Save &amp; reboot</button></p>
+</form>
+</body>
+</html>"#;
+
+/// Lets the user enter the network to join from a browser.
+///
+/// Starts `wifi` in APSTA mode with an open AP named `ap_ssid` and serves a
+/// form on `/` (http://192.168.71.1 by default). Submitted credentials are
+/// saved with store_credentials() and the device reboots, to come back up
+/// as a station on that network.
+///
+/// Returns Ok right away without touching `wifi` when NVS already holds
+/// credentials, otherwise it only returns on error.
+pub fn provision(wifi: &mut EspWifi, nvs: &EspDefaultNvsPartition, ap_ssid: &str) -> Result<()> {
+    if stored_credentials(nvs)?.is_some() {
+        log::info!("WiFi credentials found in NVS, skipping provisioning");
+        return Ok(());
+    }
+
+    // APSTA, so the station side is already up once we reboot into it.
+    wifi.set_configuration(&Configuration::Mixed(
+        ClientConfiguration::default(),
+        AccessPointConfiguration {
+            ssid: ap_ssid
+                .try_into()
+                .map_err(|_| EspError::from_infallible::<ESP_ERR_INVALID_ARG>())?,
+            channel: AP_CHANNEL,
+            auth_method: AuthMethod::None,
+            ..Default::default()
+        },
+    ))?;
+    wifi.start()?;
+    log::info!("Provisioning AP '{}' started", ap_ssid);
+
+    let (saved_tx, saved_rx) = mpsc::channel();
+    let mut server = EspHttpServer::new(&HttpConfiguration::default()).map_err(|e| e.0)?;
+
+    server.fn_handler("/", Method::Get, |req| {
+        req.into_ok_response()?.write_all(FORM_HTML.as_bytes())
+    })?;
+
+    let nvs = nvs.clone();
+    server.fn_handler::<EspIOError, _>("/", Method::Post, move |mut req| {
+        let mut body = [0u8; MAX_FORM_LEN];
+        let mut len = 0;
+        // The body may arrive in several chunks.
+        while len < body.len() {
+            match req.read(&mut body[len..])? {
+                0 => break,
+                n => len += n,
+            }
+        }
+
+        let Some((ssid, password)) = parse_credentials(&body[..len]) else {
+            req.into_status_response(400)?
+                .write_all(b"Invalid SSID or password, go back and try again.")?;
+            return Ok(());
+        };
+
+        if let Err(e) = store_credentials(&nvs, &ssid, &password) {
+            log::error!("Failed to store credentials: {}", e);
+            req.into_status_response(500)?
+                .write_all(b"Couldn't save the credentials, try again.")?;
+            return Ok(());
+        }
+        log::info!("Stored credentials for '{}'", ssid);
+
+        req.into_ok_response()?
+            .write_all(format!("Saved, rebooting to join {}...", ssid).as_bytes())?;
+        saved_tx.send(()).ok();
+        Ok(())
+    })?;
+
+    // Blocks until the form was submitted, the server runs on its own task.
+    saved_rx.recv().ok();
+    thread::sleep(RESTART_DELAY);
+    drop(server);
+    reset::restart();
+    unreachable!("esp_restart() doesn't return");
+}
+
+// Extracts valid (ssid, password) fields from an url-encoded form body.
+fn parse_credentials(body: &[u8]) -> Option<(String, String)> {
+    let body = std::str::from_utf8(body).ok()?;
+
+    let mut ssid = None;
+    let mut password = String::new();
+    for field in body.split('&') {
+        let (key, value) = field.split_once('=')?;
+        match key {
+            "ssid" => ssid = Some(url_decode(value)?),
+            "password" => password = url_decode(value)?,
+            _ => {}
+        }
+    }
+
+    let ssid = ssid.filter(|ssid| !ssid.is_empty() && ssid.len() <= MAX_SSID_LEN)?;
+    let password_ok =
+        password.is_empty() || (MIN_PASSWORD_LEN..=MAX_PASSWORD_LEN).contains(&password.len());
+
+    password_ok.then_some((ssid, password))
+}
+
+// Undoes the `+` and `%XX` escaping browsers apply to form values.
+fn url_decode(value: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut input = value.bytes();
+    while let Some(byte) = input.next() {
+        match byte {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex = [input.next()?, input.next()?];
+                let hex = std::str::from_utf8(&hex).ok()?;
+                bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            }
+            _ => bytes.push(byte),
+        }
+    }
+
+    String::from_utf8(bytes).ok()
+}
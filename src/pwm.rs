@@ -0,0 +1,271 @@
+// Dimming LEDs and positioning servos with the LEDC PWM peripheral.
+//
+// Past the LEDC's channels, SoftPwm drives any output pin from the alarm
+// ISR of a hardware timer. The timer auto reloads with the alarm set to
+// the length of the current level, and the ISR flips the pin and sets the
+// alarm to the length of the next level: two interrupts per period.
+
+use core::sync::atomic::{AtomicU8, Ordering};
+use std::{sync::Arc, thread, time::Duration};
+
+use esp_idf_svc::{
+    hal::{
+        gpio::{AnyOutputPin, OutputPin, PinDriver},
+        ledc::{
+            config::{Resolution, TimerConfig},
+            LedcDriver,
+        },
+        peripheral::Peripheral,
+        units::Hertz,
+    },
+    sys::{timer_group_set_alarm_value_in_isr, EspError, ESP_ERR_INVALID_ARG},
+};
+
+use crate::timer::{divider_for_hz, HwTimer, TimerConfigBuilder, TimerError, TimerId};
+
+// Time between two duty updates while fading, short enough to look smooth.
+const FADE_STEP: Duration = Duration::from_millis(10);
+
+/// Frequency hobby servos expect their control pulses at.
+pub const SERVO_FREQUENCY_HZ: u32 = 50;
+
+/// Pulse width a standard servo moves to 0° at.
+pub const SERVO_MIN_PULSE_US: u32 = 1000;
+
+/// Pulse width a standard servo moves to 180° at.
+pub const SERVO_MAX_PULSE_US: u32 = 2000;
+
+/// Largest angle `Servo::set_angle` moves to.
+pub const SERVO_MAX_ANGLE: u8 = 180;
+
+// One period of the servo signal.
+const SERVO_PERIOD_US: u32 = 1_000_000 / SERVO_FREQUENCY_HZ;
+
+/// Highest frequency a SoftPwm runs at.
+pub const SOFT_PWM_MAX_HZ: u32 = 5_000;
+
+// Rate the SoftPwm timer counts at.
+const SOFT_PWM_TICK_HZ: u32 = 1_000_000;
+
+// Shortest level a SoftPwm outputs, shorter ones would end before the ISR
+// that started them returns. Duties that close to 0% or 100% are output
+// as fully off or on.
+const SOFT_PWM_MIN_LEVEL_US: u64 = 10;
+
+/// An LED whose brightness is set through the duty cycle of a LEDC channel.
+pub struct PwmLed<'d> {
+    driver: LedcDriver<'d>,
+}
+
+impl<'d> PwmLed<'d> {
+    pub fn new(driver: LedcDriver<'d>) -> Self {
+        Self { driver }
+    }
+
+    /// Current brightness, in percent.
+    pub fn brightness(&self) -> u8 {
+        duty_to_percent(self.driver.get_duty(), self.driver.get_max_duty())
+    }
+
+    /// Sets the brightness to `percent`, anything above 100 is full on.
+    pub fn set_brightness(&mut self, percent: u8) -> Result<(), EspError> {
+        let duty = percent_to_duty(percent, self.driver.get_max_duty());
+        self.driver.set_duty(duty)
+    }
+
+    /// Steps the brightness from its current value to `target` percent over
+    /// `duration`, blocking until done.
+    pub fn fade_to(&mut self, target: u8, duration: Duration) -> Result<(), EspError> {
+        let max_duty = self.driver.get_max_duty();
+        let start = i64::from(self.driver.get_duty());
+        let end = i64::from(percent_to_duty(target, max_duty));
+
+        let steps = (duration.as_millis() / FADE_STEP.as_millis()).max(1) as i64;
+        for step in 1..=steps {
+            let duty = start + (end - start) * step / steps;
+            self.driver.set_duty(duty as u32)?;
+            if step < steps {
+                thread::sleep(FADE_STEP);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Gives the LEDC channel back.
+    pub fn release(self) -> LedcDriver<'d> {
+        self.driver
+    }
+}
+
+fn percent_to_duty(percent: u8, max_duty: u32) -> u32 {
+    (u64::from(percent.min(100)) * u64::from(max_duty) / 100) as u32
+}
+
+fn duty_to_percent(duty: u32, max_duty: u32) -> u8 {
+    if max_duty == 0 {
+        return 0;
+    }
+    (u64::from(duty.min(max_duty)) * 100 / u64::from(max_duty)) as u8
+}
+
+/// LEDC timer configuration for driving servos: 50 Hz, with the finest
+/// resolution every chip supports so the pulse width has small steps.
+pub fn servo_timer_config() -> TimerConfig {
+    TimerConfig::new()
+        .frequency(Hertz(SERVO_FREQUENCY_HZ))
+        .resolution(Resolution::Bits14)
+}
+
+/// A hobby servo positioned by the width of its control pulse.
+///
+/// The LEDC channel must run on a timer configured with
+/// `servo_timer_config()`.
+pub struct Servo<'d> {
+    driver: LedcDriver<'d>,
+    min_pulse_us: u32,
+    max_pulse_us: u32,
+}
+
+impl<'d> Servo<'d> {
+    /// A servo using the standard 1.0–2.0 ms pulse range.
+    pub fn new(driver: LedcDriver<'d>) -> Self {
+        Self {
+            driver,
+            min_pulse_us: SERVO_MIN_PULSE_US,
+            max_pulse_us: SERVO_MAX_PULSE_US,
+        }
+    }
+
+    /// For servos whose 0° and 180° pulses differ from the standard ones.
+    ///
+    /// Fails with ESP_ERR_INVALID_ARG unless `min_pulse_us < max_pulse_us`
+    /// and both fit in one 20 ms period.
+    pub fn with_pulse_range(
+        driver: LedcDriver<'d>,
+        min_pulse_us: u32,
+        max_pulse_us: u32,
+    ) -> Result<Self, EspError> {
+        if min_pulse_us >= max_pulse_us || max_pulse_us > SERVO_PERIOD_US {
+            return Err(EspError::from_infallible::<ESP_ERR_INVALID_ARG>());
+        }
+
+        Ok(Self {
+            driver,
+            min_pulse_us,
+            max_pulse_us,
+        })
+    }
+
+    /// Moves to `degrees`, angles past SERVO_MAX_ANGLE are clamped.
+    pub fn set_angle(&mut self, degrees: u8) -> Result<(), EspError> {
+        let degrees = u32::from(degrees.min(SERVO_MAX_ANGLE));
+        let span = self.max_pulse_us - self.min_pulse_us;
+        let pulse_us = self.min_pulse_us + span * degrees / u32::from(SERVO_MAX_ANGLE);
+
+        let duty = u64::from(pulse_us) * u64::from(self.driver.get_max_duty())
+            / u64::from(SERVO_PERIOD_US);
+        self.driver.set_duty(duty as u32)
+    }
+
+    /// Gives the LEDC channel back.
+    pub fn release(self) -> LedcDriver<'d> {
+        self.driver
+    }
+}
+
+/// PWM on any output pin, toggled from a hardware timer's alarm ISR.
+///
+/// Each period takes two interrupts of a few microseconds each, so at
+/// SOFT_PWM_MAX_HZ that's 10000 interrupts a second, several percent of
+/// the CPU. A 100 Hz to 1 kHz LED dimmer costs well under 1%. The duty is
+/// in 1% steps, and levels shorter than 10 µs aren't output (at 5 kHz that
+/// rounds 1-4% down to off). The edges jitter by the interrupt latency.
+///
+/// A LEDC channel runs at up to MHz, with finer steps and no CPU at all,
+/// use it (PwmLed) whenever one is free.
+///
+/// Dropping the SoftPwm stops it and frees the pin and timer.
+pub struct SoftPwm {
+    // Owns the ISR, and the pin with it.
+    _timer: HwTimer<'static>,
+    duty: Arc<AtomicU8>,
+    frequency_hz: u32,
+}
+
+impl SoftPwm {
+    /// Starts `pin` at `frequency_hz` and 0% duty, using `timer`.
+    ///
+    /// Fails with TimerError::InvalidFrequency if `frequency_hz` is 0 or
+    /// above SOFT_PWM_MAX_HZ.
+    ///
+    /// The pin is taken for good rather than borrowed, since the ISR owns
+    /// it and would outlive a borrow if the SoftPwm were leaked.
+    pub fn new(
+        pin: impl Peripheral<P = impl OutputPin> + 'static,
+        timer: TimerId,
+        frequency_hz: u32,
+    ) -> Result<Self, TimerError> {
+        if frequency_hz == 0 || frequency_hz > SOFT_PWM_MAX_HZ {
+            return Err(TimerError::InvalidFrequency);
+        }
+
+        let mut pin = PinDriver::output(pin.into_ref().map_into::<AnyOutputPin>())?;
+        pin.set_low()?;
+
+        let config = TimerConfigBuilder::new()
+            .divider(divider_for_hz(SOFT_PWM_TICK_HZ)?)
+            .build()?;
+        let mut hw_timer = HwTimer::new(timer, config)?;
+        let period = u64::from(SOFT_PWM_TICK_HZ / frequency_hz);
+        hw_timer.set_alarm(period)?;
+
+        let duty = Arc::new(AtomicU8::new(0));
+        let isr_duty = duty.clone();
+        let mut high = false;
+        hw_timer.on_alarm(move || {
+            let on = period * u64::from(isr_duty.load(Ordering::Relaxed)) / 100;
+            // The level to output, and for how long.
+            let (level, next) = if on < SOFT_PWM_MIN_LEVEL_US {
+                (false, period)
+            } else if period - on < SOFT_PWM_MIN_LEVEL_US {
+                (true, period)
+            } else if high {
+                (false, period - on)
+            } else {
+                (true, on)
+            };
+
+            high = level;
+            // Can't fail on a pin set up as an output.
+            let _ = if level { pin.set_high() } else { pin.set_low() };
+            // SAFETY: timer_group_set_alarm_value_in_isr() is the ISR safe
+            // variant of timer_set_alarm_value(), meant to be called from here.
+            unsafe { timer_group_set_alarm_value_in_isr(timer.group(), timer.index(), next) };
+        })?;
+        hw_timer.enable_interrupt()?;
+        hw_timer.start()?;
+
+        Ok(Self {
+            _timer: hw_timer,
+            duty,
+            frequency_hz,
+        })
+    }
+
+    /// Current duty cycle, in percent.
+    pub fn duty(&self) -> u8 {
+        self.duty.load(Ordering::Relaxed)
+    }
+
+    /// Sets the duty cycle to `percent`, anything above 100 is full on.
+    ///
+    /// Takes effect at the next level change.
+    pub fn set_duty(&mut self, percent: u8) {
+        self.duty.store(percent.min(100), Ordering::Relaxed);
+    }
+
+    pub fn frequency_hz(&self) -> u32 {
+        self.frequency_hz
+    }
+}
@@ -0,0 +1,266 @@
+// A fixed size queue for handing values from an ISR to a thread.
+//
+// No locks and no allocation: the slots are inline, and the two ends are
+// atomic indexes that each side only ever moves forward on its own. The
+// producer writes a slot and then publishes it by storing `head` with
+// Release, the consumer loads `head` with Acquire before reading the slot,
+// so it always sees the whole value. The other way round, the consumer
+// frees a slot by storing `tail` with Release once it's done reading it,
+// and the producer loads `tail` with Acquire before overwriting it.
+//
+// The indexes count modulo 2 * N, so a full buffer (N apart) and an empty
+// one (equal) look different and all N slots get used.
+//
+// Each side also holds a flag while it runs, like the polling flag of a
+// QuadratureDecoder. A second producer or consumer interrupting the first
+// one just fails, rather than both using the same slot.
+
+use core::{
+    cell::UnsafeCell,
+    mem::MaybeUninit,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+/// A lock free queue of up to `N` values, pushed by one producer, e.g. an
+/// ISR, and popped by one consumer, e.g. the main loop.
+///
+/// It's single producer, single consumer: push() is meant to be called
+/// from one place, e.g. one ISR, and pop() from another. A push() made
+/// while another one is still running, e.g. from a higher level ISR that
+/// interrupted it, fails as if the buffer was full, and such a pop() finds
+/// it empty. Neither allocates, blocks or waits on the other side, so both
+/// can be called from an ISR. new() is const, so the buffer
+/// can be a static shared with an `extern "C"` ISR.
+pub struct IsrRingBuffer<T, const N: usize> {
+    slots: UnsafeCell<[MaybeUninit<T>; N]>,
+    // Next slot to push to and to pop from, counting modulo 2 * N.
+    head: AtomicUsize,
+    tail: AtomicUsize,
+    pushing: AtomicBool,
+    popping: AtomicBool,
+}
+
+// SAFETY: a slot is only accessed by the producer while it's free and by
+// the consumer while it's filled, the indexes hand each one over with
+// Release / Acquire. The flags keep it to one of each at a time.
+unsafe impl<T: Send, const N: usize> Sync for IsrRingBuffer<T, N> {}
+
+impl<T, const N: usize> IsrRingBuffer<T, N> {
+    /// An empty buffer. Panics for N = 0, at compile time for a static.
+    pub const fn new() -> Self {
+        assert!(N > 0, "IsrRingBuffer needs room for at least one value");
+        Self {
+            // SAFETY: an array of MaybeUninit doesn't need initializing.
+            slots: UnsafeCell::new(unsafe { MaybeUninit::uninit().assume_init() }),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            pushing: AtomicBool::new(false),
+            popping: AtomicBool::new(false),
+        }
+    }
+
+    /// Appends `value`, or hands it back when the buffer is full. Producer
+    /// side.
+    pub fn push(&self, value: T) -> Result<(), T> {
+        if self.pushing.swap(true, Ordering::Acquire) {
+            return Err(value);
+        }
+
+        let head = self.head.load(Ordering::Relaxed);
+        // Acquire, so the consumer is done reading a slot it freed.
+        let tail = self.tail.load(Ordering::Acquire);
+        let pushed = if distance(tail, head, N) == N {
+            Err(value)
+        } else {
+            // SAFETY: the slot at `head` is free, the consumer doesn't touch
+            // it until `head` moves past it, and `pushing` keeps other
+            // producers out.
+            unsafe { self.slot(head).write(MaybeUninit::new(value)) };
+            // Release, so the consumer sees the value before the new head.
+            self.head.store(next(head, N), Ordering::Release);
+            Ok(())
+        };
+        self.pushing.store(false, Ordering::Release);
+
+        pushed
+    }
+
+    /// Takes the oldest value, None if the buffer is empty. Consumer side.
+    pub fn pop(&self) -> Option<T> {
+        if self.popping.swap(true, Ordering::Acquire) {
+            return None;
+        }
+
+        let tail = self.tail.load(Ordering::Relaxed);
+        // Acquire, so the value the producer wrote is visible.
+        let head = self.head.load(Ordering::Acquire);
+        let value = (head != tail).then(|| {
+            // SAFETY: the slot at `tail` was filled and published by push(),
+            // the producer doesn't reuse it until `tail` moves past it, and
+            // `popping` keeps other consumers out.
+            let value = unsafe { self.slot(tail).read().assume_init() };
+            // Release, so the producer only overwrites the slot once it's
+            // read.
+            self.tail.store(next(tail, N), Ordering::Release);
+            value
+        });
+        self.popping.store(false, Ordering::Release);
+
+        value
+    }
+
+    /// Number of values waiting. Only a snapshot when the other side is
+    /// running meanwhile.
+    pub fn len(&self) -> usize {
+        let tail = self.tail.load(Ordering::Acquire);
+        distance(tail, self.head.load(Ordering::Acquire), N)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len() == N
+    }
+
+    /// The number of values the buffer holds at most, `N`.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    // The slot an index points to. A raw pointer to that slot alone, the
+    // two sides never borrow the whole array.
+    fn slot(&self, index: usize) -> *mut MaybeUninit<T> {
+        // SAFETY: index % N is within the array.
+        unsafe { self.slots.get().cast::<MaybeUninit<T>>().add(index % N) }
+    }
+}
+
+impl<T, const N: usize> Default for IsrRingBuffer<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Drop for IsrRingBuffer<T, N> {
+    fn drop(&mut self) {
+        // The values still waiting are the only initialized slots.
+        while self.pop().is_some() {}
+    }
+}
+
+// The index after `index`, modulo 2 * n.
+fn next(index: usize, n: usize) -> usize {
+    if index + 1 == 2 * n {
+        0
+    } else {
+        index + 1
+    }
+}
+
+// How many values are between `tail` and `head`, modulo 2 * n.
+fn distance(tail: usize, head: usize, n: usize) -> usize {
+    if head >= tail {
+        head - tail
+    } else {
+        head + 2 * n - tail
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_buffer_pops_nothing() {
+        let buffer = IsrRingBuffer::<u32, 4>::new();
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.len(), 0);
+        assert_eq!(buffer.pop(), None);
+    }
+
+    #[test]
+    fn full_buffer_hands_the_value_back() {
+        let buffer = IsrRingBuffer::<u32, 4>::new();
+        for value in 0..4 {
+            assert_eq!(buffer.push(value), Ok(()));
+        }
+        assert!(buffer.is_full());
+        assert_eq!(buffer.len(), 4);
+        assert_eq!(buffer.push(4), Err(4));
+
+        // Popping one frees a slot again.
+        assert_eq!(buffer.pop(), Some(0));
+        assert_eq!(buffer.push(4), Ok(()));
+        assert_eq!(buffer.push(5), Err(5));
+    }
+
+    #[test]
+    fn pops_in_push_order() {
+        let buffer = IsrRingBuffer::<u32, 4>::new();
+        for value in 0..3 {
+            buffer.push(value).unwrap();
+        }
+        assert_eq!(buffer.pop(), Some(0));
+        assert_eq!(buffer.pop(), Some(1));
+        assert_eq!(buffer.pop(), Some(2));
+        assert_eq!(buffer.pop(), None);
+    }
+
+    #[test]
+    fn wraps_around() {
+        // Enough rounds for both indexes to wrap past 2 * N several times,
+        // at every fill level.
+        let buffer = IsrRingBuffer::<u32, 3>::new();
+        let mut next_pushed = 0;
+        let mut next_popped = 0;
+        for round in 0..20 {
+            let fill = round % 4;
+            for _ in 0..fill {
+                buffer.push(next_pushed).unwrap();
+                next_pushed += 1;
+            }
+            assert_eq!(buffer.len(), fill as usize);
+            assert_eq!(buffer.is_full(), fill == 3);
+            for _ in 0..fill {
+                assert_eq!(buffer.pop(), Some(next_popped));
+                next_popped += 1;
+            }
+            assert!(buffer.is_empty());
+        }
+    }
+
+    #[test]
+    fn single_slot_buffer() {
+        let buffer = IsrRingBuffer::<u32, 1>::new();
+        for value in 0..5 {
+            assert_eq!(buffer.push(value), Ok(()));
+            assert_eq!(buffer.push(value + 100), Err(value + 100));
+            assert_eq!(buffer.pop(), Some(value));
+            assert_eq!(buffer.pop(), None);
+        }
+    }
+
+    #[test]
+    fn drop_releases_waiting_values() {
+        use std::rc::Rc;
+
+        let value = Rc::new(());
+        let buffer = IsrRingBuffer::<Rc<()>, 4>::new();
+        buffer.push(value.clone()).unwrap();
+        buffer.push(value.clone()).unwrap();
+        assert_eq!(Rc::strong_count(&value), 3);
+        drop(buffer);
+        assert_eq!(Rc::strong_count(&value), 1);
+    }
+
+    #[test]
+    fn index_math() {
+        assert_eq!(next(0, 4), 1);
+        assert_eq!(next(7, 4), 0);
+        assert_eq!(distance(0, 0, 4), 0);
+        assert_eq!(distance(6, 2, 4), 4);
+        assert_eq!(distance(7, 1, 4), 2);
+    }
+}
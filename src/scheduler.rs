@@ -0,0 +1,196 @@
+// Several periodic callbacks run off a single hardware timer.
+//
+// The timer ticks at a fixed rate and its alarm ISR goes through the
+// tasks, running each one whose interval has passed since it last ran.
+// Intervals are counted in whole ticks: a shorter tick is more precise, a
+// longer one costs less CPU time.
+//
+// The tasks live in a fixed number of slots guarded by an
+// IsrCriticalSection, which both the ISR and a thread adding or cancelling
+// a task take. Callbacks are boxed before and dropped after entering it, so
+// the ISR never waits on the allocator.
+
+use core::{
+    cell::UnsafeCell,
+    sync::atomic::{AtomicU32, Ordering},
+    time::Duration,
+};
+use std::sync::Arc;
+
+use esp_idf_svc::{
+    hal::interrupt::IsrCriticalSection,
+    sys::{EspError, ESP_ERR_NOT_FOUND, ESP_ERR_NO_MEM},
+};
+
+use crate::timer::{divider_for_hz, HwTimer, TimerConfigBuilder, TimerError, TimerId};
+
+// Longest interval in ticks, so the wrapping tick counter compares right.
+const MAX_INTERVAL_TICKS: u128 = (u32::MAX / 2) as u128;
+
+// Called from the ISR when a task is due.
+type TaskCallback = Box<dyn FnMut() + Send>;
+
+struct Task {
+    interval: u32,
+    // Tick count when it last ran, or was added.
+    last_run: u32,
+    callback: TaskCallback,
+}
+
+struct Slot {
+    task: Option<Task>,
+    // Bumped on every add, so a handle to an earlier task of the slot
+    // doesn't cancel the current one.
+    generation: u32,
+}
+
+// What the ISR shares with the Scheduler.
+struct Tasks {
+    lock: IsrCriticalSection,
+    // Only touched with `lock` held.
+    slots: UnsafeCell<Box<[Slot]>>,
+    ticks: AtomicU32,
+}
+
+// SAFETY: the slots are only accessed with the critical section held, and
+// the callbacks are Send.
+unsafe impl Sync for Tasks {}
+
+impl Tasks {
+    // Runs from the ISR on every tick.
+    fn tick(&self) {
+        let now = self.ticks.fetch_add(1, Ordering::Relaxed).wrapping_add(1);
+
+        let _guard = self.lock.enter();
+        // SAFETY: the critical section is held.
+        let slots = unsafe { &mut *self.slots.get() };
+        for task in slots.iter_mut().filter_map(|slot| slot.task.as_mut()) {
+            if now.wrapping_sub(task.last_run) >= task.interval {
+                task.last_run = now;
+                (task.callback)();
+            }
+        }
+    }
+}
+
+/// Identifies a task of a Scheduler, to cancel it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TaskHandle {
+    index: usize,
+    generation: u32,
+}
+
+/// Runs periodic callbacks at their own intervals, all from the alarm of
+/// one hardware timer.
+///
+/// The callbacks run from the timer's ISR one after the other, with the
+/// other interrupts held off meanwhile. They should be short and must not
+/// block, allocate or log, nor add or cancel tasks. Adding or cancelling a
+/// task waits for the tick being handled, if any.
+pub struct Scheduler {
+    tasks: Arc<Tasks>,
+    tick: Duration,
+    _timer: HwTimer<'static>,
+}
+
+impl Scheduler {
+    /// Starts ticking every `tick`, with room for `capacity` tasks.
+    ///
+    /// Fails with TimerError::InvalidFrequency for a zero tick.
+    pub fn new(timer: TimerId, tick: Duration, capacity: usize) -> Result<Self, TimerError> {
+        if tick.is_zero() {
+            return Err(TimerError::InvalidFrequency);
+        }
+
+        let slots = (0..capacity)
+            .map(|_| Slot {
+                task: None,
+                generation: 0,
+            })
+            .collect();
+        let tasks = Arc::new(Tasks {
+            lock: IsrCriticalSection::new(),
+            slots: UnsafeCell::new(slots),
+            ticks: AtomicU32::new(0),
+        });
+
+        let config = TimerConfigBuilder::new()
+            .divider(divider_for_hz(1_000_000)?)
+            .build()?;
+        let mut hw_timer = HwTimer::new(timer, config)?;
+        hw_timer.set_alarm_after(tick)?;
+        let isr_tasks = tasks.clone();
+        hw_timer.on_alarm(move || isr_tasks.tick())?;
+        hw_timer.enable_interrupt()?;
+        hw_timer.start()?;
+
+        Ok(Self {
+            tasks,
+            tick,
+            _timer: hw_timer,
+        })
+    }
+
+    /// Time between two ticks.
+    pub fn tick(&self) -> Duration {
+        self.tick
+    }
+
+    /// Runs `callback` every `interval`, the first time `interval` from now.
+    ///
+    /// The interval is rounded up to whole ticks, at least one, and capped
+    /// at 2^31 ticks. Fails with ESP_ERR_NO_MEM when all the slots are
+    /// taken.
+    pub fn add<F>(&self, interval: Duration, callback: F) -> Result<TaskHandle, EspError>
+    where
+        F: FnMut() + Send + 'static,
+    {
+        let tick = self.tick.as_nanos();
+        let ticks = ((interval.as_nanos() + tick - 1) / tick).clamp(1, MAX_INTERVAL_TICKS);
+        // Dropped after the critical section if there's no free slot.
+        let mut task = Some(Task {
+            interval: ticks as u32,
+            last_run: self.tasks.ticks.load(Ordering::Relaxed),
+            callback: Box::new(callback),
+        });
+
+        let handle = {
+            let _guard = self.tasks.lock.enter();
+            // SAFETY: the critical section is held.
+            let slots = unsafe { &mut *self.tasks.slots.get() };
+            let free = slots
+                .iter_mut()
+                .enumerate()
+                .find(|(_, slot)| slot.task.is_none());
+            free.map(|(index, slot)| {
+                slot.task = task.take();
+                slot.generation = slot.generation.wrapping_add(1);
+                TaskHandle {
+                    index,
+                    generation: slot.generation,
+                }
+            })
+        };
+
+        handle.ok_or_else(EspError::from_infallible::<ESP_ERR_NO_MEM>)
+    }
+
+    /// Stops the task of `handle`, it doesn't run again once this returns.
+    ///
+    /// Fails with ESP_ERR_NOT_FOUND if it was already cancelled.
+    pub fn cancel(&self, handle: TaskHandle) -> Result<(), EspError> {
+        let task = {
+            let _guard = self.tasks.lock.enter();
+            // SAFETY: the critical section is held.
+            let slots = unsafe { &mut *self.tasks.slots.get() };
+            slots
+                .get_mut(handle.index)
+                .filter(|slot| slot.generation == handle.generation)
+                .and_then(|slot| slot.task.take())
+        };
+
+        // The callback is dropped here, out of the critical section.
+        task.map(|_| ())
+            .ok_or_else(EspError::from_infallible::<ESP_ERR_NOT_FOUND>)
+    }
+}
@@ -0,0 +1,137 @@
+// Shows the state of the network connection on an RGB LED, either three
+// GPIO channels or a single WS2812.
+
+use esp_idf_svc::{
+    hal::{
+        gpio::{AnyOutputPin, Level, Output, OutputPin, PinDriver},
+        peripheral::Peripheral,
+    },
+    sys::EspError,
+};
+
+use crate::neopixel::NeoPixel;
+
+// WS2812s are blinding at full brightness, this is plenty for a status.
+const NEOPIXEL_LEVEL: u8 = 32;
+
+/// States the connection goes through, as shown by `StatusLed::show`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// Blinking blue.
+    Connecting,
+    /// Solid green.
+    Connected,
+    /// Solid red.
+    Error,
+}
+
+/// An RGB status LED.
+pub struct StatusLed<'d> {
+    driver: Driver<'d>,
+    // Whether the blue channel is lit in the current blink phase.
+    blink_on: bool,
+}
+
+enum Driver<'d> {
+    Pins {
+        red: PinDriver<'d, AnyOutputPin, Output>,
+        green: PinDriver<'d, AnyOutputPin, Output>,
+        blue: PinDriver<'d, AnyOutputPin, Output>,
+        common_anode: bool,
+    },
+    // Only the first pixel of the strip is used.
+    NeoPixel(NeoPixel<'d>),
+}
+
+impl<'d> StatusLed<'d> {
+    /// Takes the three channels of the LED, which starts switched off.
+    ///
+    /// With `common_anode` set the channels are active low, otherwise a
+    /// common cathode LED is assumed and they are active high.
+    pub fn new(
+        red: impl Peripheral<P = impl OutputPin> + 'd,
+        green: impl Peripheral<P = impl OutputPin> + 'd,
+        blue: impl Peripheral<P = impl OutputPin> + 'd,
+        common_anode: bool,
+    ) -> Result<Self, EspError> {
+        Self::with_driver(Driver::Pins {
+            red: PinDriver::output(red.into_ref().map_into::<AnyOutputPin>())?,
+            green: PinDriver::output(green.into_ref().map_into::<AnyOutputPin>())?,
+            blue: PinDriver::output(blue.into_ref().map_into::<AnyOutputPin>())?,
+            common_anode,
+        })
+    }
+
+    /// Shows the status on the first pixel of `pixel`, e.g. the WS2812
+    /// many dev boards have on board.
+    pub fn from_neopixel(pixel: NeoPixel<'d>) -> Result<Self, EspError> {
+        Self::with_driver(Driver::NeoPixel(pixel))
+    }
+
+    fn with_driver(driver: Driver<'d>) -> Result<Self, EspError> {
+        let mut led = Self {
+            driver,
+            blink_on: false,
+        };
+        led.off()?;
+
+        Ok(led)
+    }
+
+    /// Toggles the LED between blue and off, call it periodically while
+    /// the connection is being established to make it blink.
+    pub fn connecting(&mut self) -> Result<(), EspError> {
+        self.blink_on = !self.blink_on;
+        self.set(false, false, self.blink_on)
+    }
+
+    /// Lights the LED solid green.
+    pub fn connected(&mut self) -> Result<(), EspError> {
+        self.blink_on = false;
+        self.set(false, true, false)
+    }
+
+    /// Lights the LED solid red.
+    pub fn error(&mut self) -> Result<(), EspError> {
+        self.blink_on = false;
+        self.set(true, false, false)
+    }
+
+    /// Switches all the channels off.
+    pub fn off(&mut self) -> Result<(), EspError> {
+        self.blink_on = false;
+        self.set(false, false, false)
+    }
+
+    /// Shows `status`, advancing the blink for `Status::Connecting`.
+    pub fn show(&mut self, status: Status) -> Result<(), EspError> {
+        match status {
+            Status::Connecting => self.connecting(),
+            Status::Connected => self.connected(),
+            Status::Error => self.error(),
+        }
+    }
+
+    fn set(&mut self, red: bool, green: bool, blue: bool) -> Result<(), EspError> {
+        match &mut self.driver {
+            Driver::Pins {
+                red: red_pin,
+                green: green_pin,
+                blue: blue_pin,
+                common_anode,
+            } => {
+                let level = |on: bool| Level::from(on != *common_anode);
+
+                red_pin.set_level(level(red))?;
+                green_pin.set_level(level(green))?;
+                blue_pin.set_level(level(blue))
+            }
+            Driver::NeoPixel(pixel) => {
+                let level = |on: bool| if on { NEOPIXEL_LEVEL } else { 0 };
+
+                pixel.set_pixel(0, level(red), level(green), level(blue))?;
+                pixel.flush()
+            }
+        }
+    }
+}
@@ -0,0 +1,124 @@
+// A stopwatch counting microseconds on a hardware timer.
+//
+// The timer counts up at 1 MHz and is only read, never reset: the
+// stopwatch adds what it counted since the previous read to a 64-bit
+// total, masking the difference to the counter's width so a counter that
+// wrapped around still adds up right. The ESP32's counter is 64 bits wide,
+// the other chips' 54, which at 1 MHz wraps after some 570 years anyway.
+
+use core::time::Duration;
+
+use crate::timer::{
+    divider_for_hz, ticks_to_duration, HwTimer, TimerConfigBuilder, TimerError, TimerId,
+};
+
+#[cfg(esp32)]
+const COUNTER_MASK: u64 = u64::MAX;
+#[cfg(not(esp32))]
+const COUNTER_MASK: u64 = (1 << 54) - 1;
+
+/// Measures time with start() and stop(), and split times with lap().
+pub struct Stopwatch {
+    timer: HwTimer<'static>,
+    running: bool,
+    // Ticks counted up to when the counter read `mark`.
+    total: u64,
+    mark: u64,
+    // Split time of every lap, from the start.
+    laps: Vec<Duration>,
+}
+
+impl Stopwatch {
+    /// A stopped stopwatch on `timer`, at zero.
+    pub fn new(timer: TimerId) -> Result<Self, TimerError> {
+        let config = TimerConfigBuilder::new()
+            .divider(divider_for_hz(1_000_000)?)
+            .auto_reload(false)
+            .build()?;
+
+        Ok(Self {
+            timer: HwTimer::new(timer, config)?,
+            running: false,
+            total: 0,
+            mark: 0,
+            laps: Vec::new(),
+        })
+    }
+
+    /// Starts counting, or resumes where stop() left it. Does nothing if it
+    /// already runs.
+    pub fn start(&mut self) -> Result<(), TimerError> {
+        if !self.running {
+            // The counter is paused, it still reads what it did at stop().
+            self.mark = self.timer.counter()?;
+            self.timer.start()?;
+            self.running = true;
+        }
+        Ok(())
+    }
+
+    /// Stops counting, keeping the time counted so far. Does nothing if it
+    /// isn't running.
+    pub fn stop(&mut self) -> Result<(), TimerError> {
+        if self.running {
+            self.timer.pause()?;
+            self.update()?;
+            self.running = false;
+        }
+        Ok(())
+    }
+
+    /// Whether it's counting.
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// Time counted while running since new() or reset().
+    pub fn elapsed(&self) -> Result<Duration, TimerError> {
+        let mut total = self.total;
+        if self.running {
+            total = total.saturating_add(self.counted_since_mark(self.timer.counter()?));
+        }
+        Ok(self.to_duration(total))
+    }
+
+    /// Records a split time and returns the lap, the time since the
+    /// previous lap() (or since the start, for the first one). The
+    /// stopwatch keeps counting from the start.
+    pub fn lap(&mut self) -> Result<Duration, TimerError> {
+        let split = self.update()?;
+        let previous = self.laps.last().copied().unwrap_or_default();
+        self.laps.push(split);
+        Ok(split.saturating_sub(previous))
+    }
+
+    /// Split times of the laps so far, each counted from the start.
+    pub fn laps(&self) -> &[Duration] {
+        &self.laps
+    }
+
+    /// Back to zero, with no laps. Keeps running if it was.
+    pub fn reset(&mut self) -> Result<(), TimerError> {
+        self.mark = self.timer.counter()?;
+        self.total = 0;
+        self.laps.clear();
+        Ok(())
+    }
+
+    // Adds the ticks counted since the last update to the total, returning
+    // it as a Duration.
+    fn update(&mut self) -> Result<Duration, TimerError> {
+        let now = self.timer.counter()?;
+        self.total = self.total.saturating_add(self.counted_since_mark(now));
+        self.mark = now;
+        Ok(self.to_duration(self.total))
+    }
+
+    fn counted_since_mark(&self, now: u64) -> u64 {
+        now.wrapping_sub(self.mark) & COUNTER_MASK
+    }
+
+    fn to_duration(&self, ticks: u64) -> Duration {
+        ticks_to_duration(ticks, self.timer.tick_hz())
+    }
+}
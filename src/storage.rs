@@ -0,0 +1,73 @@
+// Typed key/value storage in a namespace of the default NVS partition.
+
+use esp_idf_svc::{
+    nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault},
+    sys::EspError,
+};
+
+/// Values persisted in one NVS namespace, surviving reboots and reflashes
+/// of the app.
+///
+/// Keys are at most 15 characters, as NVS requires. The getters return
+/// None for keys that were never set.
+pub struct KvStore {
+    nvs: EspNvs<NvsDefault>,
+}
+
+impl KvStore {
+    /// Opens `namespace` (at most 15 characters), creating it if needed.
+    pub fn new(partition: &EspDefaultNvsPartition, namespace: &str) -> Result<Self, EspError> {
+        // Read-write, a read-only open fails until the namespace exists.
+        Ok(Self {
+            nvs: EspNvs::new(partition.clone(), namespace, true)?,
+        })
+    }
+
+    pub fn get_u32(&self, key: &str) -> Result<Option<u32>, EspError> {
+        self.nvs.get_u32(key)
+    }
+
+    pub fn set_u32(&mut self, key: &str, value: u32) -> Result<(), EspError> {
+        self.nvs.set_u32(key, value)
+    }
+
+    pub fn get_i32(&self, key: &str) -> Result<Option<i32>, EspError> {
+        self.nvs.get_i32(key)
+    }
+
+    pub fn set_i32(&mut self, key: &str, value: i32) -> Result<(), EspError> {
+        self.nvs.set_i32(key, value)
+    }
+
+    pub fn get_str(&self, key: &str) -> Result<Option<String>, EspError> {
+        // The length NVS reports includes the NUL terminator.
+        let Some(len) = self.nvs.str_len(key)? else {
+            return Ok(None);
+        };
+
+        let mut buf = vec![0u8; len];
+        Ok(self.nvs.get_str(key, &mut buf)?.map(String::from))
+    }
+
+    pub fn set_str(&mut self, key: &str, value: &str) -> Result<(), EspError> {
+        self.nvs.set_str(key, value)
+    }
+
+    pub fn get_blob(&self, key: &str) -> Result<Option<Vec<u8>>, EspError> {
+        let Some(len) = self.nvs.blob_len(key)? else {
+            return Ok(None);
+        };
+
+        let mut buf = vec![0u8; len];
+        Ok(self.nvs.get_blob(key, &mut buf)?.map(<[u8]>::to_vec))
+    }
+
+    pub fn set_blob(&mut self, key: &str, value: &[u8]) -> Result<(), EspError> {
+        self.nvs.set_blob(key, value)
+    }
+
+    /// Deletes `key`, returning whether it was set.
+    pub fn remove(&mut self, key: &str) -> Result<bool, EspError> {
+        self.nvs.remove(key)
+    }
+}
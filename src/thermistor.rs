@@ -0,0 +1,182 @@
+// Temperature from an NTC thermistor in a voltage divider on an ADC pin.
+//
+// The thermistor and a fixed series resistor split the supply, and the
+// ADC reads the voltage in between. Taking a full scale reading as the
+// supply voltage, the reading is the ratio of the two resistances, which
+// gives the thermistor's resistance and from it the temperature, with
+// either the beta equation or the Steinhart-Hart one.
+//
+// The ADC full scale isn't exactly the supply, and gets non-linear near its
+// ends, so the result is off by a degree or two at the extremes. Readings
+// right at either rail are a disconnected or shorted thermistor rather
+// than a temperature.
+
+use core::fmt;
+use std::error::Error;
+
+use esp_idf_svc::{
+    hal::gpio::ADCPin,
+    sys::{adc_atten_t, EspError},
+};
+
+use crate::adc::{AnalogInput, MAX_RAW};
+
+// Readings averaged per measurement, the ADC is noisy.
+const SAMPLES: usize = 16;
+
+// Readings this close to 0 or MAX_RAW are taken as an open or short circuit.
+const RAIL_MARGIN: u16 = MAX_RAW / 100;
+
+const KELVIN: f32 = 273.15;
+
+/// How the temperature follows the thermistor's resistance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Coefficients {
+    /// Resistance `r0` in Ω at `t0` in °C, and the beta value in K, as
+    /// datasheets give them (e.g. 10kΩ at 25°C, beta 3950).
+    Beta { r0: f32, t0: f32, beta: f32 },
+    /// The a, b and c of 1/T = a + b ln(R) + c ln(R)³, T in K and R in Ω.
+    /// More accurate over a wide range.
+    SteinhartHart { a: f32, b: f32, c: f32 },
+}
+
+impl Coefficients {
+    /// Beta coefficients for a thermistor of `r25` Ω at 25°C.
+    pub fn beta(r25: f32, beta: f32) -> Self {
+        Coefficients::Beta {
+            r0: r25,
+            t0: 25.0,
+            beta,
+        }
+    }
+
+    /// Temperature in °C at `resistance` Ω.
+    pub fn temperature_c(&self, resistance: f32) -> f32 {
+        let inverse_t = match *self {
+            Coefficients::Beta { r0, t0, beta } => {
+                1.0 / (t0 + KELVIN) + (resistance / r0).ln() / beta
+            }
+            Coefficients::SteinhartHart { a, b, c } => {
+                let ln_r = resistance.ln();
+                a + b * ln_r + c * ln_r * ln_r * ln_r
+            }
+        };
+        1.0 / inverse_t - KELVIN
+    }
+}
+
+/// Where the thermistor sits in the divider.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Divider {
+    /// Series resistor to 3.3V, thermistor to GND: the reading drops as it
+    /// warms up.
+    NtcToGround,
+    /// Thermistor to 3.3V, series resistor to GND: the reading rises as it
+    /// warms up.
+    NtcToSupply,
+}
+
+/// Why a thermistor couldn't be read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThermistorError {
+    /// The reading is at the rail an unconnected thermistor leaves it at.
+    Open,
+    /// The reading is at the rail a short across the thermistor takes it to.
+    Shorted,
+    /// Reading the ADC failed.
+    Adc(EspError),
+}
+
+impl fmt::Display for ThermistorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ThermistorError::Open => write!(f, "Thermistor disconnected"),
+            ThermistorError::Shorted => write!(f, "Thermistor shorted"),
+            ThermistorError::Adc(e) => write!(f, "ADC read failed: {}", e),
+        }
+    }
+}
+
+impl Error for ThermistorError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ThermistorError::Adc(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<EspError> for ThermistorError {
+    fn from(e: EspError) -> Self {
+        ThermistorError::Adc(e)
+    }
+}
+
+/// An NTC thermistor read through an AnalogInput.
+///
+/// Use `attenuation::DB_11` for the input, the divider output covers most
+/// of the 0–3.3V range.
+pub struct Thermistor<'d, const A: adc_atten_t, T: ADCPin> {
+    input: AnalogInput<'d, A, T>,
+    series: f32,
+    divider: Divider,
+    coefficients: Coefficients,
+}
+
+impl<'d, const A: adc_atten_t, T: ADCPin> Thermistor<'d, A, T> {
+    /// A thermistor following `coefficients`, placed as `divider` with a
+    /// `series` Ω resistor.
+    pub fn new(
+        input: AnalogInput<'d, A, T>,
+        series: f32,
+        divider: Divider,
+        coefficients: Coefficients,
+    ) -> Self {
+        Self {
+            input,
+            series,
+            divider,
+            coefficients,
+        }
+    }
+
+    /// Resistance of the thermistor in Ω, from an averaged reading.
+    ///
+    /// Fails with Open or Shorted when the reading is within 1% of a rail.
+    pub fn resistance(&mut self) -> Result<f32, ThermistorError> {
+        let raw = self.input.read_averaged(SAMPLES)?;
+
+        // NTC to GND: an open circuit leaves the pin at 3.3V through the
+        // series resistor, a short pulls it to GND. The other way round
+        // with the NTC to 3.3V.
+        let (low, high) = match self.divider {
+            Divider::NtcToGround => (ThermistorError::Shorted, ThermistorError::Open),
+            Divider::NtcToSupply => (ThermistorError::Open, ThermistorError::Shorted),
+        };
+        if raw <= RAIL_MARGIN {
+            return Err(low);
+        }
+        if raw >= MAX_RAW - RAIL_MARGIN {
+            return Err(high);
+        }
+
+        let ratio = f32::from(raw) / f32::from(MAX_RAW);
+        Ok(match self.divider {
+            Divider::NtcToGround => self.series * ratio / (1.0 - ratio),
+            Divider::NtcToSupply => self.series * (1.0 - ratio) / ratio,
+        })
+    }
+
+    /// Temperature in °C, from an averaged reading.
+    ///
+    /// Fails like resistance().
+    pub fn temperature_c(&mut self) -> Result<f32, ThermistorError> {
+        let resistance = self.resistance()?;
+        Ok(self.coefficients.temperature_c(resistance))
+    }
+
+    /// Gives the AnalogInput back.
+    pub fn release(self) -> AnalogInput<'d, A, T> {
+        self.input
+    }
+}
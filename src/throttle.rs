@@ -0,0 +1,86 @@
+// Rate limited logging for loops that run too often to log every pass.
+//
+// log_every!() keeps a LogThrottle per call site, in a static, so every
+// call to it only logs if its own previous line is old enough.
+
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::time::Duration;
+
+use esp_idf_svc::systime::EspSystemTime;
+
+// Lets log_every!() reach the log macros without the caller depending on
+// the log crate.
+#[doc(hidden)]
+pub use log as __log;
+
+/// Logs like `log::info!`, but at most once per `interval` for each place
+/// it's written at.
+///
+/// Takes the interval first, then optionally `level: log::Level::Warn` for
+/// another level than Info, then the usual format string and arguments:
+///
+/// ```ignore
+/// log_every!(Duration::from_secs(1), "Position: {}", encoder.position());
+/// log_every!(Duration::from_secs(5), level: Level::Warn, "Battery low");
+/// ```
+///
+/// Calls made before the interval passed are dropped, arguments included,
+/// so they're only evaluated when the line is logged.
+#[macro_export]
+macro_rules! log_every {
+    ($interval:expr, level: $level:expr, $($arg:tt)+) => {{
+        static THROTTLE: $crate::throttle::LogThrottle = $crate::throttle::LogThrottle::new();
+        if THROTTLE.ready($interval) {
+            $crate::throttle::__log::log!($level, $($arg)+);
+        }
+    }};
+    ($interval:expr, $($arg:tt)+) => {
+        $crate::log_every!($interval, level: $crate::throttle::__log::Level::Info, $($arg)+)
+    };
+}
+
+/// Remembers when something last happened, to let it happen again only
+/// once an interval passed. log_every!() keeps one per call site.
+///
+/// Thread and ISR safe, the time is kept in an atomic u32 of milliseconds
+/// since boot. That wraps after 49 days, which is fine for intervals well
+/// below that.
+pub struct LogThrottle {
+    last_ms: AtomicU32,
+    // Unset until the first ready(), which always passes.
+    started: AtomicBool,
+}
+
+impl LogThrottle {
+    pub const fn new() -> Self {
+        Self {
+            last_ms: AtomicU32::new(0),
+            started: AtomicBool::new(false),
+        }
+    }
+
+    /// Whether `interval` passed since the last time this returned true,
+    /// restarting the interval if so.
+    pub fn ready(&self, interval: Duration) -> bool {
+        let now = EspSystemTime.now().as_millis() as u32;
+        if !self.started.swap(true, Ordering::SeqCst) {
+            self.last_ms.store(now, Ordering::SeqCst);
+            return true;
+        }
+
+        let interval = u32::try_from(interval.as_millis()).unwrap_or(u32::MAX);
+        let last = self.last_ms.load(Ordering::SeqCst);
+        // Only one of several callers passing at once gets to log.
+        now.wrapping_sub(last) >= interval
+            && self
+                .last_ms
+                .compare_exchange(last, now, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+    }
+}
+
+impl Default for LogThrottle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
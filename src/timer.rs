@@ -0,0 +1,1238 @@
+// Thin wrapper around the ESP32 general purpose hardware timers.
+//
+// The timers count up from a base clock (80 MHz APB or 40 MHz XTAL) divided
+// by a configurable divider, and fire an alarm once the counter reaches the
+// alarm value. The helpers here take care of the tick arithmetic so the
+// alarm can be expressed as a Duration or a frequency instead.
+//
+// The alarm interrupt of a timer is a level signal on every chip: it stays
+// asserted until the ISR clears it, which the driver's dispatcher does
+// before calling the callback. The original ESP32 also routes an edge
+// signal of each timer, the S2, S3 and C3 don't, and the ESP IDF 5 driver
+// only ever uses the level one ("only support Level Interrupt" from
+// timer_init() otherwise). How the CPU interrupt line reacts to it is a
+// separate choice, IsrFlags::edge().
+//
+// The HwTimer methods aren't ISR safe: the driver calls behind them take
+// its spinlock with the task level critical section, and live in flash. An
+// alarm callback has IsrTimer instead, over the driver's *_in_isr calls
+// (counter, alarm value, pause/resume), which are in IRAM and take no lock.
+// The dispatcher re-enables the alarm after the callback returns, so a
+// callback can move the alarm and have it fire again. With auto reload on
+// the counter restarts from 0 at each alarm, for a callback scheduling the
+// next alarm relative to now it has to be off, see HwTimer::rescheduling().
+//
+// How many timers there are depends on the chip: the ESP32, S2 and S3 have
+// two groups of two, the C3, C6 and H2 two groups of one and the C2 a
+// single timer. TimerId only has the ones of the chip built for, so naming
+// a missing one fails to compile rather than at runtime.
+
+use core::{
+    cell::UnsafeCell,
+    ffi::c_void,
+    fmt,
+    pin::Pin,
+    sync::atomic::{AtomicU8, Ordering},
+    time::Duration,
+};
+use std::{error::Error, sync::Arc};
+
+use esp_idf_svc::{
+    hal::interrupt,
+    sys::{
+        esp, esp_err_t, soc_periph_tg_clk_src_legacy_t_TIMER_SRC_CLK_APB,
+        soc_periph_tg_clk_src_legacy_t_TIMER_SRC_CLK_XTAL, timer_alarm_t_TIMER_ALARM_EN,
+        timer_autoreload_t_TIMER_AUTORELOAD_DIS, timer_autoreload_t_TIMER_AUTORELOAD_EN,
+        timer_config_t, timer_count_dir_t_TIMER_COUNT_DOWN, timer_count_dir_t_TIMER_COUNT_UP,
+        timer_deinit, timer_enable_intr, timer_get_alarm_value, timer_get_counter_value,
+        timer_group_get_counter_value_in_isr, timer_group_set_alarm_value_in_isr,
+        timer_group_set_counter_enable_in_isr, timer_group_t, timer_group_t_TIMER_GROUP_0,
+        timer_idx_t, timer_idx_t_TIMER_0, timer_init, timer_intr_mode_t,
+        timer_intr_mode_t_TIMER_INTR_LEVEL, timer_isr_callback_add, timer_isr_callback_remove,
+        timer_pause, timer_set_alarm_value, timer_set_auto_reload, timer_set_counter_value,
+        timer_set_divider, timer_src_clk_t, timer_start, timer_start_t, timer_start_t_TIMER_PAUSE,
+        timer_start_t_TIMER_START, EspError, ESP_INTR_FLAG_EDGE, ESP_INTR_FLAG_IRAM,
+        ESP_INTR_FLAG_LEVEL1, ESP_INTR_FLAG_SHARED, ESP_OK,
+    },
+};
+
+// ESP IDF only declares the groups and timers the chip has.
+#[cfg(not(esp32c2))]
+use esp_idf_svc::sys::timer_group_t_TIMER_GROUP_1;
+#[cfg(any(esp32, esp32s2, esp32s3))]
+use esp_idf_svc::sys::timer_idx_t_TIMER_1;
+
+/// Frequency of the APB clock the timers count from.
+pub const APB_CLK_HZ: u64 = 80_000_000;
+
+/// Frequency of the crystal oscillator, the timers' other clock source.
+pub const XTAL_CLK_HZ: u64 = 40_000_000;
+
+/// Smallest divider accepted by the timer hardware.
+pub const MIN_DIVIDER: u16 = 2;
+
+/// Largest divider accepted by the timer hardware.
+pub const MAX_DIVIDER: u16 = 65535;
+
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+// Highest interrupt level that can run a C (or Rust) handler, the levels
+// above need handlers written in assembly.
+const MAX_ISR_LEVEL: u8 = 3;
+
+// One bit per TimerId, set while a HwTimer owns that timer.
+static CLAIMED_TIMERS: AtomicU8 = AtomicU8::new(0);
+
+/// Errors returned by the timer helpers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerError {
+    /// timer_init() failed with the contained esp_err_t code.
+    InitFailed(esp_err_t),
+    /// The divider is outside of MIN_DIVIDER..=MAX_DIVIDER.
+    InvalidDivider,
+    /// The requested alarm frequency is zero, negative or not a number.
+    InvalidFrequency,
+    /// The requested alarm doesn't fit in the timer's tick range.
+    AlarmOverflow,
+    /// Another HwTimer already owns this timer.
+    AlreadyInUse(TimerId),
+    /// The timer peripheral can't raise its alarm as that IntrType.
+    UnsupportedIntrType(IntrType),
+    /// The IsrFlags ask for a combination ESP IDF can't allocate.
+    InvalidIsrFlags,
+    /// Any other timer ABI call failed.
+    Esp(EspError),
+}
+
+impl fmt::Display for TimerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimerError::InitFailed(code) => {
+                write!(f, "Failed to initialize timer.\nReturned: {}", code)
+            }
+            TimerError::InvalidDivider => write!(
+                f,
+                "Timer divider must be within {}..={}",
+                MIN_DIVIDER, MAX_DIVIDER
+            ),
+            TimerError::InvalidFrequency => write!(f, "Alarm frequency must be a positive number"),
+            TimerError::AlarmOverflow => write!(f, "Alarm overflows the timer's tick range"),
+            TimerError::AlreadyInUse(id) => write!(f, "{:?} is already in use", id),
+            TimerError::UnsupportedIntrType(intr_type) => {
+                write!(f, "Timer alarms can't be {:?} interrupts", intr_type)
+            }
+            TimerError::InvalidIsrFlags => write!(
+                f,
+                "ISR flags need a level within 1..={} and can't share an edge interrupt",
+                MAX_ISR_LEVEL
+            ),
+            TimerError::Esp(e) => write!(f, "Timer call failed: {}", e),
+        }
+    }
+}
+
+impl Error for TimerError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TimerError::Esp(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<EspError> for TimerError {
+    fn from(e: EspError) -> Self {
+        TimerError::Esp(e)
+    }
+}
+
+/// The base clock a timer counts from, before the divider.
+///
+/// APB gives twice the resolution, but when power management is enabled
+/// (CONFIG_PM_ENABLE) a running APB timer holds a lock that keeps the APB
+/// clock at 80 MHz, so dynamic frequency scaling can't lower it to save
+/// power. XTAL runs at a fixed 40 MHz whatever the CPU and APB clocks do,
+/// so it takes no lock and keeps counting at the same rate, which makes it
+/// the better pick for battery powered designs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockSource {
+    Apb,
+    Xtal,
+}
+
+impl ClockSource {
+    /// Frequency of the clock, APB_CLK_HZ or XTAL_CLK_HZ.
+    pub fn hz(self) -> u64 {
+        match self {
+            ClockSource::Apb => APB_CLK_HZ,
+            ClockSource::Xtal => XTAL_CLK_HZ,
+        }
+    }
+
+    /// Returns the divider of this clock whose counting frequency is
+    /// closest to `target_hz`.
+    ///
+    /// The actual frequency is rarely exact, use hz_for_divider() on the
+    /// result to see what the timer will really count at.
+    pub fn divider_for_hz(self, target_hz: u32) -> Result<u16, TimerError> {
+        if target_hz == 0 {
+            return Err(TimerError::InvalidDivider);
+        }
+
+        // The best divider is either side of the exact (fractional) one.
+        let base_hz = self.hz();
+        let low = (base_hz / target_hz as u64).max(1);
+        let high = low + 1;
+        let error = |divider: u64| (base_hz as f64 / divider as f64 - target_hz as f64).abs();
+        let divider = if error(high) < error(low) { high } else { low };
+
+        match u16::try_from(divider) {
+            Ok(divider) if divider >= MIN_DIVIDER => Ok(divider),
+            _ => Err(TimerError::InvalidDivider),
+        }
+    }
+
+    /// Counting frequency of a timer on this clock using `divider`.
+    pub fn hz_for_divider(self, divider: u16) -> u64 {
+        self.hz() / divider as u64
+    }
+
+    fn raw(self) -> timer_src_clk_t {
+        match self {
+            ClockSource::Apb => soc_periph_tg_clk_src_legacy_t_TIMER_SRC_CLK_APB,
+            ClockSource::Xtal => soc_periph_tg_clk_src_legacy_t_TIMER_SRC_CLK_XTAL,
+        }
+    }
+
+    // Anything but XTAL is APB, the default of the legacy timer driver.
+    fn from_raw(raw: timer_src_clk_t) -> Self {
+        if raw == soc_periph_tg_clk_src_legacy_t_TIMER_SRC_CLK_XTAL {
+            ClockSource::Xtal
+        } else {
+            ClockSource::Apb
+        }
+    }
+}
+
+/// Returns the divider of the APB clock whose counting frequency is closest
+/// to `target_hz`, see ClockSource::divider_for_hz() for XTAL.
+pub fn divider_for_hz(target_hz: u32) -> Result<u16, TimerError> {
+    ClockSource::Apb.divider_for_hz(target_hz)
+}
+
+/// Counting frequency of a timer on the APB clock using `divider`.
+pub fn hz_for_divider(divider: u16) -> u64 {
+    ClockSource::Apb.hz_for_divider(divider)
+}
+
+/// How the timer peripheral signals its alarm interrupt, the `intr_type`
+/// of `timer_config_t`.
+///
+/// Level is the one to use, on the ESP32 as on the S3 and C3: the signal
+/// stays up until the dispatcher clears it, so the alarm fires exactly once
+/// per period and can't be lost while another ISR runs. Edge is only wired
+/// on the original ESP32 and the ESP IDF 5 driver refuses it, so
+/// TimerConfigBuilder::build() rejects it rather than timer_init().
+///
+/// To have the CPU latch the interrupt on its rising edge instead, keep
+/// Level here and allocate the ISR with IsrFlags::edge(). The dispatcher
+/// clears the alarm before the callback runs, so that fires once per period
+/// too, see the alarm_rate example. A level CPU line is the safer default:
+/// an edge one misses an alarm raised again before the previous one is
+/// cleared, which only happens with periods shorter than the ISR.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntrType {
+    Level,
+    Edge,
+}
+
+impl IntrType {
+    fn raw(self) -> Result<timer_intr_mode_t, TimerError> {
+        match self {
+            IntrType::Level => Ok(timer_intr_mode_t_TIMER_INTR_LEVEL),
+            IntrType::Edge => Err(TimerError::UnsupportedIntrType(self)),
+        }
+    }
+}
+
+/// Builds a validated `timer_config_t`.
+///
+/// Defaults to a 1 MHz, auto reloading, count up timer on the APB clock,
+/// with the alarm and level interrupt enabled and the counter paused.
+#[derive(Debug, Clone, Copy)]
+pub struct TimerConfigBuilder {
+    divider: u16,
+    auto_reload: bool,
+    count_up: bool,
+    clock_source: ClockSource,
+    intr_type: IntrType,
+}
+
+impl TimerConfigBuilder {
+    pub fn new() -> Self {
+        Self {
+            divider: 80,
+            auto_reload: true,
+            count_up: true,
+            clock_source: ClockSource::Apb,
+            intr_type: IntrType::Level,
+        }
+    }
+
+    /// Divides the base clock, see ClockSource::divider_for_hz().
+    pub fn divider(mut self, divider: u16) -> Self {
+        self.divider = divider;
+        self
+    }
+
+    /// Restart counting from 0 each time the alarm fires.
+    pub fn auto_reload(mut self, auto_reload: bool) -> Self {
+        self.auto_reload = auto_reload;
+        self
+    }
+
+    /// Count up (the default) or down.
+    pub fn count_up(mut self, count_up: bool) -> Self {
+        self.count_up = count_up;
+        self
+    }
+
+    /// The clock the divider divides. Pick the divider for that same clock,
+    /// the default 80 counts at 1 MHz on APB but 500 kHz on XTAL.
+    pub fn clock_source(mut self, clock_source: ClockSource) -> Self {
+        self.clock_source = clock_source;
+        self
+    }
+
+    /// How the alarm interrupt is signalled, Level (the default) being the
+    /// only one the driver supports, see IntrType.
+    pub fn intr_type(mut self, intr_type: IntrType) -> Self {
+        self.intr_type = intr_type;
+        self
+    }
+
+    /// Returns the raw config, or TimerError::InvalidDivider if the divider
+    /// is below MIN_DIVIDER and TimerError::UnsupportedIntrType for Edge.
+    pub fn build(self) -> Result<timer_config_t, TimerError> {
+        if self.divider < MIN_DIVIDER {
+            return Err(TimerError::InvalidDivider);
+        }
+
+        Ok(timer_config_t {
+            alarm_en: timer_alarm_t_TIMER_ALARM_EN,
+            counter_en: timer_start_t_TIMER_PAUSE,
+            intr_type: self.intr_type.raw()?,
+            counter_dir: if self.count_up {
+                timer_count_dir_t_TIMER_COUNT_UP
+            } else {
+                timer_count_dir_t_TIMER_COUNT_DOWN
+            },
+            auto_reload: if self.auto_reload {
+                timer_autoreload_t_TIMER_AUTORELOAD_EN
+            } else {
+                timer_autoreload_t_TIMER_AUTORELOAD_DIS
+            },
+            clk_src: self.clock_source.raw(),
+            divider: self.divider as u32,
+        })
+    }
+}
+
+impl Default for TimerConfigBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Interrupt allocation flags for an alarm ISR, the ESP_INTR_FLAG_* bits
+/// passed to HwTimer::on_alarm_with_flags().
+///
+/// Defaults to no flags (0): a non shared, level triggered interrupt of
+/// whichever level 1-3 ESP IDF has free, with the ISR in flash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IsrFlags {
+    level: Option<u8>,
+    shared: bool,
+    edge: bool,
+    iram: bool,
+}
+
+impl IsrFlags {
+    pub fn new() -> Self {
+        Self {
+            level: None,
+            shared: false,
+            edge: false,
+            iram: false,
+        }
+    }
+
+    /// Interrupt priority, from 1 (lowest) to 3. A higher level alarm can
+    /// preempt the ISRs of the lower ones, which lowers its latency.
+    pub fn level(mut self, level: u8) -> Self {
+        self.level = Some(level);
+        self
+    }
+
+    /// Share the CPU interrupt with other peripherals. The ISRs sharing it
+    /// all have to agree on iram().
+    pub fn shared(mut self, shared: bool) -> Self {
+        self.shared = shared;
+        self
+    }
+
+    /// Edge triggered instead of level triggered, can't be shared. This is
+    /// the CPU interrupt line, the timer's own signal stays a level one,
+    /// see IntrType.
+    pub fn edge(mut self, edge: bool) -> Self {
+        self.edge = edge;
+        self
+    }
+
+    /// Keep the ISR running while the flash cache is off, e.g. during NVS
+    /// and OTA writes, instead of holding it back until they're done.
+    ///
+    /// Everything the ISR runs and touches must then be in IRAM or DRAM: the
+    /// alarm callback, anything it calls, and the data it uses. Rust code
+    /// goes to flash unless placed with `#[link_section = ".iram1"]`, so an
+    /// ordinary on_alarm() closure crashes the chip if the alarm fires
+    /// during a flash write.
+    pub fn iram(mut self, iram: bool) -> Self {
+        self.iram = iram;
+        self
+    }
+
+    /// The ESP_INTR_FLAG_* bits, or TimerError::InvalidIsrFlags for a level
+    /// outside 1..=3 or a shared edge interrupt.
+    pub fn bits(self) -> Result<i32, TimerError> {
+        let mut bits = 0;
+        if let Some(level) = self.level {
+            if !(1..=MAX_ISR_LEVEL).contains(&level) {
+                return Err(TimerError::InvalidIsrFlags);
+            }
+            // The level flags are consecutive bits, LEVEL1 being the lowest.
+            bits |= ESP_INTR_FLAG_LEVEL1 << (level - 1);
+        }
+        if self.shared && self.edge {
+            return Err(TimerError::InvalidIsrFlags);
+        }
+        if self.shared {
+            bits |= ESP_INTR_FLAG_SHARED;
+        }
+        if self.edge {
+            bits |= ESP_INTR_FLAG_EDGE;
+        }
+        if self.iram {
+            bits |= ESP_INTR_FLAG_IRAM;
+        }
+        Ok(bits as i32)
+    }
+}
+
+impl Default for IsrFlags {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The hardware timers, named by their group and index within the group.
+///
+/// Only the timers of the target chip exist: Group0Timer0 everywhere,
+/// Group1Timer0 on all but the C2, and the Timer1 of each group on the
+/// ESP32, S2 and S3 only.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerId {
+    Group0Timer0,
+    #[cfg(any(esp32, esp32s2, esp32s3))]
+    Group0Timer1,
+    #[cfg(not(esp32c2))]
+    Group1Timer0,
+    #[cfg(any(esp32, esp32s2, esp32s3))]
+    Group1Timer1,
+}
+
+impl TimerId {
+    pub fn group(self) -> timer_group_t {
+        match self {
+            TimerId::Group0Timer0 => timer_group_t_TIMER_GROUP_0,
+            #[cfg(any(esp32, esp32s2, esp32s3))]
+            TimerId::Group0Timer1 => timer_group_t_TIMER_GROUP_0,
+            #[cfg(not(esp32c2))]
+            TimerId::Group1Timer0 => timer_group_t_TIMER_GROUP_1,
+            #[cfg(any(esp32, esp32s2, esp32s3))]
+            TimerId::Group1Timer1 => timer_group_t_TIMER_GROUP_1,
+        }
+    }
+
+    pub fn index(self) -> timer_idx_t {
+        match self {
+            TimerId::Group0Timer0 => timer_idx_t_TIMER_0,
+            #[cfg(any(esp32, esp32s2, esp32s3))]
+            TimerId::Group0Timer1 => timer_idx_t_TIMER_1,
+            #[cfg(not(esp32c2))]
+            TimerId::Group1Timer0 => timer_idx_t_TIMER_0,
+            #[cfg(any(esp32, esp32s2, esp32s3))]
+            TimerId::Group1Timer1 => timer_idx_t_TIMER_1,
+        }
+    }
+
+    fn mask(self) -> u8 {
+        1 << self as u8
+    }
+
+    // Marks the timer as owned, failing if it already was.
+    fn claim(self) -> Result<(), TimerError> {
+        if CLAIMED_TIMERS.fetch_or(self.mask(), Ordering::SeqCst) & self.mask() != 0 {
+            return Err(TimerError::AlreadyInUse(self));
+        }
+        Ok(())
+    }
+
+    fn release(self) {
+        CLAIMED_TIMERS.fetch_and(!self.mask(), Ordering::SeqCst);
+    }
+}
+
+// Closure run from the timer ISR when the alarm fires.
+type AlarmCallback<'d> = Box<dyn FnMut() + Send + 'd>;
+
+// Alarms fired, counted by the ISR of on_alarm(). The chips have no 64-bit
+// atomics, so the count is guarded by a critical section instead.
+struct FireCount {
+    lock: interrupt::IsrCriticalSection,
+    // Only touched with `lock` held.
+    count: UnsafeCell<u64>,
+}
+
+// SAFETY: the count is only accessed with the critical section held.
+unsafe impl Sync for FireCount {}
+
+impl FireCount {
+    fn new() -> Self {
+        Self {
+            lock: interrupt::IsrCriticalSection::new(),
+            count: UnsafeCell::new(0),
+        }
+    }
+
+    fn increment(&self) {
+        let _guard = self.lock.enter();
+        // SAFETY: the critical section is held.
+        unsafe { *self.count.get() += 1 };
+    }
+
+    fn get(&self) -> u64 {
+        let _guard = self.lock.enter();
+        // SAFETY: the critical section is held.
+        unsafe { *self.count.get() }
+    }
+}
+
+/// State for an alarm ISR, on the heap so it stays put for as long as the
+/// HwTimer it's handed to, which owns it from then on.
+///
+/// Passing the address of a local as the ISR's argument, as
+/// timer_isr_callback_add() invites, is unsound: nothing ties the local to
+/// the ISR, so the code around it can still move it, read and write it
+/// while the ISR holds a `&mut` to it, or return and drop it with the ISR
+/// still registered, leaving it a dangling pointer. With an IsrContext the
+/// HwTimer unregisters the ISR before freeing the state, and nothing else
+/// can reach it meanwhile.
+pub struct IsrContext<T> {
+    value: Box<T>,
+}
+
+impl<T: Send> IsrContext<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            value: Box::new(value),
+        }
+    }
+
+    /// The pointer to pass as the ISR argument. Moving the IsrContext
+    /// doesn't move the value, so it stays valid.
+    pub fn as_arg(&mut self) -> *mut c_void {
+        &mut *self.value as *mut T as *mut c_void
+    }
+
+    /// The value behind an ISR argument.
+    ///
+    /// # Safety
+    ///
+    /// `arg` must come from as_arg() on an IsrContext<T> still alive, and
+    /// nothing else may use the value at the same time, which holds for
+    /// the ISR of an HwTimer owning it.
+    pub unsafe fn from_arg<'a>(arg: *mut c_void) -> &'a mut T {
+        // SAFETY: the caller guarantees the pointer is a live, unaliased T.
+        unsafe { &mut *(arg as *mut T) }
+    }
+}
+
+/// The ISR safe side of an HwTimer, for alarm callbacks driving their own
+/// timer, from HwTimer::isr_timer().
+///
+/// Its methods are safe to call from the timer's ISR, or anywhere else.
+/// They don't lock against the HwTimer methods the way those do against
+/// each other, so only use them from the ISR while the HwTimer is left
+/// alone (it's usually only kept to be dropped).
+#[derive(Debug, Clone, Copy)]
+pub struct IsrTimer {
+    id: TimerId,
+    tick_hz: u64,
+}
+
+impl IsrTimer {
+    /// Counter ticks per second, see HwTimer::tick_hz().
+    pub fn tick_hz(&self) -> u64 {
+        self.tick_hz
+    }
+
+    /// The counter value, in ticks.
+    pub fn counter(&self) -> u64 {
+        // SAFETY: timer_group_get_counter_value_in_isr() is an ESP32 ABI
+        // call meant for ISRs.
+        unsafe { timer_group_get_counter_value_in_isr(self.id.group(), self.id.index()) }
+    }
+
+    /// Sets the alarm to a raw counter value. Called from the alarm
+    /// callback, it's the next alarm.
+    pub fn set_alarm(&self, ticks: u64) {
+        // SAFETY: timer_group_set_alarm_value_in_isr() is an ESP32 ABI call
+        // meant for ISRs.
+        unsafe { timer_group_set_alarm_value_in_isr(self.id.group(), self.id.index(), ticks) };
+    }
+
+    /// Sets the alarm to fire `dur` from now, i.e. the counter plus `dur`.
+    /// Needs auto reload off to fire at that time.
+    ///
+    /// Fails with TimerError::AlarmOverflow if that's past the 64-bit tick
+    /// range, leaving the alarm as it was.
+    pub fn set_alarm_in(&self, dur: Duration) -> Result<(), TimerError> {
+        let ticks = duration_to_ticks(dur, self.tick_hz)
+            .and_then(|ticks| self.counter().checked_add(ticks))
+            .ok_or(TimerError::AlarmOverflow)?;
+        self.set_alarm(ticks);
+        Ok(())
+    }
+
+    /// Stops the counter, so no alarm fires until resume().
+    pub fn pause(&self) {
+        self.set_counting(timer_start_t_TIMER_PAUSE);
+    }
+
+    /// Lets the counter count again after pause().
+    pub fn resume(&self) {
+        self.set_counting(timer_start_t_TIMER_START);
+    }
+
+    fn set_counting(&self, counting: timer_start_t) {
+        // SAFETY: timer_group_set_counter_enable_in_isr() is an ESP32 ABI
+        // call meant for ISRs.
+        unsafe {
+            timer_group_set_counter_enable_in_isr(self.id.group(), self.id.index(), counting)
+        };
+    }
+}
+
+/// An initialized hardware timer.
+///
+/// Only one HwTimer can exist per TimerId at a time. The lifetime `'d`
+/// bounds whatever a callback from on_alarm_nonstatic() borrows, the safe
+/// on_alarm() and the like take `'static` ones.
+pub struct HwTimer<'d> {
+    id: TimerId,
+    clock_source: ClockSource,
+    divider: u32,
+    callback: Option<Pin<Box<AlarmCallback<'d>>>>,
+    // The IsrContext of an on_alarm_raw() ISR, only dropped once the ISR
+    // is unregistered.
+    context: Option<Box<dyn Send>>,
+    fires: Arc<FireCount>,
+}
+
+impl<'d> HwTimer<'d> {
+    /// Initializes the timer with `config` and resets its counter to 0.
+    ///
+    /// Fails with TimerError::AlreadyInUse if another HwTimer owns `id`.
+    pub fn new(id: TimerId, config: timer_config_t) -> Result<Self, TimerError> {
+        id.claim()?;
+
+        // SAFETY: timer_init() is an ESP32 ABI call.
+        let result =
+            unsafe { timer_init(id.group(), id.index(), &config as *const timer_config_t) };
+        if result != ESP_OK {
+            id.release();
+            return Err(TimerError::InitFailed(result));
+        };
+
+        // From here on dropping the timer deinitializes and releases it.
+        let mut timer = Self {
+            id,
+            clock_source: ClockSource::from_raw(config.clk_src),
+            divider: config.divider,
+            callback: None,
+            context: None,
+            fires: Arc::new(FireCount::new()),
+        };
+        timer.set_counter(0)?;
+
+        Ok(timer)
+    }
+
+    /// Starts a timer that runs `callback` once, `after` from now, and then
+    /// stops counting.
+    ///
+    /// The timer counts at 1 MHz with auto reload off, so the alarm isn't
+    /// re-armed once it fires. The returned timer must be kept alive until
+    /// the callback has run.
+    pub fn oneshot<F>(id: TimerId, after: Duration, callback: F) -> Result<Self, TimerError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let config = TimerConfigBuilder::new()
+            .divider(divider_for_hz(1_000_000)?)
+            .auto_reload(false)
+            .build()?;
+        let mut timer = Self::new(id, config)?;
+        timer.set_alarm_after(after)?;
+
+        let mut callback = Some(callback);
+        let isr = timer.isr_timer();
+        timer.on_alarm(move || {
+            isr.pause();
+            if let Some(callback) = callback.take() {
+                callback();
+            }
+        })?;
+        timer.enable_interrupt()?;
+        timer.start()?;
+
+        Ok(timer)
+    }
+
+    /// Starts a timer that runs `callback` `first` from now, and then again
+    /// after whatever interval the callback returns, for events at varying
+    /// intervals. Returning None stops the timer.
+    ///
+    /// The timer counts at 1 MHz with auto reload off, the callback's
+    /// interval counts from when it runs. Interrupt context restrictions
+    /// apply to the callback as with on_alarm(). An interval past the
+    /// 64-bit tick range stops the timer too.
+    pub fn rescheduling<F>(
+        id: TimerId,
+        first: Duration,
+        mut callback: F,
+    ) -> Result<Self, TimerError>
+    where
+        F: FnMut() -> Option<Duration> + Send + 'static,
+    {
+        let config = TimerConfigBuilder::new()
+            .divider(divider_for_hz(1_000_000)?)
+            .auto_reload(false)
+            .build()?;
+        let mut timer = Self::new(id, config)?;
+        timer.set_alarm_after(first)?;
+
+        let isr = timer.isr_timer();
+        timer.on_alarm(move || {
+            let scheduled = callback().map(|next| isr.set_alarm_in(next));
+            if !matches!(scheduled, Some(Ok(()))) {
+                isr.pause();
+            }
+        })?;
+        timer.enable_interrupt()?;
+        timer.start()?;
+
+        Ok(timer)
+    }
+
+    /// Starts a timer that counts microseconds and never fires, for timing
+    /// events by reading counter().
+    pub fn free_running(id: TimerId) -> Result<Self, TimerError> {
+        let config = TimerConfigBuilder::new()
+            .divider(divider_for_hz(1_000_000)?)
+            .auto_reload(false)
+            .build()?;
+        let mut timer = Self::new(id, config)?;
+        timer.start()?;
+
+        Ok(timer)
+    }
+
+    pub fn id(&self) -> TimerId {
+        self.id
+    }
+
+    pub fn group(&self) -> timer_group_t {
+        self.id.group()
+    }
+
+    pub fn index(&self) -> timer_idx_t {
+        self.id.index()
+    }
+
+    /// The base clock the counter runs from.
+    pub fn clock_source(&self) -> ClockSource {
+        self.clock_source
+    }
+
+    /// Number of counter ticks per second with the configured clock source
+    /// and divider.
+    pub fn tick_hz(&self) -> u64 {
+        self.clock_source.hz() / self.divider as u64
+    }
+
+    /// Divider applied to the clock source.
+    pub fn divider(&self) -> u16 {
+        self.divider as u16
+    }
+
+    /// Changes the divider, also while the timer runs, e.g. to sweep the
+    /// alarm frequency. Fails with TimerError::InvalidDivider if it's below
+    /// MIN_DIVIDER.
+    ///
+    /// The counter and alarm values are kept as raw ticks, which now last
+    /// a different time: elapsed() reads the ticks counted so far at the
+    /// new rate and the alarm fires after the same number of ticks, not
+    /// the same time. Set them again as needed.
+    pub fn set_divider(&mut self, divider: u16) -> Result<(), TimerError> {
+        if divider < MIN_DIVIDER {
+            return Err(TimerError::InvalidDivider);
+        }
+
+        // SAFETY: timer_set_divider() is an ESP32 ABI call.
+        esp!(unsafe { timer_set_divider(self.group(), self.index(), divider as u32) })?;
+        self.divider = divider as u32;
+        Ok(())
+    }
+
+    /// Sets the counter to a raw tick value.
+    pub fn set_counter(&mut self, ticks: u64) -> Result<(), TimerError> {
+        // SAFETY: timer_set_counter_value() is an ESP32 ABI call.
+        esp!(unsafe { timer_set_counter_value(self.group(), self.index(), ticks) })?;
+        Ok(())
+    }
+
+    /// Current raw tick value of the counter.
+    pub fn counter(&self) -> Result<u64, TimerError> {
+        let mut ticks = 0;
+        // SAFETY: timer_get_counter_value() is an ESP32 ABI call writing to `ticks`.
+        esp!(unsafe { timer_get_counter_value(self.group(), self.index(), &mut ticks) })?;
+        Ok(ticks)
+    }
+
+    /// Time the counter represents, i.e. how long the timer has been
+    /// counting since it started from 0 (or since the last auto reload).
+    pub fn elapsed(&self) -> Result<Duration, TimerError> {
+        Ok(ticks_to_duration(self.counter()?, self.tick_hz()))
+    }
+
+    /// Returns elapsed() and restarts the count from 0, to time consecutive
+    /// laps like a stopwatch.
+    pub fn reset_and_elapsed(&mut self) -> Result<Duration, TimerError> {
+        let elapsed = self.elapsed()?;
+        self.set_counter(0)?;
+        Ok(elapsed)
+    }
+
+    /// Busy waits for `micros` microseconds, measured on the counter.
+    ///
+    /// The timer must be counting, e.g. one from free_running(). This spins
+    /// the current core without yielding, so keep it for the short waits
+    /// of bit-banged protocols and use thread::sleep() for anything longer
+    /// than a tick.
+    ///
+    /// Not ISR safe, like the other HwTimer methods: it reads the counter
+    /// through timer_get_counter_value(), which takes the driver's lock. An
+    /// alarm callback can spin on IsrTimer::counter() instead.
+    ///
+    /// With auto reload on, an alarm restarting the counter mid-wait throws
+    /// the wait off, as the distance to the start jumps: it may end early,
+    /// or never for a wait longer than the alarm period. Wait on a timer
+    /// without auto reload, like free_running() ones.
+    pub fn delay_us(&self, micros: u32) -> Result<(), TimerError> {
+        self.delay(Duration::from_micros(micros.into()))
+    }
+
+    /// Busy waits for `nanos` nanoseconds, see delay_us().
+    ///
+    /// The wait is rounded up to whole ticks (1µs with free_running(),
+    /// 25ns at MIN_DIVIDER on APB), and reading the counter itself takes a few
+    /// hundred nanoseconds, so very short delays come out longer.
+    pub fn delay_ns(&self, nanos: u32) -> Result<(), TimerError> {
+        self.delay(Duration::from_nanos(nanos.into()))
+    }
+
+    fn delay(&self, dur: Duration) -> Result<(), TimerError> {
+        // Rounded up so we never return early.
+        let ticks =
+            ((dur.as_nanos() * self.tick_hz() as u128 + NANOS_PER_SEC - 1) / NANOS_PER_SEC) as u64;
+        let start = self.counter()?;
+        // abs_diff() so this also works when counting down. It can't tell
+        // an auto reload back to 0 from a long wait, see delay_us().
+        while self.counter()?.abs_diff(start) < ticks {}
+        Ok(())
+    }
+
+    /// Sets the alarm to a raw counter value.
+    pub fn set_alarm(&mut self, ticks: u64) -> Result<(), TimerError> {
+        // SAFETY: timer_set_alarm_value() is an ESP32 ABI call.
+        esp!(unsafe { timer_set_alarm_value(self.group(), self.index(), ticks) })?;
+        Ok(())
+    }
+
+    /// Current alarm value in raw ticks, e.g. to move the alarm relative to
+    /// where it is with set_alarm(timer.alarm_value()? + ticks).
+    pub fn alarm_value(&self) -> Result<u64, TimerError> {
+        let mut ticks = 0;
+        // SAFETY: timer_get_alarm_value() is an ESP32 ABI call writing to `ticks`.
+        esp!(unsafe { timer_get_alarm_value(self.group(), self.index(), &mut ticks) })?;
+        Ok(ticks)
+    }
+
+    /// Sets the alarm to fire `dur` after the counter starts from 0.
+    ///
+    /// Returns an error if `dur` doesn't fit in the 64-bit tick range.
+    pub fn set_alarm_after(&mut self, dur: Duration) -> Result<(), TimerError> {
+        let ticks = duration_to_ticks(dur, self.tick_hz()).ok_or(TimerError::AlarmOverflow)?;
+        self.set_alarm(ticks)
+    }
+
+    /// Sets the alarm so that, with auto reload enabled, it fires `freq`
+    /// times per second.
+    pub fn set_alarm_hz(&mut self, freq: f32) -> Result<(), TimerError> {
+        if !freq.is_finite() || freq <= 0.0 {
+            return Err(TimerError::InvalidFrequency);
+        }
+
+        let ticks = (self.tick_hz() as f64 / freq as f64).round();
+        if ticks < 1.0 || ticks >= u64::MAX as f64 {
+            return Err(TimerError::AlarmOverflow);
+        }
+        self.set_alarm(ticks as u64)
+    }
+
+    /// Turns auto reload on or off while the timer runs, switching it
+    /// between periodic and one-shot without reinitializing it.
+    ///
+    /// Takes effect on the next alarm: with auto reload off that alarm still
+    /// fires, but the counter then keeps counting past the alarm value
+    /// instead of restarting from 0, so it's the last one.
+    pub fn set_auto_reload(&mut self, enable: bool) -> Result<(), TimerError> {
+        let reload = if enable {
+            timer_autoreload_t_TIMER_AUTORELOAD_EN
+        } else {
+            timer_autoreload_t_TIMER_AUTORELOAD_DIS
+        };
+        // SAFETY: timer_set_auto_reload() is an ESP32 ABI call.
+        esp!(unsafe { timer_set_auto_reload(self.group(), self.index(), reload) })?;
+        Ok(())
+    }
+
+    /// Enables the alarm interrupt of this timer.
+    pub fn enable_interrupt(&mut self) -> Result<(), TimerError> {
+        // SAFETY: timer_enable_intr() is an ESP32 ABI call.
+        esp!(unsafe { timer_enable_intr(self.group(), self.index()) })?;
+        Ok(())
+    }
+
+    /// Starts (or resumes) counting.
+    pub fn start(&mut self) -> Result<(), TimerError> {
+        // SAFETY: timer_start() is an ESP32 ABI call.
+        esp!(unsafe { timer_start(self.group(), self.index()) })?;
+        Ok(())
+    }
+
+    /// Pauses counting, keeping the current counter value.
+    pub fn pause(&mut self) -> Result<(), TimerError> {
+        // SAFETY: timer_pause() is an ESP32 ABI call.
+        esp!(unsafe { timer_pause(self.group(), self.index()) })?;
+        Ok(())
+    }
+
+    /// Runs `f` from the timer's ISR every time the alarm fires, replacing
+    /// any previously registered callback.
+    ///
+    /// The closure owns what it uses, e.g. a `move` of the led or an Arc to
+    /// shared state: dropping the timer unregisters the ISR, but a timer
+    /// that's leaked (mem::forget(), an Rc cycle) is never dropped, and its
+    /// ISR would keep running on borrows long gone. on_alarm_nonstatic()
+    /// takes a borrowing closure, leaving that to the caller. It runs in
+    /// interrupt context, so it should be short and must not block, allocate
+    /// or log.
+    ///
+    /// Each timer keeps its own closure and registers it as the argument of
+    /// its own ISR, so timers of different TimerIds run their callbacks side
+    /// by side, each on its own captured state.
+    pub fn on_alarm<F>(&mut self, f: F) -> Result<(), TimerError>
+    where
+        F: FnMut() + Send + 'static,
+    {
+        self.on_alarm_with_flags(f, IsrFlags::new())
+    }
+
+    /// Like on_alarm(), but the closure may borrow locals (e.g. `&mut led`)
+    /// that outlive the timer.
+    ///
+    /// # Safety
+    ///
+    /// The timer must be dropped before anything the closure borrows: it
+    /// may not be leaked with mem::forget(), an Rc cycle or the like, which
+    /// would leave the ISR running on dangling borrows.
+    pub unsafe fn on_alarm_nonstatic<F>(&mut self, f: F) -> Result<(), TimerError>
+    where
+        F: FnMut() + Send + 'd,
+    {
+        self.register_callback(f, IsrFlags::new())
+    }
+
+    /// Like on_alarm(), but allocates the interrupt with `flags`, e.g. at a
+    /// higher level for latency sensitive work.
+    ///
+    /// Fails with TimerError::InvalidIsrFlags for flags ESP IDF would
+    /// reject, see IsrFlags::bits().
+    pub fn on_alarm_with_flags<F>(&mut self, f: F, flags: IsrFlags) -> Result<(), TimerError>
+    where
+        F: FnMut() + Send + 'static,
+    {
+        self.register_callback(f, flags)
+    }
+
+    // Registers `f` as the alarm callback. Only sound if `f` outlives the
+    // timer's ISR, which the callers see to.
+    fn register_callback<F>(&mut self, mut f: F, flags: IsrFlags) -> Result<(), TimerError>
+    where
+        F: FnMut() + Send + 'd,
+    {
+        let flags = flags.bits()?;
+        self.remove_callback()?;
+
+        let fires = self.fires.clone();
+        let counted = move || {
+            fires.increment();
+            f();
+        };
+
+        // The closure is boxed twice: the outer box gives the ISR a thin,
+        // stable pointer to the (fat) inner trait object, and is only dropped
+        // after the ISR has been unregistered.
+        let mut callback: Pin<Box<AlarmCallback<'d>>> = Box::pin(Box::new(counted));
+        let arg = callback.as_mut().get_mut() as *mut AlarmCallback<'d> as *mut c_void;
+
+        // SAFETY: timer_isr_callback_add() is an ESP32 ABI call. `arg` stays
+        // valid until remove_callback() unregisters it.
+        esp!(unsafe {
+            timer_isr_callback_add(
+                self.group(),
+                self.index(),
+                Some(alarm_trampoline),
+                arg,
+                flags,
+            )
+        })?;
+        self.callback = Some(callback);
+
+        Ok(())
+    }
+
+    /// Like on_alarm(), with `context` moved to the heap and the callback
+    /// getting `&mut` to it on every alarm, e.g. for a struct of several
+    /// pins the ISR drives.
+    pub fn on_alarm_with_context<T, F>(
+        &mut self,
+        context: IsrContext<T>,
+        mut f: F,
+    ) -> Result<(), TimerError>
+    where
+        T: Send + 'static,
+        F: FnMut(&mut T) + Send + 'static,
+    {
+        let mut value = context.value;
+        self.on_alarm(move || f(&mut value))
+    }
+
+    /// Registers `isr` as the timer's ISR, with `context` as its argument,
+    /// for ISRs written as plain `extern "C"` functions. The timer keeps
+    /// `context` until the ISR is replaced or the timer dropped. Its fires
+    /// aren't counted in fire_count().
+    ///
+    /// `isr` should get the context back with IsrContext::from_arg(), and
+    /// return whether it woke a higher priority task. It must not block,
+    /// allocate or log.
+    pub fn on_alarm_raw<T>(
+        &mut self,
+        isr: unsafe extern "C" fn(*mut c_void) -> bool,
+        mut context: IsrContext<T>,
+        flags: IsrFlags,
+    ) -> Result<(), TimerError>
+    where
+        T: Send + 'static,
+    {
+        let flags = flags.bits()?;
+        self.remove_callback()?;
+
+        // SAFETY: timer_isr_callback_add() is an ESP32 ABI call. The
+        // context is kept until remove_callback() unregisters the ISR.
+        esp!(unsafe {
+            timer_isr_callback_add(
+                self.group(),
+                self.index(),
+                Some(isr),
+                context.as_arg(),
+                flags,
+            )
+        })?;
+        self.context = Some(context.value);
+
+        Ok(())
+    }
+
+    /// What the alarm callback may use of this timer, see IsrTimer.
+    pub fn isr_timer(&self) -> IsrTimer {
+        IsrTimer {
+            id: self.id,
+            tick_hz: self.tick_hz(),
+        }
+    }
+
+    /// How many times the alarm has fired since new(), over every callback
+    /// registered with on_alarm() and the like.
+    ///
+    /// Counted by the timer before running the callback. Comparing it with
+    /// the time elapsed shows missed or extra fires.
+    pub fn fire_count(&self) -> u64 {
+        self.fires.get()
+    }
+
+    // Unregisters the ISR before dropping the closure or context it points
+    // to.
+    fn remove_callback(&mut self) -> Result<(), TimerError> {
+        if self.callback.is_some() || self.context.is_some() {
+            // SAFETY: timer_isr_callback_remove() is an ESP32 ABI call.
+            esp!(unsafe { timer_isr_callback_remove(self.group(), self.index()) })?;
+            self.callback = None;
+            self.context = None;
+        }
+        Ok(())
+    }
+}
+
+// ISR registered by register_callback(), forwards to the boxed closure
+// in `arg`.
+unsafe extern "C" fn alarm_trampoline(arg: *mut c_void) -> bool {
+    // SAFETY: `arg` is the pointer registered in register_callback(),
+    // which the timer keeps alive for as long as this ISR is registered.
+    let callback = unsafe { &mut *(arg as *mut AlarmCallback) };
+
+    // The return value tells the ISR dispatcher whether a higher priority
+    // task was woken and a context switch is needed on exit.
+    interrupt::with_isr_yield_signal(callback)
+}
+
+impl Drop for HwTimer<'_> {
+    fn drop(&mut self) {
+        let _ = self.remove_callback();
+
+        // SAFETY: timer_deinit() is an ESP32 ABI call. The timer was
+        // initialized in new() so deinit can't fail on a bad argument.
+        unsafe { timer_deinit(self.group(), self.index()) };
+        self.id.release();
+    }
+}
+
+// Converts a duration into counter ticks, or None if it overflows u64.
+fn duration_to_ticks(dur: Duration, tick_hz: u64) -> Option<u64> {
+    let ticks = dur.as_nanos().checked_mul(tick_hz as u128)? / NANOS_PER_SEC;
+    u64::try_from(ticks).ok()
+}
+
+// Converts counter ticks into the time they take at `tick_hz`.
+pub(crate) fn ticks_to_duration(ticks: u64, tick_hz: u64) -> Duration {
+    let nanos = ticks as u128 * NANOS_PER_SEC / tick_hz as u128;
+    Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn divider_for_50_khz() {
+        assert_eq!(divider_for_hz(50_000), Ok(1600));
+        assert_eq!(hz_for_divider(1600), 50_000);
+    }
+
+    #[test]
+    fn divider_for_4_mhz() {
+        assert_eq!(divider_for_hz(4_000_000), Ok(20));
+        assert_eq!(hz_for_divider(20), 4_000_000);
+    }
+
+    #[test]
+    fn divider_rounds_to_the_nearest_frequency() {
+        // 80 MHz / 3 MHz is 26.7, 27 gives 2.963 MHz, 26 gives 3.077 MHz.
+        assert_eq!(divider_for_hz(3_000_000), Ok(27));
+    }
+
+    #[test]
+    fn divider_on_the_crystal_clock() {
+        assert_eq!(ClockSource::Xtal.divider_for_hz(50_000), Ok(800));
+        assert_eq!(ClockSource::Xtal.hz_for_divider(800), 50_000);
+    }
+
+    #[test]
+    fn divider_rejects_frequencies_too_high() {
+        // Divider 2 is the smallest, 1 would be needed.
+        assert_eq!(divider_for_hz(40_000_000), Ok(MIN_DIVIDER));
+        assert_eq!(divider_for_hz(80_000_000), Err(TimerError::InvalidDivider));
+        assert_eq!(divider_for_hz(u32::MAX), Err(TimerError::InvalidDivider));
+    }
+
+    #[test]
+    fn divider_rejects_frequencies_too_low() {
+        // 80 MHz / 65535 is 1220.7 Hz, the slowest the timer counts.
+        assert_eq!(divider_for_hz(1221), Ok(65520));
+        assert_eq!(divider_for_hz(1220), Err(TimerError::InvalidDivider));
+        assert_eq!(divider_for_hz(1), Err(TimerError::InvalidDivider));
+        assert_eq!(divider_for_hz(0), Err(TimerError::InvalidDivider));
+    }
+
+    #[test]
+    fn divider_sweep_counts_a_second() {
+        let one_second = Duration::from_secs(1);
+        for divider in [MIN_DIVIDER, 3, 80, 1600, 40_000, MAX_DIVIDER] {
+            let tick_hz = hz_for_divider(divider);
+            assert!(tick_hz * divider as u64 <= APB_CLK_HZ);
+            assert!(tick_hz * divider as u64 > APB_CLK_HZ - divider as u64);
+
+            let ticks = duration_to_ticks(one_second, tick_hz);
+            assert_eq!(ticks, Some(tick_hz), "divider {}", divider);
+            assert_eq!(ticks_to_duration(tick_hz, tick_hz), one_second);
+        }
+    }
+
+    #[test]
+    fn divider_change_stretches_counted_ticks() {
+        // What set_divider() does to the ticks already counted.
+        assert_eq!(
+            ticks_to_duration(1000, hz_for_divider(80)),
+            Duration::from_millis(1)
+        );
+        assert_eq!(
+            ticks_to_duration(1000, hz_for_divider(160)),
+            Duration::from_millis(2)
+        );
+    }
+
+    #[test]
+    fn divider_below_the_minimum_is_rejected() {
+        for divider in 0..MIN_DIVIDER {
+            let config = TimerConfigBuilder::new().divider(divider).build();
+            assert_eq!(config.err(), Some(TimerError::InvalidDivider));
+        }
+        assert!(TimerConfigBuilder::new()
+            .divider(MIN_DIVIDER)
+            .build()
+            .is_ok());
+    }
+}
@@ -0,0 +1,84 @@
+// Serial port setup and helpers on top of the HAL's UartDriver.
+
+use esp_idf_svc::{
+    hal::{
+        uart::{
+            config::{Config, DataBits, Parity, StopBits},
+            UartDriver,
+        },
+        units::Hertz,
+    },
+    sys::EspError,
+};
+
+/// The serial line settings of a UartDriver.
+///
+/// Defaults to 115200 baud, 8 data bits, no parity and 1 stop bit (8N1).
+#[derive(Debug, Clone, Copy)]
+pub struct UartConfig {
+    baudrate: u32,
+    data_bits: DataBits,
+    parity: Parity,
+    stop_bits: StopBits,
+}
+
+impl UartConfig {
+    pub fn new() -> Self {
+        Self {
+            baudrate: 115_200,
+            data_bits: DataBits::DataBits8,
+            parity: Parity::ParityNone,
+            stop_bits: StopBits::STOP1,
+        }
+    }
+
+    pub fn baudrate(mut self, baudrate: u32) -> Self {
+        self.baudrate = baudrate;
+        self
+    }
+
+    pub fn data_bits(mut self, data_bits: DataBits) -> Self {
+        self.data_bits = data_bits;
+        self
+    }
+
+    pub fn parity(mut self, parity: Parity) -> Self {
+        self.parity = parity;
+        self
+    }
+
+    pub fn stop_bits(mut self, stop_bits: StopBits) -> Self {
+        self.stop_bits = stop_bits;
+        self
+    }
+
+    /// Returns the config to pass to UartDriver::new().
+    pub fn build(self) -> Config {
+        let config = Config::new()
+            .baudrate(Hertz(self.baudrate))
+            .data_bits(self.data_bits)
+            .stop_bits(self.stop_bits);
+
+        match self.parity {
+            Parity::ParityNone => config.parity_none(),
+            Parity::ParityEven => config.parity_even(),
+            Parity::ParityOdd => config.parity_odd(),
+        }
+    }
+}
+
+impl Default for UartConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Writes all of `bytes`, UartDriver::write() may take only part of them
+/// when the TX buffer is full.
+pub fn write_all(uart: &UartDriver, mut bytes: &[u8]) -> Result<(), EspError> {
+    while !bytes.is_empty() {
+        let written = uart.write(bytes)?;
+        bytes = &bytes[written..];
+    }
+    Ok(())
+}
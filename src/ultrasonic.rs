@@ -0,0 +1,151 @@
+// Driver for the HC-SR04 ultrasonic distance sensor.
+//
+// A 10µs pulse on TRIG makes the sensor send a burst of ultrasound, then
+// hold ECHO high until the echo comes back. The width of that pulse, read
+// off a free running hardware timer, is the round trip time of the sound.
+
+use core::fmt;
+use std::{error::Error, thread, time::Duration};
+
+use esp_idf_svc::{
+    hal::{
+        delay::Ets,
+        gpio::{AnyInputPin, AnyOutputPin, Input, InputPin, Output, OutputPin, PinDriver},
+        peripheral::Peripheral,
+    },
+    sys::EspError,
+};
+
+use crate::timer::{HwTimer, TimerError, TimerId};
+
+/// Width of the trigger pulse.
+pub const TRIGGER_PULSE_US: u32 = 10;
+
+/// Farthest the HC-SR04 can measure.
+pub const MAX_RANGE_CM: f32 = 400.0;
+
+// Speed of sound at 20°C.
+const SOUND_CM_PER_US: f32 = 0.0343;
+
+// Round trip time to MAX_RANGE_CM and back, with some margin.
+const ECHO_TIMEOUT_US: u64 = (MAX_RANGE_CM * 2.0 / SOUND_CM_PER_US) as u64 + 2_000;
+
+// Time for the echoes of the previous measurement to die out.
+const SETTLE_TIME: Duration = Duration::from_millis(60);
+
+/// Why a measurement failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UltrasonicError {
+    /// No echo within the time sound takes to travel MAX_RANGE_CM and back.
+    Timeout,
+    /// Driving or reading the sensor's pins failed.
+    Gpio(EspError),
+    /// The timer used to measure the echo couldn't be set up or read.
+    Timer(TimerError),
+}
+
+impl fmt::Display for UltrasonicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UltrasonicError::Timeout => write!(f, "No echo, nothing within range"),
+            UltrasonicError::Gpio(e) => write!(f, "Trigger/echo pin call failed: {}", e),
+            UltrasonicError::Timer(e) => write!(f, "Timer failed: {}", e),
+        }
+    }
+}
+
+impl Error for UltrasonicError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            UltrasonicError::Gpio(e) => Some(e),
+            UltrasonicError::Timer(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<EspError> for UltrasonicError {
+    fn from(e: EspError) -> Self {
+        UltrasonicError::Gpio(e)
+    }
+}
+
+impl From<TimerError> for UltrasonicError {
+    fn from(e: TimerError) -> Self {
+        UltrasonicError::Timer(e)
+    }
+}
+
+/// Converts the width of an echo pulse into the distance to the obstacle.
+pub fn echo_to_cm(echo_us: u64) -> f32 {
+    echo_us as f32 * SOUND_CM_PER_US / 2.0
+}
+
+/// An HC-SR04 on a trigger and an echo pin, with a hardware timer to time
+/// the echo.
+///
+/// The HC-SR04 runs on 5V, put a divider on ECHO so it doesn't exceed 3.3V.
+pub struct Ultrasonic<'d> {
+    trigger: PinDriver<'d, AnyOutputPin, Output>,
+    echo: PinDriver<'d, AnyInputPin, Input>,
+    timer: HwTimer<'d>,
+}
+
+impl<'d> Ultrasonic<'d> {
+    pub fn new(
+        trigger: impl Peripheral<P = impl OutputPin> + 'd,
+        echo: impl Peripheral<P = impl InputPin> + 'd,
+        timer: TimerId,
+    ) -> Result<Self, UltrasonicError> {
+        let mut trigger = PinDriver::output(trigger.into_ref().map_into::<AnyOutputPin>())?;
+        trigger.set_low()?;
+
+        Ok(Self {
+            trigger,
+            echo: PinDriver::input(echo.into_ref().map_into::<AnyInputPin>())?,
+            timer: HwTimer::free_running(timer)?,
+        })
+    }
+
+    /// Measures the distance to the nearest obstacle, in centimeters.
+    ///
+    /// Blocks for up to ~85ms, so measure at most about 10 times a second.
+    pub fn distance_cm(&mut self) -> Result<f32, UltrasonicError> {
+        let echo_us = self.echo_us();
+        // Wait out stray echoes before the next trigger, even after a timeout.
+        thread::sleep(SETTLE_TIME);
+
+        echo_us.map(echo_to_cm)
+    }
+
+    // Triggers a measurement and returns the width of the echo pulse.
+    fn echo_us(&mut self) -> Result<u64, UltrasonicError> {
+        self.trigger.set_high()?;
+        Ets::delay_us(TRIGGER_PULSE_US);
+        self.trigger.set_low()?;
+
+        let start = self.timer.counter()?;
+        let timed_out = |now: u64| now - start > ECHO_TIMEOUT_US;
+
+        // The sensor takes a few hundred µs to raise ECHO after the trigger.
+        let rise = loop {
+            let now = self.timer.counter()?;
+            if self.echo.is_high() {
+                break now;
+            }
+            if timed_out(now) {
+                return Err(UltrasonicError::Timeout);
+            }
+        };
+
+        loop {
+            let now = self.timer.counter()?;
+            if self.echo.is_low() {
+                return Ok(now - rise);
+            }
+            if timed_out(now) {
+                return Err(UltrasonicError::Timeout);
+            }
+        }
+    }
+}
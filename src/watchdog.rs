@@ -0,0 +1,82 @@
+// Resets the chip when a task stops making progress, e.g. because it is
+// stuck behind a misbehaving ISR.
+//
+// Wraps the ESP IDF task watchdog (TWDT): every task added to it has to
+// feed() it at least once per timeout, otherwise the watchdog panics and
+// the chip reboots. Sleeping is fine as long as the task wakes up and
+// feeds in time, so a loop such as
+//
+//     loop {
+//         watchdog.feed()?;
+//         do_work();
+//         thread::sleep(Duration::from_secs(1));
+//     }
+//
+// needs a timeout comfortably longer than one iteration, sleep included.
+// Blocking in thread::sleep() also lets the IDLE task run, which the IDF
+// watches on its own when configured to.
+
+use std::{ptr, time::Duration};
+
+use esp_idf_svc::sys::{
+    esp, esp_task_wdt_add, esp_task_wdt_config_t, esp_task_wdt_delete, esp_task_wdt_init,
+    esp_task_wdt_reconfigure, esp_task_wdt_reset, EspError, ESP_ERR_INVALID_STATE,
+};
+
+/// Handle to the task watchdog, see init().
+#[derive(Debug)]
+pub struct Watchdog {
+    timeout: Duration,
+}
+
+impl Watchdog {
+    /// Configures the task watchdog to reboot the chip once a watched task
+    /// went `timeout` without feeding it.
+    ///
+    /// The IDF usually starts the watchdog at boot already, in which case
+    /// it is reconfigured. Only the tasks added with add_current_task() are
+    /// watched afterwards.
+    pub fn init(timeout: Duration) -> Result<Self, EspError> {
+        let config = esp_task_wdt_config_t {
+            timeout_ms: u32::try_from(timeout.as_millis()).unwrap_or(u32::MAX),
+            idle_core_mask: 0,
+            trigger_panic: true,
+        };
+
+        // SAFETY: esp_task_wdt_init() is an ESP32 ABI call reading `config`.
+        match esp!(unsafe { esp_task_wdt_init(&config) }) {
+            Err(e) if e.code() == ESP_ERR_INVALID_STATE => {
+                // SAFETY: esp_task_wdt_reconfigure() is an ESP32 ABI call reading `config`.
+                esp!(unsafe { esp_task_wdt_reconfigure(&config) })?
+            }
+            result => result?,
+        }
+
+        Ok(Self { timeout })
+    }
+
+    /// Time a watched task may go without feeding the watchdog.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Starts watching the calling task, which must feed() from now on.
+    pub fn add_current_task(&self) -> Result<(), EspError> {
+        // SAFETY: esp_task_wdt_add() is an ESP32 ABI call, NULL is the calling task.
+        esp!(unsafe { esp_task_wdt_add(ptr::null_mut()) })
+    }
+
+    /// Stops watching the calling task.
+    pub fn remove_current_task(&self) -> Result<(), EspError> {
+        // SAFETY: esp_task_wdt_delete() is an ESP32 ABI call, NULL is the calling task.
+        esp!(unsafe { esp_task_wdt_delete(ptr::null_mut()) })
+    }
+
+    /// Tells the watchdog the calling task is still alive.
+    ///
+    /// Fails with ESP_ERR_NOT_FOUND if the task wasn't added.
+    pub fn feed(&self) -> Result<(), EspError> {
+        // SAFETY: esp_task_wdt_reset() is an ESP32 ABI call.
+        esp!(unsafe { esp_task_wdt_reset() })
+    }
+}
@@ -0,0 +1,1095 @@
+// Helpers for keeping an ESP32 WiFi station connected.
+
+use std::{
+    collections::HashSet,
+    fmt::{self, Write},
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use esp_idf_svc::{
+    eventloop::{EspSystemEventLoop, EspSystemSubscription},
+    hal::{modem::WifiModemPeripheral, peripheral::Peripheral},
+    handle::RawHandle,
+    ipv4::{self, IpInfo, Ipv4Addr, Mask, Subnet},
+    netif::{EspNetif, IpEvent, NetifConfiguration},
+    nvs::{EspDefaultNvsPartition, EspNvs},
+    sys::{
+        esp_wifi_ap_get_sta_list, esp_wifi_get_mode, esp_wifi_get_ps, esp_wifi_set_mode,
+        esp_wifi_set_ps, esp_wifi_sta_get_ap_info, wifi_ap_record_t, wifi_mode_t,
+        wifi_mode_t_WIFI_MODE_AP, wifi_mode_t_WIFI_MODE_APSTA, wifi_mode_t_WIFI_MODE_MAX,
+        wifi_mode_t_WIFI_MODE_NAN, wifi_mode_t_WIFI_MODE_NULL, wifi_mode_t_WIFI_MODE_STA,
+        wifi_ps_type_t, wifi_ps_type_t_WIFI_PS_MAX_MODEM, wifi_ps_type_t_WIFI_PS_MIN_MODEM,
+        wifi_ps_type_t_WIFI_PS_NONE, wifi_sta_list_t, EspError, ESP_ERR_INVALID_ARG,
+        ESP_ERR_INVALID_RESPONSE, ESP_ERR_NVS_NOT_FOUND, ESP_ERR_TIMEOUT, ESP_ERR_WIFI_NOT_CONNECT,
+    },
+    timer::EspTaskTimerService,
+    wifi::{
+        AccessPointConfiguration, AccessPointInfo, AsyncWifi, AuthMethod, BlockingWifi,
+        Configuration, EspWifi, WifiEvent,
+    },
+};
+
+use crate::error::Result;
+
+/// Delay before the first retry, doubled on every failed attempt.
+pub const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Upper bound for the delay between two attempts.
+pub const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// How long connect_with_retry() gives each attempt to associate, long
+/// enough for a WPA2 handshake with a slow AP.
+pub const ATTEMPT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// WPA2 refuses passphrases shorter than this.
+pub(crate) const MIN_PASSWORD_LEN: usize = 8;
+
+// Where store_credentials() keeps the network to join.
+const CREDENTIALS_NAMESPACE: &str = "wifi";
+const SSID_KEY: &str = "ssid";
+const PASSWORD_KEY: &str = "pwd";
+
+// How often is_connected() is polled while waiting on an attempt.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// How long disconnect() and stop() wait for the driver to confirm.
+const TEARDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Width of the SSID column of log_scan_results(), longer ones are cut.
+const SSID_COLUMN: usize = 20;
+
+/// How long to wait after the given (0 based) failed attempt:
+/// 1s, 2s, 4s, 8s, 16s and then 30s for every attempt after that.
+pub fn backoff_delay(attempt: u32) -> Duration {
+    INITIAL_BACKOFF
+        .checked_mul(1 << attempt.min(31))
+        .map_or(MAX_BACKOFF, |delay| delay.min(MAX_BACKOFF))
+}
+
+/// Connects to the configured AP, retrying up to `max_attempts` times.
+///
+/// Each attempt gets ATTEMPT_TIMEOUT to associate before it's abandoned.
+/// Whatever made it fail, the next one starts after backoff_delay(). Returns
+/// the last connect() error, or ESP_ERR_TIMEOUT if the AP never answered,
+/// once all attempts failed.
+pub fn connect_with_retry(wifi: &mut EspWifi, max_attempts: u32) -> Result<()> {
+    let mut last_error = EspError::from_infallible::<ESP_ERR_TIMEOUT>();
+
+    for attempt in 0..max_attempts {
+        if attempt > 0 {
+            let backoff = backoff_delay(attempt - 1);
+            log::info!("Retrying wifi in {:?}", backoff);
+            thread::sleep(backoff);
+        }
+        log::info!(
+            "Connecting to wifi, attempt {}/{}...",
+            attempt + 1,
+            max_attempts
+        );
+
+        // connect() only starts the association, it doesn't wait for it.
+        match wifi.connect() {
+            Ok(()) => {
+                if wait_connected(wifi, ATTEMPT_TIMEOUT)? {
+                    log::info!("Wifi connected");
+                    return Ok(());
+                }
+                log::warn!("Wifi didn't connect within {:?}", ATTEMPT_TIMEOUT);
+                last_error = EspError::from_infallible::<ESP_ERR_TIMEOUT>();
+
+                // Abort the pending attempt before starting a new one.
+                let _ = wifi.disconnect();
+            }
+            Err(e) => {
+                log::warn!("Wifi connect failed: {}", e);
+                last_error = e;
+            }
+        }
+    }
+
+    Err(last_error.into())
+}
+
+/// Reconnects with connect_with_retry() if the connection has dropped.
+pub fn ensure_connected(wifi: &mut EspWifi, max_attempts: u32) -> Result<()> {
+    if wifi.is_connected()? {
+        return Ok(());
+    }
+
+    log::warn!("Wifi connection lost");
+    connect_with_retry(wifi, max_attempts)
+}
+
+/// Checks the connection every `check_interval` and reconnects whenever it
+/// drops. Only returns if a reconnection runs out of attempts.
+pub fn maintain_connection(
+    wifi: &mut EspWifi,
+    max_attempts: u32,
+    check_interval: Duration,
+) -> Result<()> {
+    loop {
+        ensure_connected(wifi, max_attempts)?;
+        thread::sleep(check_interval);
+    }
+}
+
+// Waits up to `timeout` for the station to connect.
+fn wait_connected(wifi: &EspWifi, timeout: Duration) -> Result<bool> {
+    let start = Instant::now();
+    while start.elapsed() < timeout {
+        if wifi.is_connected()? {
+            return Ok(true);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    Ok(wifi.is_connected()?)
+}
+
+// What wait_for_ip() listens for on the system event loop.
+enum ConnectionEvent {
+    Connected,
+    // The raw netif handle (as usize, to be Send) and the assigned address.
+    GotIp(usize, IpInfo),
+}
+
+/// Blocks until the station is connected and DHCP has assigned it an
+/// address, returning the assigned IP info.
+///
+/// Reacts to the StaConnected and DhcpIpAssigned events rather than polling,
+/// so it returns as soon as the address is known. Fails with
+/// ESP_ERR_TIMEOUT if that doesn't happen within `timeout`.
+pub fn wait_for_ip(
+    wifi: &EspWifi,
+    sysloop: &EspSystemEventLoop,
+    timeout: Duration,
+) -> Result<IpInfo> {
+    let (tx, rx) = mpsc::channel();
+
+    // The subscriptions stay active until they're dropped on return.
+    let wifi_tx = tx.clone();
+    let _wifi_subscription = sysloop.subscribe::<WifiEvent, _>(move |event| {
+        if let WifiEvent::StaConnected = event {
+            let _ = wifi_tx.send(ConnectionEvent::Connected);
+        }
+    })?;
+    let _ip_subscription = sysloop.subscribe::<IpEvent, _>(move |event| {
+        if let IpEvent::DhcpIpAssigned(assignment) = event {
+            let handle = assignment.netif_handle() as usize;
+            let _ = tx.send(ConnectionEvent::GotIp(handle, assignment.ip_info()));
+        }
+    })?;
+
+    // The events might have fired before we subscribed.
+    if wifi.is_up()? {
+        return Ok(wifi.sta_netif().get_ip_info()?);
+    }
+
+    let sta_handle = wifi.sta_netif().handle() as usize;
+    let mut connected = wifi.is_connected()?;
+    let deadline = Instant::now() + timeout;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match rx.recv_timeout(remaining) {
+            Ok(ConnectionEvent::Connected) => {
+                log::info!("Wifi connected, waiting for an address...");
+                connected = true;
+            }
+            Ok(ConnectionEvent::GotIp(handle, ip_info)) if connected && handle == sta_handle => {
+                return Ok(ip_info);
+            }
+            Ok(ConnectionEvent::GotIp(..)) => {}
+            Err(_) => return Err(EspError::from_infallible::<ESP_ERR_TIMEOUT>().into()),
+        }
+    }
+}
+
+/// Keeps an on_wifi_event() or on_ip_event() callback subscribed for as
+/// long as it lives.
+///
+/// Dropping it unsubscribes the callback, waiting for it to return first
+/// if the event loop is running it right then, so nothing it captured is
+/// used after the drop.
+pub struct EventSubscription {
+    _subscription: EspSystemSubscription<'static>,
+}
+
+/// Runs `f` on every WifiEvent posted to `sysloop`, e.g. StaStarted,
+/// StaConnected and StaDisconnected, until the returned subscription is
+/// dropped.
+///
+/// `f` runs on the system event loop task, which posts the events the
+/// driver waits on too: it must not block for long, wait for another event
+/// or call into the wifi driver. Send what it sees to a thread for that,
+/// e.g. over an mpsc channel.
+pub fn on_wifi_event<F>(sysloop: &EspSystemEventLoop, f: F) -> Result<EventSubscription>
+where
+    F: Fn(WifiEvent) + Send + 'static,
+{
+    let subscription = sysloop.subscribe::<WifiEvent, _>(f)?;
+    Ok(EventSubscription {
+        _subscription: subscription,
+    })
+}
+
+/// Like on_wifi_event(), for the IpEvents: an address assigned by DHCP
+/// (got IP) or taken back (lost IP), on any interface. IpEvent::is_for()
+/// tells which.
+pub fn on_ip_event<F>(sysloop: &EspSystemEventLoop, f: F) -> Result<EventSubscription>
+where
+    F: Fn(IpEvent) + Send + 'static,
+{
+    let subscription = sysloop.subscribe::<IpEvent, _>(f)?;
+    Ok(EventSubscription {
+        _subscription: subscription,
+    })
+}
+
+/// Brings up a station on `modem` and returns it connected, with an
+/// address.
+///
+/// BlockingWifi isn't the default, EspWifi's start() and connect() only
+/// kick things off and return right away. This wraps it and waits for each
+/// of start, connect and DHCP (wait_netif_up()) in turn. There is no
+/// retry, use connect_with_retry() on wifi_mut() for that, and the driver
+/// is lost on failure, connect_timeout() leaves it to the caller.
+pub fn connect_blocking<'d, M: WifiModemPeripheral>(
+    modem: impl Peripheral<P = M> + 'd,
+    sysloop: EspSystemEventLoop,
+    nvs: EspDefaultNvsPartition,
+    config: &Configuration,
+) -> Result<BlockingWifi<EspWifi<'d>>> {
+    let wifi = EspWifi::new(modem, sysloop.clone(), Some(nvs))?;
+    let mut wifi = BlockingWifi::wrap(wifi, sysloop)?;
+
+    wifi.set_configuration(config)?;
+    wifi.start()?;
+    log::info!("Wifi started");
+    wifi.connect()?;
+    log::info!("Wifi connected, waiting for an address...");
+    wifi.wait_netif_up()?;
+
+    Ok(wifi)
+}
+
+/// Why connect_timeout() failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectError {
+    /// The station wasn't up within the timeout, e.g. the AP is out of
+    /// range or the password is wrong.
+    Timeout,
+    /// A wifi driver call failed.
+    Wifi(EspError),
+}
+
+impl fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectError::Timeout => write!(f, "Wifi connection timed out"),
+            ConnectError::Wifi(e) => write!(f, "Wifi driver call failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ConnectError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConnectError::Wifi(e) => Some(e),
+            ConnectError::Timeout => None,
+        }
+    }
+}
+
+impl From<EspError> for ConnectError {
+    fn from(e: EspError) -> Self {
+        match e.code() {
+            ESP_ERR_TIMEOUT => ConnectError::Timeout,
+            _ => ConnectError::Wifi(e),
+        }
+    }
+}
+
+/// Applies `config` to `wifi` and starts, connects and waits for DHCP like
+/// connect_blocking(), all of it within `timeout`.
+///
+/// Fails with ConnectError::Timeout if the station isn't up by then,
+/// after aborting the attempt. `wifi` stays usable, so the caller can retry,
+/// change the configuration or switch to provisioning.
+pub fn connect_timeout(
+    wifi: &mut BlockingWifi<EspWifi<'_>>,
+    config: &Configuration,
+    timeout: Duration,
+) -> core::result::Result<(), ConnectError> {
+    let deadline = Instant::now() + timeout;
+    let remaining = || Some(deadline.saturating_duration_since(Instant::now()));
+
+    wifi.set_configuration(config)?;
+    if !wifi.is_started()? {
+        // BlockingWifi's start() would wait without a timeout.
+        wifi.wifi_mut().start()?;
+        wifi.wifi_wait_while(|| wifi.is_started().map(|started| !started), remaining())?;
+        log::info!("Wifi started");
+    }
+
+    // Waits on the driver's events rather than polling is_connected().
+    let result = wifi
+        .wifi_mut()
+        .connect()
+        .and_then(|()| wifi.wifi_wait_while(|| wifi.is_connected().map(|up| !up), remaining()))
+        .and_then(|()| {
+            log::info!("Wifi connected, waiting for an address...");
+            wifi.ip_wait_while(|| wifi.is_up().map(|up| !up), remaining())
+        });
+
+    if let Err(e) = result {
+        // Abort the pending attempt, or drop an association DHCP never
+        // completed, so the next call starts over.
+        let _ = wifi.wifi_mut().disconnect();
+        if e.code() == ESP_ERR_TIMEOUT {
+            log::warn!("Wifi not up within {:?}", timeout);
+        }
+        return Err(e.into());
+    }
+
+    Ok(())
+}
+
+/// Like connect_blocking(), but awaits each of start, connect and DHCP
+/// instead of blocking, for code running on an async executor. Resolves
+/// once the station has an address.
+///
+/// Dropping the future before it resolves drops the half set up driver with
+/// it, which aborts the connection and stops the radio, so a timeout or a
+/// select() around it leaves nothing running. Failing does the same.
+pub async fn connect_async<'d, M: WifiModemPeripheral>(
+    modem: impl Peripheral<P = M> + 'd,
+    sysloop: EspSystemEventLoop,
+    timer_service: EspTaskTimerService,
+    nvs: EspDefaultNvsPartition,
+    config: &Configuration,
+) -> Result<AsyncWifi<EspWifi<'d>>> {
+    let wifi = EspWifi::new(modem, sysloop.clone(), Some(nvs))?;
+    let mut wifi = AsyncWifi::wrap(wifi, sysloop, timer_service)?;
+
+    wifi.set_configuration(config)?;
+    wifi.start().await?;
+    log::info!("Wifi started");
+    wifi.connect().await?;
+    log::info!("Wifi connected, waiting for an address...");
+    wifi.wait_netif_up().await?;
+
+    Ok(wifi)
+}
+
+/// Disconnects the station from its AP, returning once the driver reports
+/// it disconnected.
+///
+/// Does nothing if it wasn't connected, besides aborting a connection
+/// attempt still in progress. Fails with ESP_ERR_TIMEOUT if the driver
+/// doesn't confirm within 5 seconds.
+pub fn disconnect(wifi: &mut EspWifi, sysloop: &EspSystemEventLoop) -> Result<()> {
+    if !wifi.is_started()? {
+        return Ok(());
+    }
+    if !wifi.is_connected()? {
+        // Only fails when there's no attempt to abort.
+        let _ = wifi.disconnect();
+        return Ok(());
+    }
+
+    run_until_event(
+        sysloop,
+        |event| matches!(event, WifiEvent::StaDisconnected),
+        || wifi.disconnect(),
+    )?;
+    log::info!("Wifi disconnected");
+    Ok(())
+}
+
+/// Stops the wifi driver, e.g. to save power before deep sleeping,
+/// returning once it reports it stopped. A connected station disconnects
+/// from its AP first.
+///
+/// Does nothing if it wasn't started. Fails with ESP_ERR_TIMEOUT if the
+/// driver doesn't confirm within 5 seconds.
+pub fn stop(wifi: &mut EspWifi, sysloop: &EspSystemEventLoop) -> Result<()> {
+    if !wifi.is_started()? {
+        return Ok(());
+    }
+    disconnect(wifi, sysloop)?;
+
+    run_until_event(
+        sysloop,
+        |event| matches!(event, WifiEvent::StaStopped | WifiEvent::ApStopped),
+        || wifi.stop(),
+    )?;
+    log::info!("Wifi stopped");
+    Ok(())
+}
+
+// Runs `action` and waits up to TEARDOWN_TIMEOUT for the event it causes.
+fn run_until_event(
+    sysloop: &EspSystemEventLoop,
+    done: fn(&WifiEvent) -> bool,
+    action: impl FnOnce() -> core::result::Result<(), EspError>,
+) -> Result<()> {
+    let (tx, rx) = mpsc::channel();
+
+    // Subscribed before running the action, so the event can't be missed.
+    let _subscription = sysloop.subscribe::<WifiEvent, _>(move |event| {
+        if done(&event) {
+            let _ = tx.send(());
+        }
+    })?;
+    action()?;
+
+    rx.recv_timeout(TEARDOWN_TIMEOUT)
+        .map_err(|_| EspError::from_infallible::<ESP_ERR_TIMEOUT>().into())
+}
+
+/// What a WifiConnectFsm poll() found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectState {
+    /// Still starting, associating or waiting for DHCP.
+    Connecting,
+    /// Connected, with the address DHCP assigned.
+    GotIp(Ipv4Addr),
+    /// Gave up, with the failed call's error or ESP_ERR_TIMEOUT.
+    Failed(EspError),
+}
+
+// Where a WifiConnectFsm is at.
+#[derive(Debug, Clone, Copy)]
+enum ConnectStep {
+    Start,
+    WaitStarted,
+    WaitConnected,
+    WaitIp,
+    Done(ConnectState),
+}
+
+/// Connects a station one non-blocking step at a time, for main loops that
+/// have other work to do in the meantime.
+///
+/// Each poll() checks on the current step (start, connect, then DHCP) and
+/// moves on to the next once it's done, so poll it every few milliseconds
+/// to hundreds of milliseconds. Once it reports GotIp or Failed it keeps
+/// reporting that.
+pub struct WifiConnectFsm<'d> {
+    wifi: EspWifi<'d>,
+    step: ConnectStep,
+    timeout: Duration,
+    // Set by the first poll, the timeout counts from there.
+    started_at: Option<Instant>,
+}
+
+impl<'d> WifiConnectFsm<'d> {
+    /// Applies `config` to `wifi`, the connection starts with the first
+    /// poll() and fails if it isn't up within `timeout`.
+    pub fn new(mut wifi: EspWifi<'d>, config: &Configuration, timeout: Duration) -> Result<Self> {
+        wifi.set_configuration(config)?;
+
+        Ok(Self {
+            wifi,
+            step: ConnectStep::Start,
+            timeout,
+            started_at: None,
+        })
+    }
+
+    /// Advances the connection if the current step is done, never blocks.
+    pub fn poll(&mut self) -> ConnectState {
+        if let ConnectStep::Done(state) = self.step {
+            return state;
+        }
+
+        let started_at = *self.started_at.get_or_insert_with(Instant::now);
+        let state = match self.advance() {
+            Ok(ConnectStep::Done(state)) => state,
+            Ok(step) if started_at.elapsed() >= self.timeout => {
+                log::warn!("Wifi not up within {:?}, stuck at {:?}", self.timeout, step);
+                ConnectState::Failed(EspError::from_infallible::<ESP_ERR_TIMEOUT>())
+            }
+            Ok(step) => {
+                self.step = step;
+                return ConnectState::Connecting;
+            }
+            Err(e) => ConnectState::Failed(e),
+        };
+
+        self.step = ConnectStep::Done(state);
+        state
+    }
+
+    pub fn wifi(&self) -> &EspWifi<'d> {
+        &self.wifi
+    }
+
+    pub fn wifi_mut(&mut self) -> &mut EspWifi<'d> {
+        &mut self.wifi
+    }
+
+    /// Gives the wifi back, e.g. to keep it once connected.
+    pub fn into_inner(self) -> EspWifi<'d> {
+        self.wifi
+    }
+
+    // The step to be at after checking on the current one.
+    fn advance(&mut self) -> core::result::Result<ConnectStep, EspError> {
+        Ok(match self.step {
+            ConnectStep::Start => {
+                // Only kicks the start off, StaStarted follows later.
+                self.wifi.start()?;
+                log::info!("Wifi starting...");
+                ConnectStep::WaitStarted
+            }
+            ConnectStep::WaitStarted if self.wifi.is_started()? => {
+                self.wifi.connect()?;
+                log::info!("Wifi started, connecting...");
+                ConnectStep::WaitConnected
+            }
+            ConnectStep::WaitConnected if self.wifi.is_connected()? => {
+                log::info!("Wifi connected, waiting for an address...");
+                ConnectStep::WaitIp
+            }
+            ConnectStep::WaitIp if self.wifi.is_up()? => {
+                let ip = self.wifi.sta_netif().get_ip_info()?.ip;
+                ConnectStep::Done(ConnectState::GotIp(ip))
+            }
+            step => step,
+        })
+    }
+}
+
+/// The WiFi modes of operation, see `wifi_mode_t`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WifiMode {
+    /// Access Point.
+    Ap,
+    /// Station, i.e. client mode.
+    Sta,
+    /// Access Point and Station at the same time.
+    ApSta,
+    /// Wi-Fi Aware (NAN).
+    Nan,
+    /// Not a real mode, marks the end of `wifi_mode_t`.
+    Max,
+    /// WiFi is off.
+    Null,
+    /// A value this crate doesn't know about.
+    Unknown(u32),
+}
+
+impl From<wifi_mode_t> for WifiMode {
+    fn from(mode: wifi_mode_t) -> Self {
+        match mode {
+            wifi_mode_t_WIFI_MODE_AP => WifiMode::Ap,
+            wifi_mode_t_WIFI_MODE_STA => WifiMode::Sta,
+            wifi_mode_t_WIFI_MODE_APSTA => WifiMode::ApSta,
+            wifi_mode_t_WIFI_MODE_NAN => WifiMode::Nan,
+            wifi_mode_t_WIFI_MODE_MAX => WifiMode::Max,
+            wifi_mode_t_WIFI_MODE_NULL => WifiMode::Null,
+            other => WifiMode::Unknown(other),
+        }
+    }
+}
+
+impl From<WifiMode> for wifi_mode_t {
+    fn from(mode: WifiMode) -> Self {
+        match mode {
+            WifiMode::Ap => wifi_mode_t_WIFI_MODE_AP,
+            WifiMode::Sta => wifi_mode_t_WIFI_MODE_STA,
+            WifiMode::ApSta => wifi_mode_t_WIFI_MODE_APSTA,
+            WifiMode::Nan => wifi_mode_t_WIFI_MODE_NAN,
+            WifiMode::Max => wifi_mode_t_WIFI_MODE_MAX,
+            WifiMode::Null => wifi_mode_t_WIFI_MODE_NULL,
+            WifiMode::Unknown(other) => other,
+        }
+    }
+}
+
+impl fmt::Display for WifiMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WifiMode::Ap => write!(f, "AP"),
+            WifiMode::Sta => write!(f, "STA"),
+            WifiMode::ApSta => write!(f, "APSTA"),
+            WifiMode::Nan => write!(f, "NAN"),
+            WifiMode::Max => write!(f, "MAX"),
+            WifiMode::Null => write!(f, "NULL"),
+            WifiMode::Unknown(other) => write!(f, "unexpected ({})", other),
+        }
+    }
+}
+
+/// Reads the current WiFi mode of operation.
+///
+/// esp_wifi_get_mode() only fails when the WiFi driver isn't initialized,
+/// which is reported as WifiMode::Null.
+pub fn current_wifi_mode() -> WifiMode {
+    let mut mode: wifi_mode_t = wifi_mode_t_WIFI_MODE_NULL;
+    // SAFETY: esp_wifi_get_mode() is an ESP32 ABI call writing to `mode`.
+    match EspError::convert(unsafe { esp_wifi_get_mode(&mut mode) }) {
+        Ok(()) => mode.into(),
+        Err(_) => WifiMode::Null,
+    }
+}
+
+/// Reads and changes the mode of the WiFi driver of an EspWifi.
+///
+/// Borrowing the EspWifi keeps the driver initialized meanwhile. Its
+/// set_configuration() sets the mode too, from the kind of configuration,
+/// so set() is for switching without reconfiguring, e.g. to turn the AP of
+/// an APSTA configuration off and back on.
+pub struct WifiModeController<'a, 'd> {
+    wifi: &'a mut EspWifi<'d>,
+}
+
+impl<'a, 'd> WifiModeController<'a, 'd> {
+    pub fn new(wifi: &'a mut EspWifi<'d>) -> Self {
+        Self { wifi }
+    }
+
+    /// The current mode, see current_wifi_mode().
+    pub fn get(&self) -> WifiMode {
+        current_wifi_mode()
+    }
+
+    /// Switches the driver to `mode`, starting and stopping the interfaces
+    /// it needs if the driver is started.
+    ///
+    /// Fails with ESP_ERR_INVALID_ARG for WifiMode::Max or an Unknown mode.
+    pub fn set(&mut self, mode: WifiMode) -> core::result::Result<(), EspError> {
+        if matches!(mode, WifiMode::Max | WifiMode::Unknown(_)) {
+            return Err(EspError::from_infallible::<ESP_ERR_INVALID_ARG>());
+        }
+
+        // SAFETY: esp_wifi_set_mode() is an ESP32 ABI call.
+        EspError::convert(unsafe { esp_wifi_set_mode(mode.into()) })?;
+        log::info!("Wifi mode set to {}", mode);
+        Ok(())
+    }
+
+    /// Same as set(WifiMode::Sta).
+    pub fn switch_to_sta(&mut self) -> core::result::Result<(), EspError> {
+        self.set(WifiMode::Sta)
+    }
+
+    /// Same as set(WifiMode::Ap).
+    pub fn switch_to_ap(&mut self) -> core::result::Result<(), EspError> {
+        self.set(WifiMode::Ap)
+    }
+
+    pub fn wifi(&self) -> &EspWifi<'d> {
+        self.wifi
+    }
+}
+
+/// How much the station lets the modem sleep between beacons, see
+/// `wifi_ps_type_t`.
+///
+/// The radio wakes up for the AP's beacons and the traffic buffered for the
+/// station, so sleeping longer saves power but delays what the AP sends,
+/// and cuts the throughput.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerSave {
+    /// The radio stays on: the lowest latency and the most current.
+    None,
+    /// Wakes for every DTIM beacon, usually every 100-300 ms. ESP IDF's
+    /// default, and what a station with BLE running needs.
+    MinModem,
+    /// Wakes every `listen_interval` beacons of the station configuration
+    /// (3 by default), the least current but packets to the station can
+    /// wait a second or more.
+    MaxModem,
+}
+
+impl From<PowerSave> for wifi_ps_type_t {
+    fn from(mode: PowerSave) -> Self {
+        match mode {
+            PowerSave::None => wifi_ps_type_t_WIFI_PS_NONE,
+            PowerSave::MinModem => wifi_ps_type_t_WIFI_PS_MIN_MODEM,
+            PowerSave::MaxModem => wifi_ps_type_t_WIFI_PS_MAX_MODEM,
+        }
+    }
+}
+
+/// Sets the modem sleep mode of the station, it can change at any time
+/// once the driver is up. `wifi` is only borrowed to be sure it is.
+pub fn set_power_save(_wifi: &mut EspWifi, mode: PowerSave) -> Result<()> {
+    // SAFETY: esp_wifi_set_ps() is an ESP32 ABI call.
+    EspError::convert(unsafe { esp_wifi_set_ps(mode.into()) })?;
+    log::info!("Wifi power save set to {:?}", mode);
+    Ok(())
+}
+
+/// The modem sleep mode of the station.
+pub fn power_save(_wifi: &EspWifi) -> Result<PowerSave> {
+    let mut mode: wifi_ps_type_t = wifi_ps_type_t_WIFI_PS_NONE;
+    // SAFETY: esp_wifi_get_ps() is an ESP32 ABI call writing to `mode`.
+    EspError::convert(unsafe { esp_wifi_get_ps(&mut mode) })?;
+
+    match mode {
+        wifi_ps_type_t_WIFI_PS_NONE => Ok(PowerSave::None),
+        wifi_ps_type_t_WIFI_PS_MIN_MODEM => Ok(PowerSave::MinModem),
+        wifi_ps_type_t_WIFI_PS_MAX_MODEM => Ok(PowerSave::MaxModem),
+        _ => Err(EspError::from_infallible::<ESP_ERR_INVALID_RESPONSE>().into()),
+    }
+}
+
+/// Configures and starts a softAP on `channel`.
+///
+/// An empty `password` creates an open network, otherwise WPA2 is used and
+/// the password must be at least 8 characters long. Invalid SSIDs or
+/// passwords are rejected with ESP_ERR_INVALID_ARG.
+pub fn start_ap(wifi: &mut EspWifi, ssid: &str, password: &str, channel: u8) -> Result<()> {
+    let invalid_arg = || EspError::from_infallible::<ESP_ERR_INVALID_ARG>();
+
+    let auth_method = match password.len() {
+        0 => AuthMethod::None,
+        len if len < MIN_PASSWORD_LEN => return Err(invalid_arg().into()),
+        _ => AuthMethod::WPA2Personal,
+    };
+
+    wifi.set_configuration(&Configuration::AccessPoint(AccessPointConfiguration {
+        ssid: ssid.try_into().map_err(|_| invalid_arg())?,
+        password: password.try_into().map_err(|_| invalid_arg())?,
+        channel,
+        auth_method,
+        ..Default::default()
+    }))?;
+    wifi.start()?;
+
+    log::info!(
+        "Started AP '{}' on channel {} ({:?})",
+        ssid,
+        channel,
+        auth_method
+    );
+    Ok(())
+}
+
+/// MAC addresses of the stations currently connected to our softAP.
+pub fn connected_clients() -> Result<Vec<[u8; 6]>> {
+    let mut list = wifi_sta_list_t::default();
+    // SAFETY: esp_wifi_ap_get_sta_list() is an ESP32 ABI call writing to `list`.
+    EspError::convert(unsafe { esp_wifi_ap_get_sta_list(&mut list) })?;
+
+    Ok(list
+        .sta
+        .iter()
+        .take(list.num as usize)
+        .map(|sta| sta.mac)
+        .collect())
+}
+
+/// Scans for APs and returns them strongest first.
+///
+/// Networks seen on several BSSIDs are only listed once, with their
+/// strongest signal. Hidden networks (empty SSID) are all kept. APs weaker
+/// than `min_rssi` dBm are dropped when it's set.
+pub fn scan_sorted(wifi: &mut EspWifi, min_rssi: Option<i8>) -> Result<Vec<AccessPointInfo>> {
+    let mut aps = wifi.scan()?;
+    aps.sort_by(|a, b| b.signal_strength.cmp(&a.signal_strength));
+
+    let mut seen = HashSet::new();
+    aps.retain(|ap| {
+        min_rssi.map_or(true, |min| ap.signal_strength >= min)
+            && (ap.ssid.is_empty() || seen.insert(ap.ssid.clone()))
+    });
+
+    Ok(aps)
+}
+
+/// Scans for `ssid` and returns its strongest AP, if any is in range.
+pub fn scan_for(wifi: &mut EspWifi, ssid: &str) -> Result<Option<AccessPointInfo>> {
+    Ok(scan_sorted(wifi, None)?
+        .into_iter()
+        .find(|ap| ap.ssid.as_str() == ssid))
+}
+
+/// Logs `aps` as a table, one row per AP with its SSID, channel, RSSI,
+/// auth method and BSSID, in the order given.
+///
+/// SSIDs longer than the column are cut short with "...", hidden networks
+/// show as "<hidden>".
+pub fn log_scan_results(aps: &[AccessPointInfo]) {
+    log::info!(
+        "{:<w$}  {:>2}  {:>4}  {:<9}  BSSID",
+        "SSID",
+        "CH",
+        "RSSI",
+        "AUTH",
+        w = SSID_COLUMN
+    );
+    for ap in aps {
+        let [a, b, c, d, e, f] = ap.bssid;
+        log::info!(
+            "{:<w$}  {:>2}  {:>4}  {:<9}  {:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+            ssid_cell(&ap.ssid),
+            ap.channel,
+            ap.signal_strength,
+            auth_name(ap.auth_method),
+            a,
+            b,
+            c,
+            d,
+            e,
+            f,
+            w = SSID_COLUMN
+        );
+    }
+    log::info!("{} APs found", aps.len());
+}
+
+// `ssid` fitted in SSID_COLUMN characters.
+fn ssid_cell(ssid: &str) -> String {
+    if ssid.is_empty() {
+        return "<hidden>".into();
+    }
+    if ssid.chars().count() <= SSID_COLUMN {
+        return ssid.into();
+    }
+
+    let mut cell: String = ssid.chars().take(SSID_COLUMN - 3).collect();
+    cell.push_str("...");
+    cell
+}
+
+// Short enough for the AUTH column of log_scan_results().
+fn auth_name(auth_method: Option<AuthMethod>) -> &'static str {
+    match auth_method {
+        Some(AuthMethod::None) => "open",
+        Some(AuthMethod::WEP) => "WEP",
+        Some(AuthMethod::WPA) => "WPA",
+        Some(AuthMethod::WPA2Personal) => "WPA2",
+        Some(AuthMethod::WPAWPA2Personal) => "WPA/WPA2",
+        Some(AuthMethod::WPA2Enterprise) => "WPA2-EAP",
+        Some(AuthMethod::WPA3Personal) => "WPA3",
+        Some(AuthMethod::WPA2WPA3Personal) => "WPA2/WPA3",
+        Some(AuthMethod::WAPIPersonal) => "WAPI",
+        None => "?",
+    }
+}
+
+/// Gives the station the fixed address `ip` instead of asking DHCP.
+///
+/// Call it before connecting. Fails with ESP_ERR_INVALID_ARG if `netmask`
+/// isn't a valid mask or `ip` isn't in the same subnet as `gateway`.
+pub fn configure_static_ip(
+    wifi: &mut EspWifi,
+    ip: Ipv4Addr,
+    gateway: Ipv4Addr,
+    netmask: Ipv4Addr,
+    dns: Option<Ipv4Addr>,
+) -> Result<()> {
+    let invalid_arg = || EspError::from_infallible::<ESP_ERR_INVALID_ARG>();
+
+    let mask = Mask::try_from(netmask).map_err(|_| invalid_arg())?;
+    let bits = u32::from(netmask);
+    if ip == gateway || u32::from(ip) & bits != u32::from(gateway) & bits {
+        log::error!("{} is not a valid address in {}/{}", ip, gateway, mask);
+        return Err(invalid_arg().into());
+    }
+
+    let netif = EspNetif::new_with_conf(&NetifConfiguration {
+        ip_configuration: ipv4::Configuration::Client(ipv4::ClientConfiguration::Fixed(
+            ipv4::ClientSettings {
+                ip,
+                subnet: Subnet { gateway, mask },
+                dns,
+                secondary_dns: None,
+            },
+        )),
+        ..NetifConfiguration::wifi_default_client()
+    })?;
+
+    // The previous, DHCP based, netif is dropped here.
+    wifi.swap_netif_sta(netif)?;
+    Ok(())
+}
+
+/// Saves the network to join in NVS, for load_credentials() to find it
+/// after a reboot.
+pub fn store_credentials(nvs: &EspDefaultNvsPartition, ssid: &str, pwd: &str) -> Result<()> {
+    let mut storage = EspNvs::new(nvs.clone(), CREDENTIALS_NAMESPACE, true)?;
+    storage.set_str(SSID_KEY, ssid)?;
+    storage.set_str(PASSWORD_KEY, pwd)?;
+    Ok(())
+}
+
+/// Returns the (ssid, password) saved by store_credentials().
+///
+/// If NVS holds none, falls back to the WIFI_SSID / WIFI_PWD environment
+/// variables set at build time, or None if those weren't set either.
+pub fn load_credentials(nvs: &EspDefaultNvsPartition) -> Option<(String, String)> {
+    let stored = stored_credentials(nvs).unwrap_or_else(|e| {
+        log::warn!("Failed to read wifi credentials from NVS: {}", e);
+        None
+    });
+
+    stored.or_else(|| {
+        let ssid = option_env!("WIFI_SSID")?;
+        Some((
+            ssid.into(),
+            option_env!("WIFI_PWD").unwrap_or_default().into(),
+        ))
+    })
+}
+
+// Credentials saved in NVS only, without the build time fallback.
+pub(crate) fn stored_credentials(nvs: &EspDefaultNvsPartition) -> Result<Option<(String, String)>> {
+    // The namespace only exists once something was written to it.
+    let storage = match EspNvs::new(nvs.clone(), CREDENTIALS_NAMESPACE, false) {
+        Ok(storage) => storage,
+        Err(e) if e.code() == ESP_ERR_NVS_NOT_FOUND => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    // Sized for the longest SSID / passphrase WiFi allows, plus the NUL.
+    let mut ssid_buf = [0u8; 33];
+    let mut pwd_buf = [0u8; 65];
+    let ssid = storage.get_str(SSID_KEY, &mut ssid_buf)?;
+    let pwd = storage.get_str(PASSWORD_KEY, &mut pwd_buf)?;
+
+    Ok(match (ssid, pwd) {
+        (Some(ssid), Some(pwd)) if !ssid.is_empty() => Some((ssid.into(), pwd.into())),
+        _ => None,
+    })
+}
+
+/// Signal strength of the AP the station is connected to, in dBm.
+///
+/// Fails with ESP_ERR_WIFI_NOT_CONNECT when not connected.
+pub fn current_rssi(wifi: &EspWifi) -> Result<i8> {
+    if !wifi.is_connected()? {
+        return Err(EspError::from_infallible::<ESP_ERR_WIFI_NOT_CONNECT>().into());
+    }
+
+    let mut ap_info = wifi_ap_record_t::default();
+    // SAFETY: esp_wifi_sta_get_ap_info() is an ESP32 ABI call writing to `ap_info`.
+    EspError::convert(unsafe { esp_wifi_sta_get_ap_info(&mut ap_info) })?;
+    Ok(ap_info.rssi)
+}
+
+/// A summary of the addresses of `net` for logging, one line each for the
+/// IP, gateway, netmask, DNS server and MAC.
+///
+/// Until DHCP assigned an address, the IP, gateway and netmask read
+/// "unassigned". A field that can't be read shows the error instead.
+pub fn format_netinfo(net: &EspNetif) -> String {
+    let mut summary = String::new();
+    // Writing to a String can't fail.
+    match net.get_ip_info() {
+        Ok(info) if info.ip.is_unspecified() => {
+            for name in ["IP", "Gateway", "Netmask"] {
+                let _ = writeln!(summary, "{}: unassigned", name);
+            }
+        }
+        Ok(info) => {
+            let _ = writeln!(summary, "IP: {}", info.ip);
+            let _ = writeln!(summary, "Gateway: {}", info.subnet.gateway);
+            let _ = writeln!(
+                summary,
+                "Netmask: {} (/{})",
+                Ipv4Addr::from(info.subnet.mask),
+                info.subnet.mask
+            );
+        }
+        Err(e) => {
+            let _ = writeln!(summary, "IP info: {}", e);
+        }
+    }
+
+    let dns = net.get_dns();
+    if dns.is_unspecified() {
+        let _ = writeln!(summary, "DNS: unassigned");
+    } else {
+        let _ = writeln!(summary, "DNS: {}", dns);
+    }
+
+    let _ = match net.get_mac() {
+        Ok([a, b, c, d, e, f]) => write!(
+            summary,
+            "MAC: {:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+            a, b, c, d, e, f
+        ),
+        Err(e) => write!(summary, "MAC: {}", e),
+    };
+
+    summary
+}
+
+/// Maps an RSSI in dBm to a 0-100% link quality.
+///
+/// Uses the usual linear curve: -100 dBm or worse is 0%, -50 dBm or better
+/// is 100%.
+pub fn signal_quality(rssi: i8) -> u8 {
+    (2 * (rssi as i16 + 100)).clamp(0, 100) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every mode ESP IDF knows, with its wifi_mode_t and Display form.
+    const MODES: [(WifiMode, wifi_mode_t, &str); 6] = [
+        (WifiMode::Null, wifi_mode_t_WIFI_MODE_NULL, "NULL"),
+        (WifiMode::Sta, wifi_mode_t_WIFI_MODE_STA, "STA"),
+        (WifiMode::Ap, wifi_mode_t_WIFI_MODE_AP, "AP"),
+        (WifiMode::ApSta, wifi_mode_t_WIFI_MODE_APSTA, "APSTA"),
+        (WifiMode::Nan, wifi_mode_t_WIFI_MODE_NAN, "NAN"),
+        (WifiMode::Max, wifi_mode_t_WIFI_MODE_MAX, "MAX"),
+    ];
+
+    #[test]
+    fn wifi_mode_from_each_known_constant() {
+        for (mode, raw, _) in MODES {
+            assert_eq!(WifiMode::from(raw), mode);
+            assert_eq!(wifi_mode_t::from(mode), raw);
+        }
+    }
+
+    #[test]
+    fn wifi_mode_from_an_unexpected_value() {
+        let raw = wifi_mode_t_WIFI_MODE_MAX + 37;
+        assert_eq!(WifiMode::from(raw), WifiMode::Unknown(raw));
+        assert_eq!(wifi_mode_t::from(WifiMode::Unknown(raw)), raw);
+    }
+
+    #[test]
+    fn wifi_mode_display() {
+        for (mode, _, shown) in MODES {
+            assert_eq!(mode.to_string(), shown);
+        }
+        assert_eq!(WifiMode::Unknown(42).to_string(), "unexpected (42)");
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let delays: Vec<u64> = (0..8).map(|n| backoff_delay(n).as_secs()).collect();
+        assert_eq!(delays, [1, 2, 4, 8, 16, 30, 30, 30]);
+    }
+
+    #[test]
+    fn backoff_stays_capped_for_huge_attempts() {
+        assert_eq!(backoff_delay(31), MAX_BACKOFF);
+        assert_eq!(backoff_delay(u32::MAX), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn signal_quality_scales_between_the_ends() {
+        assert_eq!(signal_quality(-75), 50);
+        assert_eq!(signal_quality(-60), 80);
+    }
+
+    #[test]
+    fn signal_quality_clamps_outside_the_range() {
+        assert_eq!(signal_quality(-100), 0);
+        assert_eq!(signal_quality(i8::MIN), 0);
+        assert_eq!(signal_quality(-50), 100);
+        assert_eq!(signal_quality(0), 100);
+        assert_eq!(signal_quality(i8::MAX), 100);
+    }
+}
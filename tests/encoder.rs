@@ -0,0 +1,123 @@
+// Host test of the encoder decoding, the cases of the encoder_selftest
+// example: a QuadratureDecoder over MockLevelSources is polled through
+// scripted turns, and the position it ends at checked. See the README for
+// running it off the chip.
+
+use buds::{
+    encoder::{Direction, EncoderConfig, EventMode, QuadratureDecoder},
+    levels::MockLevelSource,
+};
+
+// One clockwise turn through the whole gray code, back on the detent.
+const CW: [i8; 4] = [1, 2, 3, 0];
+const CCW: [i8; 4] = [3, 2, 1, 0];
+
+type MockDecoder = QuadratureDecoder<MockLevelSource, MockLevelSource>;
+
+// `turn` repeated `times` times.
+fn turns(turn: [i8; 4], times: usize) -> Vec<i8> {
+    turn.iter().copied().cycle().take(4 * times).collect()
+}
+
+// Each code of `codes` bouncing once back to the one before, then held for
+// 3 polls.
+fn bouncy(codes: &[i8]) -> Vec<i8> {
+    let mut previous = 0;
+    let mut bouncing = Vec::new();
+    for &code in codes {
+        bouncing.extend_from_slice(&[code, previous, code, code, code]);
+        previous = code;
+    }
+    bouncing
+}
+
+// A decoder starting on the detent, whose pins then read `codes`, one gray
+// code per poll.
+fn decoder(config: EncoderConfig, codes: &[i8]) -> MockDecoder {
+    // The inverse of greycode_from_bits().
+    let a_high = |code: &i8| matches!(code, 2 | 3);
+    let b_high = |code: &i8| matches!(code, 1 | 2);
+
+    // from_pins() reads the starting levels first.
+    let codes = || std::iter::once(&0).chain(codes.iter());
+    let a = MockLevelSource::new(codes().map(a_high).collect::<Vec<_>>());
+    let b = MockLevelSource::new(codes().map(b_high).collect::<Vec<_>>());
+    QuadratureDecoder::from_pins(a, b, config)
+}
+
+// Polls a decoder once per code of `codes`, returning the position it ends
+// at.
+fn run(config: EncoderConfig, codes: &[i8]) -> i32 {
+    let decoder = decoder(config, codes);
+    for _ in codes {
+        decoder.poll();
+    }
+    decoder.position()
+}
+
+#[test]
+fn clockwise() {
+    assert_eq!(run(EncoderConfig::new(), &turns(CW, 3)), 12);
+}
+
+#[test]
+fn counter_clockwise() {
+    assert_eq!(run(EncoderConfig::new(), &turns(CCW, 2)), -8);
+}
+
+#[test]
+fn bouncy_contacts() {
+    let codes = bouncy(&turns(CW, 2));
+    assert_eq!(run(EncoderConfig::new().debounce_samples(3), &codes), 8);
+}
+
+#[test]
+fn one_step_per_detent() {
+    // Three clicks, then partway to the next one and back.
+    let codes = [turns(CW, 3), vec![1, 2, 1, 0]].concat();
+    assert_eq!(run(EncoderConfig::new().steps_per_detent(4), &codes), 3);
+}
+
+#[test]
+fn bounded() {
+    assert_eq!(run(EncoderConfig::new().bounds(0, 10), &turns(CW, 4)), 10);
+}
+
+#[test]
+fn confirmed_reversal() {
+    let codes = [turns(CW, 1), turns(CCW, 1)].concat();
+    assert_eq!(run(EncoderConfig::new().confirm_steps(2), &codes), 0);
+}
+
+#[test]
+fn glitch_and_back() {
+    assert_eq!(run(EncoderConfig::new().confirm_steps(2), &[1, 2, 1, 2]), 2);
+}
+
+#[test]
+fn inverted() {
+    assert_eq!(run(EncoderConfig::new().invert(true), &turns(CW, 1)), -4);
+}
+
+#[test]
+fn accelerated() {
+    // Every step but the first counts 3 times.
+    let decoder = decoder(EncoderConfig::new(), &CW).with_accel_fn(|_| 3);
+    for _ in CW {
+        decoder.poll();
+    }
+    assert_eq!(decoder.position(), 1 + 3 * 3);
+}
+
+#[test]
+fn events_follow_the_turns() {
+    let codes = [turns(CW, 1), vec![3]].concat();
+    let decoder = decoder(EncoderConfig::new(), &codes);
+    let events: Vec<_> = decoder
+        .events(EventMode::NonBlocking)
+        .map(|event| (event.direction, event.position))
+        .collect();
+    let cw = Direction::Clockwise;
+    let ccw = Direction::CounterClockwise;
+    assert_eq!(events, [(cw, 1), (cw, 2), (cw, 3), (cw, 4), (ccw, 3)]);
+}